@@ -0,0 +1,58 @@
+use calib::{Event, EventCalendar};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// build `n` one hour events spread one every ten minutes from 01/01/2023
+fn events(n: i64) -> Vec<Event> {
+    let base = NaiveDate::from_ymd_opt(2023, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+
+    (0..n)
+        .map(|i| {
+            let start = base + Duration::minutes(i * 10);
+            Event::new(i.to_string(), &start.date())
+                .set_end(start + Duration::hours(1))
+                .unwrap()
+                .set_start(start)
+                .unwrap()
+        })
+        .collect()
+}
+
+fn range_queries(c: &mut Criterion) {
+    let mut group = c.benchmark_group("events_in_range");
+
+    for n in [1_000, 10_000, 100_000] {
+        let evts = events(n);
+        let mut cal = EventCalendar::default();
+        evts.iter().cloned().for_each(|evt| {
+            cal.add_event(evt);
+        });
+
+        // a one day window in the middle of the calendar
+        let start: NaiveDateTime = evts[evts.len() / 2].start();
+        let end = start + Duration::days(1);
+
+        group.bench_with_input(BenchmarkId::new("interval_index", n), &n, |b, _| {
+            b.iter(|| {
+                cal.events_in_range(black_box(start), black_box(end))
+                    .count()
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("linear_scan", n), &n, |b, _| {
+            b.iter(|| {
+                evts.iter()
+                    .filter(|evt| evt.overlaps(black_box(start), black_box(end)))
+                    .count()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, range_queries);
+criterion_main!(benches);
@@ -0,0 +1,560 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use super::{Clock, Disambiguation, Event, EventCalendar, EventError};
+
+/// What happens when an alarm goes off, the ACTION of a VALARM
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum AlarmAction {
+    /// show a message (the default)
+    #[default]
+    Display,
+    /// send an email to the attendees
+    Email,
+    /// play a sound
+    Audio,
+}
+
+impl AlarmAction {
+    /// returns the name RFC 5545 uses for the action
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlarmAction::Display => "DISPLAY",
+            AlarmAction::Email => "EMAIL",
+            AlarmAction::Audio => "AUDIO",
+        }
+    }
+}
+
+impl fmt::Display for AlarmAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AlarmAction {
+    type Err = EventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "DISPLAY" => Ok(AlarmAction::Display),
+            "EMAIL" => Ok(AlarmAction::Email),
+            "AUDIO" => Ok(AlarmAction::Audio),
+            _ => Err(EventError::InvalidProperty("ACTION", s.to_string())),
+        }
+    }
+}
+
+/// When an alarm goes off, the TRIGGER of a VALARM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Trigger {
+    /// this long after each occurrence starts, negative for before it
+    Start(#[serde(with = "seconds")] Duration),
+    /// this long after each occurrence ends, negative for before it
+    End(#[serde(with = "seconds")] Duration),
+    /// at a fixed instant, once for the whole event
+    At(DateTime<Utc>),
+}
+
+/// A reminder attached to an event, like a VALARM
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Alarm {
+    action: AlarmAction,
+    trigger: Trigger,
+    #[serde(default)]
+    repeat: u32,
+    #[serde(default = "Duration::zero", with = "seconds")]
+    interval: Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
+impl Alarm {
+    /// create an alarm that goes off once
+    pub fn new(action: AlarmAction, trigger: Trigger) -> Self {
+        Alarm {
+            action,
+            trigger,
+            repeat: 0,
+            interval: Duration::zero(),
+            description: None,
+        }
+    }
+
+    /// returns what happens when the alarm goes off
+    pub fn action(&self) -> AlarmAction {
+        self.action
+    }
+
+    /// returns when the alarm goes off
+    pub fn trigger(&self) -> Trigger {
+        self.trigger
+    }
+
+    /// returns how many more times the alarm goes off after the first
+    pub fn repeat(&self) -> u32 {
+        self.repeat
+    }
+
+    /// returns the time between repeats
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Set/Change how many more times the alarm goes off after the first,
+    /// and how long to wait between each. The interval must be positive
+    /// unless the alarm does not repeat, and it can repeat at most
+    /// `i32::MAX` times
+    pub fn set_repeat(self, repeat: u32, interval: Duration) -> Result<Self, EventError> {
+        let interval = match repeat {
+            0 => Duration::zero(),
+            _ => interval,
+        };
+        let alarm = Alarm {
+            repeat,
+            interval,
+            ..self
+        };
+        alarm.check()?;
+        Ok(alarm)
+    }
+
+    /// a repeating alarm must wait between repeats
+    pub(crate) fn check(&self) -> Result<(), EventError> {
+        if i32::try_from(self.repeat).is_err() {
+            return Err(EventError::InvalidProperty(
+                "REPEAT",
+                self.repeat.to_string(),
+            ));
+        }
+        if self.repeat > 0 && self.interval <= Duration::zero() {
+            return Err(EventError::InvalidProperty(
+                "DURATION",
+                self.interval.to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// returns the message to show or send
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Set/Change/Clear the message to show or send
+    pub fn set_description(self, description: Option<String>) -> Self {
+        Alarm {
+            description,
+            ..self
+        }
+    }
+
+    /// returns every instant the alarm goes off for an occurrence that runs
+    /// from `start` to `end`, repeats included, up to the last one that can
+    /// be represented
+    pub fn fire_times(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        let first = match self.trigger {
+            Trigger::Start(offset) => start.checked_add_signed(offset),
+            Trigger::End(offset) => end.checked_add_signed(offset),
+            Trigger::At(at) => Some(at),
+        };
+        (0..=self.repeat).map_while(move |n| {
+            let wait = self.interval.checked_mul(i32::try_from(n).ok()?)?;
+            first?.checked_add_signed(wait)
+        })
+    }
+
+    /// the range of offsets from the start of an occurrence lasting
+    /// `duration` that this alarm goes off at, None for absolute triggers.
+    /// The last offset is TimeDelta::MAX if it is too far off to represent
+    fn offsets(&self, duration: Duration) -> Option<(Duration, Duration)> {
+        let first = match self.trigger {
+            Trigger::Start(offset) => offset,
+            Trigger::End(offset) => duration.checked_add(&offset).unwrap_or(Duration::MAX),
+            Trigger::At(_) => return None,
+        };
+        let last = i32::try_from(self.repeat)
+            .ok()
+            .and_then(|repeat| self.interval.checked_mul(repeat))
+            .and_then(|wait| first.checked_add(&wait))
+            .unwrap_or(Duration::MAX);
+        Some((first, last))
+    }
+
+    /// the last instant the alarm goes off at, once the first went off at
+    /// `at` and `repetition` repeats have gone off since
+    fn last_fire_time(&self, at: DateTime<Utc>, repetition: u32) -> DateTime<Utc> {
+        i32::try_from(self.repeat.saturating_sub(repetition))
+            .ok()
+            .and_then(|left| self.interval.checked_mul(left))
+            .and_then(|wait| at.checked_add_signed(wait))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+// durations are kept as whole seconds when serialized
+mod seconds {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(duration.num_seconds())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let seconds = i64::deserialize(deserializer)?;
+        Duration::try_seconds(seconds)
+            .ok_or_else(|| serde::de::Error::custom("duration out of range"))
+    }
+}
+
+/// Identifies one alarm of one occurrence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct AlarmKey {
+    event: Uuid,
+    recurrence_id: Option<NaiveDateTime>,
+    index: usize,
+}
+
+/// An alarm that has gone off, returned by [`AlarmEngine::due`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DueAlarm {
+    key: AlarmKey,
+    start: NaiveDateTime,
+    name: String,
+    alarm: Alarm,
+    at: DateTime<Utc>,
+    repetition: u32,
+    snoozed: bool,
+}
+
+impl DueAlarm {
+    /// returns the id of the event the alarm belongs to
+    pub fn event_id(&self) -> &Uuid {
+        &self.key.event
+    }
+
+    /// returns the original start of the occurrence the alarm is for, None
+    /// for events that do not recur and for absolute alarms of a series
+    pub fn recurrence_id(&self) -> Option<NaiveDateTime> {
+        self.key.recurrence_id
+    }
+
+    /// returns when the occurrence the alarm is for starts, as wall clock
+    /// time in the event's zone
+    pub fn occurrence_start(&self) -> NaiveDateTime {
+        self.start
+    }
+
+    /// returns the name of the event
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// returns the alarm itself
+    pub fn alarm(&self) -> &Alarm {
+        &self.alarm
+    }
+
+    /// returns the instant the alarm went off
+    pub fn at(&self) -> DateTime<Utc> {
+        self.at
+    }
+
+    /// returns 0 the first time the alarm goes off, then 1, 2... for repeats
+    pub fn repetition(&self) -> u32 {
+        self.repetition
+    }
+
+    /// returns true if the alarm went off again after being snoozed
+    pub fn is_snoozed(&self) -> bool {
+        self.snoozed
+    }
+}
+
+/// What the user did with an alarm, with the instant after which it no
+/// longer matters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AlarmState {
+    Dismissed(DateTime<Utc>),
+    Snoozed(DateTime<Utc>),
+}
+
+impl AlarmState {
+    fn until(&self) -> DateTime<Utc> {
+        match *self {
+            AlarmState::Dismissed(until) | AlarmState::Snoozed(until) => until,
+        }
+    }
+}
+
+/// One alarm of one occurrence, and the instants the occurrence starts and
+/// ends at
+struct Ringing<'a> {
+    key: AlarmKey,
+    evt: &'a Event,
+    occurrence_start: NaiveDateTime,
+    alarm: &'a Alarm,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+/// Finds the alarms of a calendar that have gone off, remembering which
+/// have already been reported and which were snoozed or dismissed
+///
+/// # Examples
+/// ```
+/// use calib::{Alarm, AlarmAction, AlarmEngine, Event, EventCalendar, ManualClock, Trigger};
+/// use chrono::{Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+///
+/// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+/// let standup = Event::new("Standup".into(), &date)
+///     .set_start_time(NaiveTime::from_hms_opt(9, 0, 0).unwrap())
+///     .unwrap()
+///     .add_alarm(Alarm::new(AlarmAction::Display, Trigger::Start(-Duration::minutes(10))));
+/// let mut cal = EventCalendar::default();
+/// cal.add_event(standup);
+///
+/// let mut engine = AlarmEngine::new(Utc.with_ymd_and_hms(2023, 1, 2, 8, 0, 0).unwrap());
+/// let now = ManualClock::new(Utc.with_ymd_and_hms(2023, 1, 2, 8, 55, 0).unwrap());
+/// let due = engine.due(&cal, &now);
+/// assert_eq!(due.len(), 1);
+/// assert_eq!(due[0].at(), Utc.with_ymd_and_hms(2023, 1, 2, 8, 50, 0).unwrap());
+///
+/// // alarms are only reported once
+/// assert!(engine.due(&cal, &now).is_empty());
+/// ```
+#[derive(Debug, Clone)]
+pub struct AlarmEngine {
+    checked: DateTime<Utc>,
+    floating: Tz,
+    states: BTreeMap<AlarmKey, AlarmState>,
+}
+
+impl AlarmEngine {
+    /// create an engine that reports alarms going off after `since`.
+    /// Floating events are read as UTC until a zone is set
+    pub fn new(since: DateTime<Utc>) -> Self {
+        AlarmEngine {
+            checked: since,
+            floating: Tz::UTC,
+            states: BTreeMap::new(),
+        }
+    }
+
+    /// Set/Change the zone floating events are read in
+    pub fn set_floating_zone(self, floating: Tz) -> Self {
+        AlarmEngine { floating, ..self }
+    }
+
+    /// returns the instant up to which alarms have been reported
+    pub fn checked_until(&self) -> DateTime<Utc> {
+        self.checked
+    }
+
+    /// return the alarms that have gone off since the last call, in the
+    /// order they went off, and remember that they have been reported.
+    /// Alarms snoozed or dismissed that can't go off any more are forgotten
+    pub fn due<C: Clock>(&mut self, cal: &EventCalendar, clock: &C) -> Vec<DueAlarm> {
+        let now = clock.now();
+        let due = self.pending(cal, now);
+        self.checked = self.checked.max(now);
+        let checked = self.checked;
+        self.states.retain(|_, state| state.until() > checked);
+        due
+    }
+
+    /// return the alarms that will go off after the last call to
+    /// [`AlarmEngine::due`] and up to `until`, without reporting them. The
+    /// first one tells a notifier how long it can sleep
+    pub fn pending(&self, cal: &EventCalendar, until: DateTime<Utc>) -> Vec<DueAlarm> {
+        let from = self.checked;
+        let mut found = Vec::new();
+        if until <= from {
+            return found;
+        }
+        let in_window = |at: &DateTime<Utc>| from < *at && *at <= until;
+
+        // absolute alarms go off once per event, relative ones for every
+        // occurrence starting close enough to the window
+        let mut span: Option<(Duration, Duration)> = None;
+        let events = cal
+            .events()
+            .chain(cal.events().flat_map(|evt| cal.overrides(evt.id())));
+        for evt in events {
+            for (index, alarm) in evt.alarms().iter().enumerate() {
+                let Some((lo, hi)) = alarm.offsets(evt.duration()) else {
+                    let Some((start, end)) = self.instants(evt, evt.start(), evt.end()) else {
+                        continue;
+                    };
+                    let key = AlarmKey {
+                        event: *evt.id(),
+                        recurrence_id: evt.recurrence_id(),
+                        index,
+                    };
+                    let ringing = Ringing {
+                        key,
+                        evt,
+                        occurrence_start: evt.start(),
+                        alarm,
+                        start,
+                        end,
+                    };
+                    self.collect(&mut found, ringing, (from, until));
+                    continue;
+                };
+                span = Some(match span {
+                    Some((min, max)) => (min.min(lo), max.max(hi)),
+                    None => (lo, hi),
+                });
+            }
+        }
+
+        if let Some((lo, hi)) = span {
+            // wall clock times are within a day of UTC
+            let day = Duration::days(1);
+            let start = hi
+                .checked_add(&day)
+                .and_then(|back| from.naive_utc().checked_sub_signed(back))
+                .unwrap_or(NaiveDateTime::MIN);
+            let end = day
+                .checked_sub(&lo)
+                .and_then(|ahead| until.naive_utc().checked_add_signed(ahead))
+                .unwrap_or(NaiveDateTime::MAX);
+            for occ in cal.events_in_range(start, end) {
+                let evt = occ.event();
+                let Some((start, end)) = self.instants(evt, occ.start(), occ.end()) else {
+                    continue;
+                };
+                for (index, alarm) in evt.alarms().iter().enumerate() {
+                    if matches!(alarm.trigger, Trigger::At(_)) {
+                        continue;
+                    }
+                    let key = AlarmKey {
+                        event: *evt.id(),
+                        recurrence_id: occ.recurrence_id(),
+                        index,
+                    };
+                    let ringing = Ringing {
+                        key,
+                        evt,
+                        occurrence_start: occ.start(),
+                        alarm,
+                        start,
+                        end,
+                    };
+                    self.collect(&mut found, ringing, (from, until));
+                }
+            }
+        }
+
+        // snoozed alarms go off again once
+        for (key, state) in &self.states {
+            let AlarmState::Snoozed(at) = *state else {
+                continue;
+            };
+            if !in_window(&at) {
+                continue;
+            }
+            let evt = match key.recurrence_id {
+                Some(rid) => cal
+                    .get_override(key.event, rid)
+                    .or_else(|| cal.get(key.event)),
+                None => cal.get(key.event),
+            };
+            // the event or alarm may have been removed since
+            let Some((evt, alarm)) = evt.and_then(|evt| Some((evt, evt.alarms().get(key.index)?)))
+            else {
+                continue;
+            };
+            found.push(DueAlarm {
+                key: *key,
+                start: key.recurrence_id.unwrap_or(evt.start()),
+                name: evt.name().to_string(),
+                alarm: alarm.clone(),
+                at,
+                repetition: 0,
+                snoozed: true,
+            });
+        }
+
+        found.sort_by(|a, b| a.at.cmp(&b.at).then_with(|| a.key.cmp(&b.key)));
+        found
+    }
+
+    /// make an alarm that went off go off again at `until`, instead of any
+    /// repeats before then
+    pub fn snooze(&mut self, alarm: &DueAlarm, until: DateTime<Utc>) {
+        self.states.insert(alarm.key, AlarmState::Snoozed(until));
+    }
+
+    /// stop an alarm that went off from going off again, repeats included
+    pub fn dismiss(&mut self, alarm: &DueAlarm) {
+        let last = alarm.alarm.last_fire_time(alarm.at, alarm.repetition);
+        self.states.insert(alarm.key, AlarmState::Dismissed(last));
+    }
+
+    /// the instants an occurrence starts and ends at
+    fn instants(
+        &self,
+        evt: &Event,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let policy = Disambiguation::Compatible;
+        let start = evt.tz().to_utc(start, &self.floating, policy).ok()?;
+        let end = evt.tz().to_utc(end, &self.floating, policy).ok()?;
+        Some((start, end))
+    }
+
+    /// add each time an alarm goes off within the window (from, until],
+    /// unless it was dismissed or snoozed past it
+    fn collect(
+        &self,
+        found: &mut Vec<DueAlarm>,
+        ringing: Ringing<'_>,
+        (from, until): (DateTime<Utc>, DateTime<Utc>),
+    ) {
+        let Ringing {
+            key,
+            evt,
+            occurrence_start,
+            alarm,
+            start,
+            end,
+        } = ringing;
+        let fire_times = alarm.fire_times(start, end).enumerate();
+        for (repetition, at) in fire_times.take_while(|(_, at)| *at <= until) {
+            let silenced = match self.states.get(&key) {
+                Some(AlarmState::Dismissed(_)) => true,
+                Some(AlarmState::Snoozed(snoozed)) => at < *snoozed,
+                None => false,
+            };
+            if silenced || at <= from {
+                continue;
+            }
+            found.push(DueAlarm {
+                key,
+                start: occurrence_start,
+                name: evt.name().to_string(),
+                alarm: alarm.clone(),
+                at,
+                repetition: repetition as u32,
+                snoozed: false,
+            });
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use super::{props, EventError};
+
+/// What part someone plays in an event, the ROLE of an ATTENDEE
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Role {
+    /// runs the event
+    Chair,
+    /// is expected to attend (the default)
+    #[default]
+    Required,
+    /// may attend
+    Optional,
+    /// is told about the event but not expected to attend
+    NonParticipant,
+}
+
+impl Role {
+    /// returns the name RFC 5545 uses for the role
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Chair => "CHAIR",
+            Role::Required => "REQ-PARTICIPANT",
+            Role::Optional => "OPT-PARTICIPANT",
+            Role::NonParticipant => "NON-PARTICIPANT",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = EventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "CHAIR" => Ok(Role::Chair),
+            "REQ-PARTICIPANT" => Ok(Role::Required),
+            "OPT-PARTICIPANT" => Ok(Role::Optional),
+            "NON-PARTICIPANT" => Ok(Role::NonParticipant),
+            _ => Err(EventError::InvalidProperty("ROLE", s.to_string())),
+        }
+    }
+}
+
+/// Whether someone has said they will come, the PARTSTAT of an ATTENDEE
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum PartStat {
+    /// no reply yet (the default)
+    #[default]
+    NeedsAction,
+    Accepted,
+    Declined,
+    Tentative,
+}
+
+impl PartStat {
+    /// returns the name RFC 5545 uses for the participation status
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PartStat::NeedsAction => "NEEDS-ACTION",
+            PartStat::Accepted => "ACCEPTED",
+            PartStat::Declined => "DECLINED",
+            PartStat::Tentative => "TENTATIVE",
+        }
+    }
+}
+
+impl fmt::Display for PartStat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PartStat {
+    type Err = EventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "NEEDS-ACTION" => Ok(PartStat::NeedsAction),
+            "ACCEPTED" => Ok(PartStat::Accepted),
+            "DECLINED" => Ok(PartStat::Declined),
+            "TENTATIVE" => Ok(PartStat::Tentative),
+            _ => Err(EventError::InvalidProperty("PARTSTAT", s.to_string())),
+        }
+    }
+}
+
+/// Someone invited to an event
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Attendee {
+    address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default)]
+    role: Role,
+    #[serde(default)]
+    rsvp: bool,
+    #[serde(default)]
+    status: PartStat,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    replied_at: Option<DateTime<Utc>>,
+}
+
+impl Attendee {
+    /// create a required attendee who has not replied yet. Takes a calendar
+    /// address URI, a bare email address is turned into a `mailto:` one
+    pub fn new(address: &str) -> Result<Self, EventError> {
+        Ok(Attendee {
+            address: props::cal_address("ATTENDEE", address)?,
+            name: None,
+            role: Role::Required,
+            rsvp: false,
+            status: PartStat::NeedsAction,
+            replied_at: None,
+        })
+    }
+
+    /// returns the calendar address of the attendee, such as
+    /// `mailto:bob@example.com`
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// returns true if `address` refers to this attendee, ignoring case. A
+    /// bare email address matches its `mailto:` address
+    pub fn is(&self, address: &str) -> bool {
+        props::cal_address("ATTENDEE", address)
+            .is_ok_and(|address| address.eq_ignore_ascii_case(&self.address))
+    }
+
+    /// returns the name to show for the attendee (CN)
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Set/Change/Clear the name to show for the attendee
+    pub fn set_name(self, name: Option<String>) -> Self {
+        Attendee { name, ..self }
+    }
+
+    /// returns the part the attendee plays in the event
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Set/Change the part the attendee plays in the event
+    pub fn set_role(self, role: Role) -> Self {
+        Attendee { role, ..self }
+    }
+
+    /// returns true if the attendee is expected to attend, that is they
+    /// chair the event or are a required participant
+    pub fn is_required(&self) -> bool {
+        matches!(self.role, Role::Chair | Role::Required)
+    }
+
+    /// returns true if the organizer has asked for a reply
+    pub fn rsvp(&self) -> bool {
+        self.rsvp
+    }
+
+    /// Set/Change whether the organizer asks for a reply
+    pub fn set_rsvp(self, rsvp: bool) -> Self {
+        Attendee { rsvp, ..self }
+    }
+
+    /// returns whether the attendee has accepted, declined or not replied
+    pub fn status(&self) -> PartStat {
+        self.status
+    }
+
+    /// returns when the attendee last replied, if they have
+    pub fn replied_at(&self) -> Option<DateTime<Utc>> {
+        self.replied_at
+    }
+
+    /// returns true unless the attendee has declined or is only being kept
+    /// informed
+    pub fn is_attending(&self) -> bool {
+        self.status != PartStat::Declined && self.role != Role::NonParticipant
+    }
+
+    /// record a reply from the attendee made at `at`, a reply of
+    /// needs-action clears the reply time
+    pub fn respond(self, status: PartStat, at: DateTime<Utc>) -> Self {
+        let replied_at = match status {
+            PartStat::NeedsAction => None,
+            _ => Some(at),
+        };
+        Attendee {
+            status,
+            replied_at,
+            ..self
+        }
+    }
+
+    /// Set/Change the participation status without recording a reply, as
+    /// when reading it back from iCalendar text
+    pub(crate) fn with_status(self, status: PartStat) -> Self {
+        Attendee { status, ..self }
+    }
+}
@@ -1,50 +1,1732 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::{BTreeMap, BTreeSet};
-use std::rc::Rc;
+use std::sync::Arc;
 use uuid::Uuid;
 
-use super::{event::Event, IntoUuid};
+use super::{
+    event::Event,
+    history::{Command, History},
+    ical,
+    index::IntervalIndex,
+    storage::{Change, Key},
+    Clock, Disambiguation, EventError, EventTz, FreeBusy, IntoUuid, Journal, PartStat, Task,
+    TryIntoUuid,
+};
 
 // Maybe use a BTreeSet to keep events in chronological order
 // and then add a second field which is a Hashmap<UUID, &Event>
 // keep the BTreeSet as append-only and only edit events through
 // dereferencing hashmap
 
-/// Represents a calendar of events
+/// A single occurrence of an event in the calendar, for recurring events
+/// this is one instance of the series with its own start and end
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Occurrence<'a> {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    event: &'a Arc<Event>,
+    recurrence_id: Option<NaiveDateTime>,
+}
+
+impl<'a> Occurrence<'a> {
+    /// returns the event this is an occurrence of
+    pub fn event(&self) -> &'a Arc<Event> {
+        self.event
+    }
+
+    /// returns when this occurrence starts
+    pub fn start(&self) -> NaiveDateTime {
+        self.start
+    }
+
+    /// returns when this occurrence ends
+    pub fn end(&self) -> NaiveDateTime {
+        self.end
+    }
+
+    /// returns the name of the event
+    pub fn name(&self) -> &'a str {
+        self.event.name()
+    }
+
+    /// returns the original start of this occurrence within its series, or
+    /// None for events that do not recur. This stays the same when the
+    /// occurrence has been moved by an override
+    pub fn recurrence_id(&self) -> Option<NaiveDateTime> {
+        self.recurrence_id
+    }
+
+    /// returns true if this occurrence has been overridden
+    pub fn is_override(&self) -> bool {
+        self.event.recurrence_id().is_some()
+    }
+
+    /// returns true if this occurrence takes up time, it is neither
+    /// transparent nor cancelled
+    pub fn is_busy(&self) -> bool {
+        self.event.is_busy()
+    }
+
+    /// returns a copy of this occurrence as an event of its own, occurrences
+    /// of a series come back as overrides of their occurrence
+    pub fn to_event(&self) -> Event {
+        match self.recurrence_id {
+            Some(recurrence_id) if !self.is_override() => self.event.occurrence_at(recurrence_id),
+            _ => Event::clone(self.event),
+        }
+    }
+
+    /// returns the start of this occurrence as an instant in `zone`,
+    /// floating events are read as wall clock time in `zone`
+    pub fn start_in<Z: TimeZone>(
+        &self,
+        zone: &Z,
+        policy: Disambiguation,
+    ) -> Result<DateTime<Z>, EventError> {
+        let utc = self.event.tz().to_utc(self.start, zone, policy)?;
+        Ok(utc.with_timezone(zone))
+    }
+
+    /// returns the end of this occurrence as an instant in `zone`,
+    /// floating events are read as wall clock time in `zone`
+    pub fn end_in<Z: TimeZone>(
+        &self,
+        zone: &Z,
+        policy: Disambiguation,
+    ) -> Result<DateTime<Z>, EventError> {
+        let utc = self.event.tz().to_utc(self.end, zone, policy)?;
+        Ok(utc.with_timezone(zone))
+    }
+}
+
+/// Represents a calendar of events, it can be saved and loaded with serde,
+/// for example as JSON with `serde_json`
 #[derive(Default)]
 pub struct EventCalendar {
-    ids: BTreeMap<Uuid, Rc<Event>>,
-    evts: BTreeSet<Rc<Event>>,
+    ids: BTreeMap<Uuid, Arc<Event>>,
+    evts: BTreeSet<Arc<Event>>,
+    index: IntervalIndex,
+    // changed occurrences of recurring events, by series and original start
+    overrides: BTreeMap<Uuid, BTreeMap<NaiveDateTime, Arc<Event>>>,
+    tasks: BTreeMap<Uuid, Task>,
+    // tasks with a due date, in the order they are due
+    due: BTreeSet<(NaiveDateTime, Uuid)>,
+    journals: BTreeMap<Uuid, Journal>,
+    // journal entries in the order of their dates
+    dated: BTreeSet<(NaiveDate, Uuid)>,
+    policy: OverlapPolicy,
+    // while a command is recorded, how each item it touched was before it
+    before: Option<BTreeMap<Key, Change>>,
+    history: History,
 }
 
+/// What a calendar does with an event that overlaps events already in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapPolicy {
+    /// add it without checking (the default)
+    #[default]
+    Allow,
+    /// add it, [`EventCalendar::try_add_event`] returns what it overlaps
+    Warn,
+    /// refuse it with [`EventError::Conflict`]
+    Reject,
+}
+
+impl OverlapPolicy {
+    /// returns true for the default policy of allowing overlaps
+    pub fn is_allow(&self) -> bool {
+        *self == OverlapPolicy::Allow
+    }
+}
+
+// a candidate series that never ends is only checked this far
+const MAX_CONFLICT_OCCURRENCES: usize = 1000;
+
 impl EventCalendar {
+    /// create a calendar from iCalendar (.ics) text
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::EventCalendar;
+    ///
+    /// let ics = "BEGIN:VCALENDAR\r\n\
+    ///            VERSION:2.0\r\n\
+    ///            BEGIN:VEVENT\r\n\
+    ///            UID:standup@example.com\r\n\
+    ///            DTSTART;TZID=Europe/Paris:20230102T090000\r\n\
+    ///            DURATION:PT15M\r\n\
+    ///            RRULE:FREQ=WEEKLY;BYDAY=MO\r\n\
+    ///            SUMMARY:Standup\r\n\
+    ///            END:VEVENT\r\n\
+    ///            END:VCALENDAR\r\n";
+    ///
+    /// let cal = EventCalendar::from_ics(ics).unwrap();
+    /// let standup = cal.first_event().unwrap();
+    /// assert_eq!(standup.name(), "Standup");
+    /// assert_eq!(standup.tz().name(), "Europe/Paris");
+    /// ```
+    pub fn from_ics(input: &str) -> Result<Self, EventError> {
+        let mut cal = EventCalendar::default();
+        cal.import_ics(input)?;
+        cal.clear_history();
+        Ok(cal)
+    }
+
+    /// add every event, task and journal entry in iCalendar (.ics) text to
+    /// the calendar, returning how many were added. Nothing is added if the
+    /// input is malformed, events the overlap policy rejects are skipped
+    /// along with the overrides of any series it rejects
+    pub fn import_ics(&mut self, input: &str) -> Result<usize, EventError> {
+        self.recorded(|cal| {
+            let (events, tasks, journals) = ical::read_items(input)?;
+            let mut count = 0;
+            // series are read before their overrides
+            let mut rejected = BTreeSet::new();
+            for event in events {
+                let id = *event.id();
+                if event.recurrence_id().is_some() && rejected.contains(&id) {
+                    continue;
+                }
+                match cal.try_add_event(event) {
+                    Ok(_) => count += 1,
+                    Err(_) => {
+                        rejected.insert(id);
+                    }
+                }
+            }
+            for task in tasks {
+                cal.add_task(task);
+                count += 1;
+            }
+            for journal in journals {
+                cal.add_journal(journal);
+                count += 1;
+            }
+            Ok(count)
+        })
+    }
+
+    /// write the calendar as iCalendar (.ics) text, every event and override
+    /// is stamped with the current time
+    ///
+    /// ```
+    /// use calib::{Event, EventCalendar};
+    /// use chrono::NaiveDate;
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// let day = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// cal.add_event(Event::new("Lunch, then coffee".to_string(), &day));
+    ///
+    /// let ics = cal.to_ics();
+    /// assert!(ics.contains("SUMMARY:Lunch\\, then coffee\r\n"));
+    /// assert_eq!(EventCalendar::from_ics(&ics).unwrap().first_event(), cal.first_event());
+    /// ```
+    pub fn to_ics(&self) -> String {
+        self.to_ics_with_stamp(Utc::now())
+    }
+
+    /// write the calendar as iCalendar (.ics) text with every DTSTAMP set to
+    /// `dtstamp`, so the output is the same each time
+    pub fn to_ics_with_stamp(&self, dtstamp: DateTime<Utc>) -> String {
+        ical::write_calendar(self, dtstamp)
+    }
+
+    /// returns how the calendar treats events that overlap ones already in it
+    pub fn policy(&self) -> OverlapPolicy {
+        self.policy
+    }
+
+    /// Set/Change how the calendar treats events that overlap ones already
+    /// in it, events already in the calendar are left as they are
+    pub fn set_policy(&mut self, policy: OverlapPolicy) {
+        self.policy = policy;
+    }
+
     /// inserts event into calednar, returning true if the event
     /// is new to the calendar and false if the event already exits
+    /// or the overlap policy rejects it.
+    /// Events with a recurrence id are stored as overrides of their series
     pub fn add_event(&mut self, event: Event) -> bool {
+        self.recorded(|cal| match cal.policy {
+            OverlapPolicy::Reject if !cal.conflicts(&event).is_empty() => false,
+            _ => cal.insert(event),
+        })
+    }
+
+    /// inserts event into the calendar following the overlap policy. Returns
+    /// the ids of the events it overlaps, always empty when overlaps are
+    /// allowed, or [`EventError::Conflict`] if the policy rejects it
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Event, EventCalendar, EventError, OverlapPolicy};
+    /// use chrono::NaiveDate;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let birthday = Event::new("Birthday".into(), &date);
+    /// let id = *birthday.id();
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// cal.set_policy(OverlapPolicy::Warn);
+    /// assert!(cal.try_add_event(birthday).unwrap().is_empty());
+    /// assert_eq!(cal.try_add_event(Event::new("Dentist".into(), &date)).unwrap(), vec![id]);
+    ///
+    /// cal.set_policy(OverlapPolicy::Reject);
+    /// let party = Event::new("Party".into(), &date);
+    /// assert!(matches!(cal.try_add_event(party), Err(EventError::Conflict(ids)) if ids.len() == 2));
+    /// ```
+    pub fn try_add_event(&mut self, event: Event) -> Result<Vec<Uuid>, EventError> {
+        self.recorded(|cal| {
+            let ids = match cal.policy {
+                OverlapPolicy::Allow => Vec::new(),
+                _ => cal.conflict_ids(&event),
+            };
+            if cal.policy == OverlapPolicy::Reject && !ids.is_empty() {
+                return Err(EventError::Conflict(ids));
+            }
+            cal.insert(event);
+            Ok(ids)
+        })
+    }
+
+    /// return every occurrence in the calendar that overlaps an occurrence of
+    /// `candidate`, ordered by when they start. Transparent and cancelled
+    /// events on either side never conflict, nor do an event and its own
+    /// series or overrides. Occurrences are checked from the start of the
+    /// first event in the calendar to the end of the last, a series that
+    /// never ends only for 1000 occurrences if the calendar never ends either
+    pub fn conflicts(&self, candidate: &Event) -> Vec<Occurrence<'_>> {
+        let (Some(first), Some(horizon)) = (self.index.min_start(), self.index.max_end()) else {
+            return Vec::new();
+        };
+        if !candidate.is_busy() {
+            return Vec::new();
+        }
+        let duration = candidate.duration();
+        let limit = match horizon {
+            NaiveDateTime::MAX => MAX_CONFLICT_OCCURRENCES,
+            _ => usize::MAX,
+        };
+
+        let mut found = Vec::new();
+        for start in candidate
+            .occurrences_from(
+                first
+                    .checked_sub_signed(duration)
+                    .unwrap_or(NaiveDateTime::MIN),
+            )
+            .skip_while(|start| {
+                start
+                    .checked_add_signed(duration)
+                    .is_some_and(|end| end <= first)
+            })
+            .take_while(|start| *start < horizon)
+            .take(limit)
+        {
+            found.extend(
+                self.occurrences_overlapping(start, start + duration)
+                    .into_iter()
+                    .filter(|occ| occ.event.id() != candidate.id() && occ.is_busy()),
+            );
+        }
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    /// fail with [`EventError::Conflict`] if the overlap policy rejects
+    /// `candidate` taking the place of the event with id `own`, which it
+    /// can't conflict with
+    fn check_policy(&self, candidate: &Event, own: Uuid) -> Result<(), EventError> {
+        if self.policy != OverlapPolicy::Reject {
+            return Ok(());
+        }
+        let ids: Vec<_> = self
+            .conflict_ids(candidate)
+            .into_iter()
+            .filter(|id| *id != own)
+            .collect();
+        if ids.is_empty() {
+            Ok(())
+        } else {
+            Err(EventError::Conflict(ids))
+        }
+    }
+
+    /// the ids of the events `candidate` conflicts with, in order
+    fn conflict_ids(&self, candidate: &Event) -> Vec<Uuid> {
+        let ids: BTreeSet<_> = self
+            .conflicts(candidate)
+            .iter()
+            .map(|occ| *occ.event.id())
+            .collect();
+        ids.into_iter().collect()
+    }
+
+    /// inserts event into the calendar whatever the overlap policy
+    fn insert(&mut self, event: Event) -> bool {
         let id = *event.id();
-        let evt = Rc::new(event);
-        self.ids.insert(id, Rc::clone(&evt));
-        self.evts.insert(Rc::clone(&evt))
+        let evt = Arc::new(event);
+        if let Some(recurrence_id) = evt.recurrence_id() {
+            return self.insert_override(id, recurrence_id, evt).is_none();
+        }
+
+        // an event with the same id but different contents would otherwise
+        // be left behind in evts once its entry in ids is overwritten
+        self.touch(Key::Event(id, None));
+        if let Some(old) = self.ids.insert(id, Arc::clone(&evt)) {
+            self.evts.remove(&old);
+            self.index.remove(&old);
+        }
+        self.index.insert(Arc::clone(&evt), evt.span_end());
+        self.evts.insert(Arc::clone(&evt))
+    }
+
+    /// removes an event from the calendar, returning the removed event
+    /// or None if no event with that id exists. Any overrides of a
+    /// recurring event are removed with it
+    pub fn remove<T: IntoUuid>(&mut self, id: T) -> Option<Arc<Event>> {
+        self.recorded(|cal| {
+            let id = id.into_uuid();
+            let old = cal.take(&id)?;
+            cal.take_overrides(id);
+            Some(old)
+        })
     }
 
-    /// return an iterator of all events between start and end
+    /// removes an event from the calendar given an id that may not be valid,
+    /// returning the removed event
+    pub fn try_remove<T: TryIntoUuid>(&mut self, id: T) -> Result<Arc<Event>, EventError> {
+        let id = id.try_into_uuid()?;
+        self.remove(id).ok_or(EventError::NotFound(id))
+    }
+
+    /// replaces the event with the given id, returning the old event.
+    /// The new event is stored under its own id, which may differ from
+    /// the id of the event it replaces, overrides follow the new id. If the
+    /// start of a series moves its overrides move with it, and those of
+    /// occurrences the new series doesn't have are removed. Fails with
+    /// [`EventError::DuplicateId`] if the new id belongs to another event
+    /// and [`EventError::Conflict`] if the overlap policy rejects the new
+    /// event
+    pub fn replace<T: TryIntoUuid>(
+        &mut self,
+        id: T,
+        event: Event,
+    ) -> Result<Arc<Event>, EventError> {
+        self.recorded(|cal| {
+            let id = id.try_into_uuid()?;
+            cal.check_replace(id, &event)?;
+            cal.check_policy(&event, id)?;
+            Ok(cal.swap(id, event))
+        })
+    }
+
+    /// fail unless the event with id `id` can be replaced by `event`
+    fn check_replace(&self, id: Uuid, event: &Event) -> Result<(), EventError> {
+        if !self.ids.contains_key(&id) {
+            return Err(EventError::NotFound(id));
+        }
+        let new_id = *event.id();
+        if new_id != id && self.ids.contains_key(&new_id) {
+            return Err(EventError::DuplicateId(new_id));
+        }
+        Ok(())
+    }
+
+    /// replace an event once [`EventCalendar::check_replace`] has passed,
+    /// returning the old event. Nothing here can fail, as a command is not
+    /// undone when it fails partway through
+    fn swap(&mut self, id: Uuid, event: Event) -> Arc<Event> {
+        let old = self.take(&id).expect("checked the event exists");
+        let new_id = *event.id();
+        let delta = event.start() - old.start();
+        let overrides = self.take_overrides(id);
+        self.insert(event);
+
+        for (recurrence_id, evt) in overrides {
+            let recurrence_id = recurrence_id + delta;
+            if self.occurrence_of(new_id, recurrence_id).is_ok() {
+                let moved = Event::clone(&evt).into_override(new_id, recurrence_id);
+                self.insert_override(new_id, recurrence_id, Arc::new(moved));
+            }
+        }
+        old
+    }
+
+    /// edits the event with the given id in place by passing a copy of it to
+    /// `f`, returning the old event. If `f` fails the calendar is unchanged
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Event, EventCalendar};
+    /// use chrono::{NaiveDate, NaiveTime};
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+    /// let event = Event::new("Birthday".into(), &date);
+    /// let id = *event.id();
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// cal.add_event(event);
+    ///
+    /// let noon = NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+    /// cal.update(id, |evt| evt.set_start_time(noon)).unwrap();
+    /// assert_eq!(cal.get(id).unwrap().start().time(), noon);
+    /// ```
+    pub fn update<T, F>(&mut self, id: T, f: F) -> Result<Arc<Event>, EventError>
+    where
+        T: TryIntoUuid,
+        F: FnOnce(Event) -> Result<Event, EventError>,
+    {
+        let id = id.try_into_uuid()?;
+        let old = self.ids.get(&id).ok_or(EventError::NotFound(id))?;
+        let new = f(Event::clone(old))?;
+        self.replace(id, new)
+    }
+
+    /// changes a single occurrence of a recurring event by passing a copy of
+    /// it to `f`, leaving the rest of the series alone. The occurrence is
+    /// identified by its original start, even if it has already been moved.
+    /// Returns the previous override of the occurrence, if there was one, or
+    /// [`EventError::Conflict`] if the overlap policy rejects the new one
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Event, EventCalendar};
+    /// use chrono::{Duration, NaiveDate};
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let standup = Event::new("Standup".into(), &date)
+    ///     .set_recurrence(Some("FREQ=DAILY;COUNT=5".parse().unwrap()));
+    /// let (id, second) = (*standup.id(), standup.start() + Duration::days(1));
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// cal.add_event(standup);
+    /// cal.override_occurrence(id, second, |mut evt| {
+    ///     evt.set_name("Retro".into());
+    ///     Ok(evt)
+    /// })
+    /// .unwrap();
+    ///
+    /// let names: Vec<_> = cal
+    ///     .events_in_range(second - Duration::days(1), second + Duration::days(2))
+    ///     .map(|occ| occ.name())
+    ///     .collect();
+    /// assert_eq!(names, vec!["Standup", "Retro", "Standup"]);
+    /// ```
+    pub fn override_occurrence<T, F>(
+        &mut self,
+        series: T,
+        recurrence_id: NaiveDateTime,
+        f: F,
+    ) -> Result<Option<Arc<Event>>, EventError>
+    where
+        T: TryIntoUuid,
+        F: FnOnce(Event) -> Result<Event, EventError>,
+    {
+        self.recorded(|cal| {
+            let series = series.try_into_uuid()?;
+            let current = match cal.get_override(series, recurrence_id) {
+                Some(evt) => Event::clone(evt),
+                None => cal.occurrence_of(series, recurrence_id)?,
+            };
+
+            let new = f(current)?.into_override(series, recurrence_id);
+            cal.check_policy(&new, series)?;
+            Ok(cal.insert_override(series, recurrence_id, Arc::new(new)))
+        })
+    }
+
+    /// removes the override of an occurrence, restoring it to match the
+    /// rest of its series. Returns the removed override
+    pub fn remove_override<T: IntoUuid>(
+        &mut self,
+        series: T,
+        recurrence_id: NaiveDateTime,
+    ) -> Option<Arc<Event>> {
+        let series = series.into_uuid();
+        self.recorded(|cal| cal.take_override(series, recurrence_id))
+    }
+
+    /// remove the override of an occurrence
+    fn take_override(&mut self, series: Uuid, recurrence_id: NaiveDateTime) -> Option<Arc<Event>> {
+        self.get_override(series, recurrence_id)?;
+        self.touch(Key::Event(series, Some(recurrence_id)));
+        let overrides = self.overrides.get_mut(&series)?;
+        let old = overrides.remove(&recurrence_id)?;
+        if overrides.is_empty() {
+            self.overrides.remove(&series);
+        }
+        self.index.remove(&old);
+        Some(old)
+    }
+
+    /// cancels a single occurrence of a recurring event by adding it to the
+    /// series' EXDATEs, dropping any override of it. Returns the old series
+    pub fn cancel_occurrence<T: TryIntoUuid>(
+        &mut self,
+        series: T,
+        recurrence_id: NaiveDateTime,
+    ) -> Result<Arc<Event>, EventError> {
+        self.recorded(|cal| {
+            let series = series.try_into_uuid()?;
+            let old = cal.ids.get(&series).ok_or(EventError::NotFound(series))?;
+            if cal.get_override(series, recurrence_id).is_none() {
+                cal.occurrence_of(series, recurrence_id)?;
+            }
+            // dropping an occurrence can't add a conflict
+            let new = Event::clone(old).add_exdate(recurrence_id);
+            cal.take_override(series, recurrence_id);
+            Ok(cal.swap(series, new))
+        })
+    }
+
+    /// adds an extra occurrence to an event starting at `start` by adding
+    /// it to the event's RDATEs. Returns the old event
+    pub fn add_occurrence<T: TryIntoUuid>(
+        &mut self,
+        series: T,
+        start: NaiveDateTime,
+    ) -> Result<Arc<Event>, EventError> {
+        self.update(series, |evt| Ok(evt.add_rdate(start)))
+    }
+
+    /// applies `f` to an occurrence and every occurrence after it ("this and
+    /// following"), splitting the series in two. The original series is
+    /// truncated to end before `recurrence_id` and a new series, related to
+    /// the original, continues from the edited occurrence. EXDATEs, RDATEs and
+    /// overrides are kept with the half of the series they belong to, and
+    /// along with UNTIL move with the new series if `f` changes its start.
+    /// Returns the new series, which keeps the original id if
+    /// `recurrence_id` is the first occurrence, or [`EventError::Conflict`]
+    /// if the overlap policy rejects it
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Event, EventCalendar};
+    /// use chrono::{Duration, NaiveDate, NaiveTime};
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let standup = Event::new("Standup".into(), &date)
+    ///     .set_recurrence(Some("FREQ=WEEKLY;COUNT=4".parse().unwrap()));
+    /// let (id, third) = (*standup.id(), standup.start() + Duration::weeks(2));
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// cal.add_event(standup);
+    /// let new = cal
+    ///     .split_series(id, third, |evt| evt.set_start_time(NaiveTime::from_hms_opt(9, 0, 0).unwrap()))
+    ///     .unwrap();
+    ///
+    /// assert_eq!(new.related_to(), Some(&id));
+    /// assert_eq!(cal.get(id).unwrap().occurrences().count(), 2);
+    /// assert_eq!(new.occurrences().count(), 2);
+    /// ```
+    pub fn split_series<T, F>(
+        &mut self,
+        series: T,
+        recurrence_id: NaiveDateTime,
+        f: F,
+    ) -> Result<Arc<Event>, EventError>
+    where
+        T: TryIntoUuid,
+        F: FnOnce(Event) -> Result<Event, EventError>,
+    {
+        self.recorded(|cal| {
+            let series = series.try_into_uuid()?;
+            cal.occurrence_of(series, recurrence_id)?;
+
+            let (head, tail) = cal.ids[&series].split_at(recurrence_id)?;
+            let tail = f(tail)?;
+            let delta = tail.start() - recurrence_id;
+            let tail = tail.shift_exceptions(delta);
+            let tail_id = *tail.id();
+            if head.is_some() && cal.ids.contains_key(&tail_id) {
+                return Err(EventError::DuplicateId(tail_id));
+            }
+            // the head only loses occurrences, so only the tail can conflict
+            cal.check_policy(&tail, series)?;
+
+            // overrides from the split point on follow the new series
+            let mut overrides = cal.take_overrides(series);
+            let moved = overrides.split_off(&recurrence_id);
+            for (recurrence_id, evt) in overrides {
+                cal.insert_override(series, recurrence_id, evt);
+            }
+
+            match head {
+                Some(head) => {
+                    cal.swap(series, head);
+                    cal.insert(tail);
+                }
+                None => {
+                    cal.swap(series, tail);
+                }
+            }
+            for (recurrence_id, evt) in moved {
+                let recurrence_id = recurrence_id + delta;
+                let evt = Event::clone(&evt).into_override(tail_id, recurrence_id);
+                cal.insert_override(tail_id, recurrence_id, Arc::new(evt));
+            }
+
+            Ok(Arc::clone(&cal.ids[&tail_id]))
+        })
+    }
+
+    /// record a reply made at `at` from an attendee of the event with the
+    /// given id, for a recurring event this is a reply to the whole series.
+    /// Returns the event as it was before the reply
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Attendee, Event, EventCalendar, PartStat};
+    /// use chrono::{NaiveDate, TimeZone, Utc};
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let event = Event::new("Review".into(), &date)
+    ///     .add_attendee(Attendee::new("bob@example.com").unwrap());
+    /// let id = *event.id();
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// cal.add_event(event);
+    ///
+    /// let at = Utc.with_ymd_and_hms(2022, 12, 31, 9, 0, 0).unwrap();
+    /// cal.respond(id, "bob@example.com", PartStat::Accepted, at).unwrap();
+    /// let bob = cal.get(id).unwrap().attendee("bob@example.com").unwrap().clone();
+    /// assert_eq!((bob.status(), bob.replied_at()), (PartStat::Accepted, Some(at)));
+    /// ```
+    pub fn respond<T: TryIntoUuid>(
+        &mut self,
+        id: T,
+        address: &str,
+        status: PartStat,
+        at: DateTime<Utc>,
+    ) -> Result<Arc<Event>, EventError> {
+        self.update(id, |evt| evt.respond(address, status, at))
+    }
+
+    /// record a reply made at `at` from an attendee to a single occurrence of
+    /// a recurring event, overriding that occurrence. Returns the previous
+    /// override of the occurrence, if it had one
+    pub fn respond_to_occurrence<T: TryIntoUuid>(
+        &mut self,
+        series: T,
+        recurrence_id: NaiveDateTime,
+        address: &str,
+        status: PartStat,
+        at: DateTime<Utc>,
+    ) -> Result<Option<Arc<Event>>, EventError> {
+        self.override_occurrence(series, recurrence_id, |evt| {
+            evt.respond(address, status, at)
+        })
+    }
+
+    /// return an iterator over the overrides of a recurring event, ordered by
+    /// the original start of the occurrence they replace
+    pub fn overrides<T: IntoUuid>(&self, series: T) -> impl Iterator<Item = &Arc<Event>> {
+        self.overrides
+            .get(&series.into_uuid())
+            .into_iter()
+            .flat_map(|o| o.values())
+    }
+
+    /// return the override of a single occurrence, if it has been changed
+    pub fn get_override<T: IntoUuid>(
+        &self,
+        series: T,
+        recurrence_id: NaiveDateTime,
+    ) -> Option<&Arc<Event>> {
+        self.overrides.get(&series.into_uuid())?.get(&recurrence_id)
+    }
+
+    /// return an iterator of all occurrences overlapping the half-open range
+    /// [start, end), including events that span the whole range. Events
+    /// that end exactly at `start` or begin exactly at `end` are excluded.
+    /// Recurring events are expanded into their individual occurrences
     pub fn events_in_range(
         &self,
         start: NaiveDateTime,
         end: NaiveDateTime,
-    ) -> impl Iterator<Item = &Rc<Event>> {
-        self.evts.iter().filter(move |evt| {
-            (evt.start() >= start && evt.start() <= end) || (evt.end() >= start && evt.end() <= end)
-        })
+    ) -> impl Iterator<Item = Occurrence<'_>> {
+        self.occurrences_overlapping(start, end).into_iter()
+    }
+
+    /// return an iterator of all occurrences taking place on any part of
+    /// `date`, all day events are only on the days they cover
+    pub fn events_on(&self, date: NaiveDate) -> impl Iterator<Item = Occurrence<'_>> {
+        let start = NaiveDateTime::new(date, NaiveTime::MIN);
+        let end = date.succ_opt().map_or(NaiveDateTime::MAX, |next| {
+            NaiveDateTime::new(next, NaiveTime::MIN)
+        });
+        self.occurrences_overlapping(start, end).into_iter()
+    }
+
+    /// return an iterator of all occurrences taking place at the given instant
+    pub fn events_containing(
+        &self,
+        instant: NaiveDateTime,
+    ) -> impl Iterator<Item = Occurrence<'_>> {
+        // the smallest range containing only the instant itself
+        self.occurrences_overlapping(instant, instant + Duration::nanoseconds(1))
+            .into_iter()
+    }
+
+    /// return an iterator of all occurrences that start and end within [start, end]
+    pub fn events_fully_within(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> impl Iterator<Item = Occurrence<'_>> {
+        self.occurrences_overlapping(start, end)
+            .into_iter()
+            .filter(move |occ| occ.start >= start && occ.end <= end)
+    }
+
+    /// return an iterator of the occurrences overlapping [start, end) that
+    /// the person with the given calendar address is invited to and has not
+    /// declined. Replies to a single occurrence are taken into account
+    pub fn events_attended_by<'a>(
+        &'a self,
+        address: &'a str,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> impl Iterator<Item = Occurrence<'a>> {
+        self.occurrences_overlapping(start, end)
+            .into_iter()
+            .filter(move |occ| occ.event.is_attending(address))
+    }
+
+    /// return the occurrences overlapping the half-open range [start, end)
+    /// of real time, comparing each event in its own time zone. Floating
+    /// events are read as wall clock time in the zone of `start`. Occurrences
+    /// are ordered by the instant they start at
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Disambiguation, Event, EventCalendar};
+    /// use chrono::{NaiveDate, NaiveTime, TimeZone};
+    /// use chrono_tz::{America::New_York, Europe::Paris};
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 7, 3).unwrap();
+    /// let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+    /// let standup = Event::new("Standup".into(), &date)
+    ///     .set_end_time(NaiveTime::from_hms_opt(9, 30, 0).unwrap())
+    ///     .unwrap()
+    ///     .set_start_time(nine)
+    ///     .unwrap()
+    ///     .set_tz(Paris.into());
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// cal.add_event(standup);
+    ///
+    /// // 09:00 in Paris is 03:00 in New York
+    /// let start = New_York.with_ymd_and_hms(2023, 7, 3, 3, 0, 0).unwrap();
+    /// let end = New_York.with_ymd_and_hms(2023, 7, 3, 4, 0, 0).unwrap();
+    /// let found = cal.events_in_range_tz(&start, &end, Disambiguation::Reject).unwrap();
+    /// assert_eq!(found.count(), 1);
+    /// ```
+    pub fn events_in_range_tz<Z: TimeZone>(
+        &self,
+        start: &DateTime<Z>,
+        end: &DateTime<Z>,
+        policy: Disambiguation,
+    ) -> Result<impl Iterator<Item = Occurrence<'_>>, EventError> {
+        // UTC offsets are within -12:00 and +14:00, so any wall clock time
+        // for the range falls within this window
+        let zone = start.timezone();
+        let (start, end) = (start.to_utc(), end.to_utc());
+        let naive_start = start.naive_utc() - Duration::hours(13);
+        let naive_end = end.naive_utc() + Duration::hours(15);
+
+        let mut found = Vec::new();
+        for occ in self.occurrences_overlapping(naive_start, naive_end) {
+            let occ_start = occ.event.tz().to_utc(occ.start, &zone, policy)?;
+            let occ_end = occ.event.tz().to_utc(occ.end, &zone, policy)?;
+            if occ_start < end && occ_end > start {
+                found.push((occ_start, occ));
+            }
+        }
+
+        found.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+        Ok(found.into_iter().map(|(_, occ)| occ))
+    }
+
+    /// return the first occurrence to start after `instant`, recurring
+    /// events included. Of occurrences starting at the same time the one
+    /// [`EventCalendar::events_in_range`] would list first is returned
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Event, EventCalendar, RRule};
+    /// use chrono::{NaiveDate, NaiveTime};
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let at = |day, h| {
+    ///     NaiveDate::from_ymd_opt(2023, 1, day)
+    ///         .unwrap()
+    ///         .and_time(NaiveTime::from_hms_opt(h, 0, 0).unwrap())
+    /// };
+    /// let standup = Event::new("Standup".into(), &date)
+    ///     .set_start(at(2, 9))
+    ///     .and_then(|evt| evt.set_end(at(2, 10)))
+    ///     .unwrap()
+    ///     .set_recurrence(Some("FREQ=DAILY".parse::<RRule>().unwrap()));
+    /// let review = Event::new("Review".into(), &at(4, 0).date())
+    ///     .set_start(at(4, 14))
+    ///     .and_then(|evt| evt.set_end(at(4, 15)))
+    ///     .unwrap();
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// cal.add_event(standup);
+    /// cal.add_event(review);
+    ///
+    /// assert_eq!(cal.next_after(at(4, 9)).unwrap().name(), "Review");
+    /// assert_eq!(cal.next_after(at(4, 14)).unwrap().start(), at(5, 9));
+    /// assert_eq!(cal.previous_before(at(4, 9)).unwrap().start(), at(3, 9));
+    /// assert!(cal.previous_before(at(2, 9)).is_none());
+    /// ```
+    pub fn next_after(&self, instant: NaiveDateTime) -> Option<Occurrence<'_>> {
+        self.next_starts(instant).into_iter().next()
+    }
+
+    /// return the last occurrence to start before `instant`, recurring
+    /// events included. Of occurrences starting at the same time the one
+    /// [`EventCalendar::events_in_range`] would list last is returned
+    pub fn previous_before(&self, instant: NaiveDateTime) -> Option<Occurrence<'_>> {
+        self.previous_starts(instant).pop()
+    }
+
+    /// return the next `n` occurrences to start after the time given by
+    /// `clock`, comparing each event in its own time zone. Floating events
+    /// are read as UTC. Occurrences are ordered by the instant they start at
+    pub fn upcoming<C: Clock>(&self, clock: &C, n: usize) -> Vec<Occurrence<'_>> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let now = clock.now();
+        let policy = Disambiguation::Compatible;
+        let instant = |occ: &Occurrence| occ.event.tz().to_utc(occ.start, &Utc, policy).ok();
+
+        // UTC offsets are within -12:00 and +14:00, so an occurrence whose
+        // wall clock time is more than 26 hours later also starts later
+        let slack = Duration::hours(26);
+        let mut cursor = now.naive_utc() - Duration::hours(15);
+        let mut found: Vec<(DateTime<Utc>, Occurrence)> = Vec::new();
+        loop {
+            let next = self.next_starts(cursor);
+            let Some(first) = next.first().map(|occ| occ.start) else {
+                break;
+            };
+            if found.len() == n && first > found[n - 1].1.start + slack {
+                break;
+            }
+            found.extend(
+                next.into_iter()
+                    .filter_map(|occ| Some((instant(&occ)?, occ)))
+                    .filter(|(at, _)| *at > now),
+            );
+            found.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+            found.truncate(n);
+            cursor = first;
+        }
+
+        found.into_iter().map(|(_, occ)| occ).collect()
+    }
+
+    /// return when the calendar is busy and free over [start, end).
+    /// Overlapping busy occurrences are merged, occurrences that are
+    /// transparent or cancelled leave their time free
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Event, EventCalendar, Transparency};
+    /// use chrono::{NaiveDate, NaiveTime};
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let at = |h| date.and_time(NaiveTime::from_hms_opt(h, 0, 0).unwrap());
+    /// let meeting = |name: &str, start, end| {
+    ///     Event::new(name.into(), &date)
+    ///         .set_start(at(start))
+    ///         .and_then(|evt| evt.set_end(at(end)))
+    ///         .unwrap()
+    /// };
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// cal.add_event(meeting("Planning", 9, 11));
+    /// cal.add_event(meeting("Review", 10, 12));
+    /// cal.add_event(meeting("Reminder", 14, 15).set_transparency(Transparency::Transparent));
+    ///
+    /// let fb = cal.free_busy(at(8), at(17));
+    /// let busy: Vec<_> = fb.busy().iter().map(|p| (p.start(), p.end())).collect();
+    /// let free: Vec<_> = fb.free().iter().map(|p| (p.start(), p.end())).collect();
+    /// assert_eq!(busy, vec![(at(9), at(12))]);
+    /// assert_eq!(free, vec![(at(8), at(9)), (at(12), at(17))]);
+    /// ```
+    pub fn free_busy(&self, start: NaiveDateTime, end: NaiveDateTime) -> FreeBusy {
+        let busy = self
+            .occurrences_overlapping(start, end)
+            .into_iter()
+            .filter(|occ| occ.is_busy())
+            .map(|occ| (occ.start, occ.end));
+        FreeBusy::new(start, end, busy, EventTz::Floating)
+    }
+
+    /// return when the calendar is busy and free over the half-open range
+    /// [start, end) of real time, with every period in UTC. Floating events
+    /// are read as wall clock time in the zone of `start`
+    pub fn free_busy_tz<Z: TimeZone>(
+        &self,
+        start: &DateTime<Z>,
+        end: &DateTime<Z>,
+        policy: Disambiguation,
+    ) -> Result<FreeBusy, EventError> {
+        let zone = start.timezone();
+        let mut busy = Vec::new();
+        for occ in self.events_in_range_tz(start, end, policy)? {
+            if occ.is_busy() {
+                let occ_start = occ.event.tz().to_utc(occ.start, &zone, policy)?;
+                let occ_end = occ.event.tz().to_utc(occ.end, &zone, policy)?;
+                busy.push((occ_start.naive_utc(), occ_end.naive_utc()));
+            }
+        }
+
+        Ok(FreeBusy::new(
+            start.naive_utc(),
+            end.naive_utc(),
+            busy,
+            EventTz::Utc,
+        ))
+    }
+
+    /// every occurrence sharing the earliest start after `instant`, in order
+    fn next_starts(&self, instant: NaiveDateTime) -> Vec<Occurrence<'_>> {
+        // the first series to start afterwards bounds the search, series
+        // that started before can still have occurrences in between
+        let probe = Event::probe(instant + Duration::nanoseconds(1));
+        let bound = self
+            .evts
+            .range::<Event, _>(&probe..)
+            .find_map(|evt| self.first_start_after(evt, instant));
+        let end = match bound {
+            Some(bound) => bound + Duration::nanoseconds(1),
+            None => match self.index.max_end() {
+                Some(end) => end,
+                None => return Vec::new(),
+            },
+        };
+
+        let mut found: Vec<_> = self
+            .index
+            .overlapping(instant, end)
+            .filter_map(|evt| match evt.recurrence_id() {
+                Some(_) => (evt.start() > instant).then(|| self.occurrence(evt, evt.start())),
+                None => Some(self.occurrence(evt, self.first_start_after(evt, instant)?)),
+            })
+            .collect();
+        let first = found.iter().map(|occ| occ.start).min();
+        found.retain(|occ| Some(occ.start) == first);
+        found.sort();
+        found
+    }
+
+    /// every occurrence sharing the latest start before `instant`, in order
+    fn previous_starts(&self, instant: NaiveDateTime) -> Vec<Occurrence<'_>> {
+        let probe = Event::probe(instant);
+        let bound = self
+            .evts
+            .range::<Event, _>(..&probe)
+            .rev()
+            .find_map(|evt| self.last_start_before(evt, instant));
+        let start = bound.unwrap_or(NaiveDateTime::MIN);
+
+        let mut found: Vec<_> = self
+            .index
+            .overlapping(start, instant)
+            .filter_map(|evt| match evt.recurrence_id() {
+                Some(_) => (evt.start() < instant).then(|| self.occurrence(evt, evt.start())),
+                None => Some(self.occurrence(evt, self.last_start_before(evt, instant)?)),
+            })
+            .collect();
+        let last = found.iter().map(|occ| occ.start).max();
+        found.retain(|occ| Some(occ.start) == last);
+        found.sort();
+        found
+    }
+
+    /// the first start of a series after `instant` that has not been
+    /// overridden
+    fn first_start_after(&self, evt: &Event, instant: NaiveDateTime) -> Option<NaiveDateTime> {
+        let overridden = self.overrides.get(evt.id());
+        evt.occurrences_from(instant)
+            .skip_while(|start| *start <= instant)
+            .find(|start| overridden.is_none_or(|o| !o.contains_key(start)))
+    }
+
+    /// the last start of a series before `instant` that has not been
+    /// overridden
+    fn last_start_before(&self, evt: &Event, instant: NaiveDateTime) -> Option<NaiveDateTime> {
+        let overridden = self.overrides.get(evt.id());
+        evt.occurrences()
+            .take_while(|start| *start < instant)
+            .filter(|start| overridden.is_none_or(|o| !o.contains_key(start)))
+            .last()
+    }
+
+    /// the occurrence of an indexed event starting at `start`
+    fn occurrence<'a>(&self, evt: &'a Arc<Event>, start: NaiveDateTime) -> Occurrence<'a> {
+        let recurrence_id = match evt.recurrence_id() {
+            Some(recurrence_id) => Some(recurrence_id),
+            None => evt.is_recurring().then_some(start),
+        };
+        Occurrence {
+            start,
+            end: start + evt.duration(),
+            event: evt,
+            recurrence_id,
+        }
+    }
+
+    /// every occurrence overlapping [start, end) in chronological order
+    fn occurrences_overlapping(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Vec<Occurrence<'_>> {
+        let mut found = Vec::new();
+        for evt in self.index.overlapping(start, end) {
+            // overrides are indexed on their own, already moved, times
+            if let Some(recurrence_id) = evt.recurrence_id() {
+                found.push(Occurrence {
+                    start: evt.start(),
+                    end: evt.end(),
+                    event: evt,
+                    recurrence_id: Some(recurrence_id),
+                });
+                continue;
+            }
+
+            let overridden = self.overrides.get(evt.id());
+            let duration = evt.duration();
+            let recurring = evt.is_recurring();
+            found.extend(
+                // occurrences starting this long before the window still
+                // overlap it
+                evt.occurrences_from(
+                    start
+                        .checked_sub_signed(duration)
+                        .unwrap_or(NaiveDateTime::MIN),
+                )
+                .take_while(|occ_start| *occ_start < end)
+                .filter(|occ_start| overridden.is_none_or(|o| !o.contains_key(occ_start)))
+                .map(|occ_start| Occurrence {
+                    start: occ_start,
+                    end: occ_start + duration,
+                    event: evt,
+                    recurrence_id: recurring.then_some(occ_start),
+                })
+                .filter(|occ| occ.end > start),
+            );
+        }
+
+        // occurrences of different series interleave
+        found.sort();
+        found
+    }
+
+    /// inserts a task into the calendar, returning true if it is new and
+    /// false if it replaced a task with the same id
+    pub fn add_task(&mut self, task: Task) -> bool {
+        self.recorded(|cal| cal.insert_task(task))
+    }
+
+    /// removes a task from the calendar, returning it or None if no task
+    /// with that id exists
+    pub fn remove_task<T: IntoUuid>(&mut self, id: T) -> Option<Task> {
+        let id = id.into_uuid();
+        self.recorded(|cal| cal.take_task(id))
+    }
+
+    /// inserts a task, returning true if it is new
+    fn insert_task(&mut self, task: Task) -> bool {
+        let id = *task.id();
+        self.touch(Key::Task(id));
+        let old = self.take_task(id);
+        if let Some(due) = task.due() {
+            self.due.insert((due, id));
+        }
+        self.tasks.insert(id, task);
+        old.is_none()
+    }
+
+    /// removes a task, returning it
+    fn take_task(&mut self, id: Uuid) -> Option<Task> {
+        self.touch(Key::Task(id));
+        let task = self.tasks.remove(&id)?;
+        if let Some(due) = task.due() {
+            self.due.remove(&(due, *task.id()));
+        }
+        Some(task)
+    }
+
+    /// edits the task with the given id by passing a copy of it to `f`,
+    /// returning the task as it was before
+    ///
+    /// ```
+    /// use calib::{EventCalendar, Task, TaskStatus};
+    /// use chrono::{TimeZone, Utc};
+    ///
+    /// let task = Task::new("Renew passport".into());
+    /// let id = *task.id();
+    /// let mut cal = EventCalendar::default();
+    /// cal.add_task(task);
+    ///
+    /// let done = Utc.with_ymd_and_hms(2023, 1, 6, 12, 0, 0).unwrap();
+    /// cal.update_task(id, |task| Ok(task.complete(done))).unwrap();
+    /// assert_eq!(cal.get_task(id).unwrap().status(), TaskStatus::Completed);
+    /// ```
+    pub fn update_task<T, F>(&mut self, id: T, f: F) -> Result<Task, EventError>
+    where
+        T: TryIntoUuid,
+        F: FnOnce(Task) -> Result<Task, EventError>,
+    {
+        let id = id.try_into_uuid()?;
+        let old = self.tasks.get(&id).ok_or(EventError::NotFound(id))?;
+        // the task keeps its id whatever `f` does
+        let new = f(old.clone())?.with_id(id);
+        let old = old.clone();
+        self.add_task(new);
+        Ok(old)
+    }
+
+    /// return a task from its id
+    pub fn get_task<T: IntoUuid>(&self, id: T) -> Option<&Task> {
+        self.tasks.get(&id.into_uuid())
+    }
+
+    /// return an iterator over every task in the order they are due, tasks
+    /// without a due date come last
+    pub fn tasks(&self) -> impl Iterator<Item = &Task> {
+        self.due
+            .iter()
+            .map(|(_, id)| &self.tasks[id])
+            .chain(self.tasks.values().filter(|task| task.due().is_none()))
+    }
+
+    /// return an iterator over the tasks due within [start, end) in the
+    /// order they are due, whether or not they are done
+    pub fn tasks_due_in_range(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> impl Iterator<Item = &Task> {
+        let range = (start.min(end), Uuid::nil())..(end, Uuid::nil());
+        self.due.range(range).map(|(_, id)| &self.tasks[id])
+    }
+
+    /// return an iterator over the tasks that are still open at `now` but
+    /// were due by then, the longest overdue first
+    pub fn overdue_tasks(&self, now: NaiveDateTime) -> impl Iterator<Item = &Task> {
+        self.due
+            .range(..=(now, Uuid::max()))
+            .map(|(_, id)| &self.tasks[id])
+            .filter(move |task| task.is_overdue(now))
+    }
+
+    /// inserts a journal entry into the calendar, returning true if it is new
+    /// and false if it replaced an entry with the same id
+    pub fn add_journal(&mut self, journal: Journal) -> bool {
+        self.recorded(|cal| cal.insert_journal(journal))
+    }
+
+    /// removes a journal entry from the calendar, returning it or None if no
+    /// entry with that id exists
+    pub fn remove_journal<T: IntoUuid>(&mut self, id: T) -> Option<Journal> {
+        let id = id.into_uuid();
+        self.recorded(|cal| cal.take_journal(id))
+    }
+
+    /// edits the journal entry with the given id by passing a copy of it to
+    /// `f`, returning the entry as it was before
+    ///
+    /// ```
+    /// use calib::{EventCalendar, Journal};
+    /// use chrono::NaiveDate;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let notes = Journal::new(date, "Planning".into());
+    /// let id = *notes.id();
+    /// let mut cal = EventCalendar::default();
+    /// cal.add_journal(notes);
+    ///
+    /// cal.update_journal(id, |notes| Ok(notes.set_body("Ship on Friday".into())))
+    ///     .unwrap();
+    /// assert_eq!(cal.get_journal(id).unwrap().body(), "Ship on Friday");
+    /// ```
+    pub fn update_journal<T, F>(&mut self, id: T, f: F) -> Result<Journal, EventError>
+    where
+        T: TryIntoUuid,
+        F: FnOnce(Journal) -> Result<Journal, EventError>,
+    {
+        let id = id.try_into_uuid()?;
+        let old = self.journals.get(&id).ok_or(EventError::NotFound(id))?;
+        // the entry keeps its id whatever `f` does
+        let new = f(old.clone())?.with_id(id);
+        let old = old.clone();
+        self.add_journal(new);
+        Ok(old)
+    }
+
+    /// inserts a journal entry, returning true if it is new
+    fn insert_journal(&mut self, journal: Journal) -> bool {
+        let id = *journal.id();
+        self.touch(Key::Journal(id));
+        let old = self.take_journal(id);
+        self.dated.insert((journal.date(), id));
+        self.journals.insert(id, journal);
+        old.is_none()
+    }
+
+    /// removes a journal entry, returning it
+    fn take_journal(&mut self, id: Uuid) -> Option<Journal> {
+        self.touch(Key::Journal(id));
+        let journal = self.journals.remove(&id)?;
+        self.dated.remove(&(journal.date(), *journal.id()));
+        Some(journal)
+    }
+
+    /// return a journal entry from its id
+    pub fn get_journal<T: IntoUuid>(&self, id: T) -> Option<&Journal> {
+        self.journals.get(&id.into_uuid())
+    }
+
+    /// return an iterator over every journal entry in date order
+    pub fn journals(&self) -> impl Iterator<Item = &Journal> {
+        self.dated.iter().map(|(_, id)| &self.journals[id])
+    }
+
+    /// return an iterator over the journal entries for the days in
+    /// [start, end) in date order
+    pub fn journals_in_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> impl Iterator<Item = &Journal> {
+        let range = (start.min(end), Uuid::nil())..(end, Uuid::nil());
+        self.dated.range(range).map(|(_, id)| &self.journals[id])
+    }
+
+    /// return an iterator over the journal entries for a single day
+    pub fn journals_on(&self, date: NaiveDate) -> impl Iterator<Item = &Journal> {
+        let range = (date, Uuid::nil())..=(date, Uuid::max());
+        self.dated.range(range).map(|(_, id)| &self.journals[id])
+    }
+
+    /// return an iterator over every event in the calendar in chronological
+    /// order, overrides are not included
+    pub fn events(&self) -> impl Iterator<Item = &Arc<Event>> {
+        self.evts.iter()
     }
 
     /// return the first event in the Calendar
-    pub fn first_event(&self) -> Option<&Rc<Event>> {
+    pub fn first_event(&self) -> Option<&Arc<Event>> {
         self.evts.first()
     }
 
     /// return a reference to an event from it's ID
-    pub fn get<T: IntoUuid>(&self, id: T) -> Option<&Rc<Event>> {
+    pub fn get<T: IntoUuid>(&self, id: T) -> Option<&Arc<Event>> {
         self.ids.get(&id.into_uuid())
     }
+
+    /// return a reference to an event from an id that may not be valid, such
+    /// as one given by a user
+    ///
+    /// ```
+    /// use calib::{Event, EventCalendar, EventError};
+    /// use chrono::NaiveDate;
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// let evt = Event::new("Dentist".to_string(), &NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+    /// let id = evt.id().simple().to_string();
+    /// cal.add_event(evt);
+    ///
+    /// assert_eq!(cal.try_get(&id).unwrap().name(), "Dentist");
+    /// assert!(matches!(cal.try_get("garbage"), Err(EventError::InvalidUuid(..))));
+    /// assert!(matches!(
+    ///     cal.try_get("00000000-0000-0000-0000-000000000000"),
+    ///     Err(EventError::NotFound(_))
+    /// ));
+    /// ```
+    pub fn try_get<T: TryIntoUuid>(&self, id: T) -> Result<&Arc<Event>, EventError> {
+        let id = id.try_into_uuid()?;
+        self.ids.get(&id).ok_or(EventError::NotFound(id))
+    }
+
+    /// return the override of a single occurrence from a series id that may
+    /// not be valid
+    pub fn try_get_override<T: TryIntoUuid>(
+        &self,
+        series: T,
+        recurrence_id: NaiveDateTime,
+    ) -> Result<&Arc<Event>, EventError> {
+        let series = series.try_into_uuid()?;
+        self.get_override(series, recurrence_id)
+            .ok_or(EventError::NoSuchOccurrence(series, recurrence_id))
+    }
+
+    /// remove an event from ids, evts and the index, leaving its overrides
+    fn take(&mut self, id: &Uuid) -> Option<Arc<Event>> {
+        self.ids.get(id)?;
+        self.touch(Key::Event(*id, None));
+        let old = self.ids.remove(id)?;
+        self.evts.remove(&old);
+        self.index.remove(&old);
+        Some(old)
+    }
+
+    /// a copy of the occurrence of `series` starting at `recurrence_id`,
+    /// failing if the series does not produce such an occurrence
+    fn occurrence_of(
+        &self,
+        series: Uuid,
+        recurrence_id: NaiveDateTime,
+    ) -> Result<Event, EventError> {
+        let evt = self.ids.get(&series).ok_or(EventError::NotFound(series))?;
+        evt.occurrences_from(recurrence_id)
+            .take_while(|start| *start <= recurrence_id)
+            .any(|start| start == recurrence_id)
+            .then(|| evt.occurrence_at(recurrence_id))
+            .ok_or(EventError::NoSuchOccurrence(series, recurrence_id))
+    }
+
+    /// store an override, returning the one it replaced
+    fn insert_override(
+        &mut self,
+        series: Uuid,
+        recurrence_id: NaiveDateTime,
+        evt: Arc<Event>,
+    ) -> Option<Arc<Event>> {
+        self.touch(Key::Event(series, Some(recurrence_id)));
+        let old = self
+            .overrides
+            .entry(series)
+            .or_default()
+            .insert(recurrence_id, Arc::clone(&evt));
+        if let Some(old) = &old {
+            self.index.remove(old);
+        }
+        let end = evt.end();
+        self.index.insert(evt, end);
+        old
+    }
+
+    /// remove every override of a series, returning them
+    fn take_overrides(&mut self, series: Uuid) -> BTreeMap<NaiveDateTime, Arc<Event>> {
+        let overrides = self.overrides.remove(&series).unwrap_or_default();
+        for (recurrence_id, evt) in &overrides {
+            if let Some(before) = &mut self.before {
+                let key = Key::Event(series, Some(*recurrence_id));
+                before
+                    .entry(key)
+                    .or_insert_with(|| Change::PutEvent(Event::clone(evt)));
+            }
+            self.index.remove(evt);
+        }
+        overrides
+    }
+
+    /// make a change whatever the overlap policy. Removing a series leaves
+    /// its overrides, which are removed by their own changes
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Change, Event, EventCalendar};
+    /// use chrono::NaiveDate;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let event = Event::new("Standup".into(), &date);
+    /// let id = *event.id();
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// cal.apply(Change::PutEvent(event));
+    /// assert!(cal.get(id).is_some());
+    /// cal.apply(Change::RemoveEvent(id, None));
+    /// assert!(cal.get(id).is_none());
+    /// ```
+    pub fn apply(&mut self, change: Change) {
+        self.recorded(|cal| cal.restore(change));
+    }
+
+    /// make a change without recording it
+    pub(crate) fn restore(&mut self, change: Change) {
+        match change {
+            Change::PutEvent(evt) => {
+                self.insert(evt);
+            }
+            Change::RemoveEvent(id, None) => {
+                self.take(&id);
+            }
+            Change::RemoveEvent(id, Some(recurrence_id)) => {
+                self.take_override(id, recurrence_id);
+            }
+            Change::PutTask(task) => {
+                self.insert_task(task);
+            }
+            Change::RemoveTask(id) => {
+                self.take_task(id);
+            }
+            Change::PutJournal(journal) => {
+                self.insert_journal(journal);
+            }
+            Change::RemoveJournal(id) => {
+                self.take_journal(id);
+            }
+            Change::SetPolicy(policy) => {
+                self.policy = policy;
+            }
+        }
+    }
+
+    /// make several changes with `f` as a single command, undone and redone
+    /// all at once. If `f` fails every change it made is undone. A
+    /// transaction inside another is part of it
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Event, EventCalendar, Task};
+    /// use chrono::NaiveDate;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let mut cal = EventCalendar::default();
+    /// cal.transaction(|cal| {
+    ///     cal.add_event(Event::new("Review".into(), &date));
+    ///     cal.add_task(Task::new("Prepare slides".into()));
+    ///     Ok(())
+    /// })
+    /// .unwrap();
+    ///
+    /// assert!(cal.undo());
+    /// assert_eq!((cal.events().count(), cal.tasks().count()), (0, 0));
+    /// assert!(cal.redo());
+    /// assert_eq!((cal.events().count(), cal.tasks().count()), (1, 1));
+    /// ```
+    pub fn transaction<R, F>(&mut self, f: F) -> Result<R, EventError>
+    where
+        F: FnOnce(&mut Self) -> Result<R, EventError>,
+    {
+        if self.before.is_some() {
+            return f(self);
+        }
+        self.before = Some(BTreeMap::new());
+        let result = f(self);
+        let command = self.take_command();
+        match result {
+            Ok(_) => self.history.push(command),
+            Err(_) => {
+                for change in command.undo {
+                    self.restore(change);
+                }
+            }
+        }
+        result
+    }
+
+    /// undo the latest command, returning false if there is nothing to undo
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Event, EventCalendar};
+    /// use chrono::NaiveDate;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let event = Event::new("Review".into(), &date);
+    /// let id = *event.id();
+    ///
+    /// let mut cal = EventCalendar::default();
+    /// cal.add_event(event);
+    /// cal.update(id, |mut evt| {
+    ///     evt.set_name("Retro".into());
+    ///     Ok(evt)
+    /// })
+    /// .unwrap();
+    ///
+    /// assert!(cal.undo());
+    /// assert_eq!(cal.get(id).unwrap().name(), "Review");
+    /// assert!(cal.redo());
+    /// assert_eq!(cal.get(id).unwrap().name(), "Retro");
+    /// ```
+    pub fn undo(&mut self) -> bool {
+        self.undo_changes().is_some()
+    }
+
+    /// make the latest undone command again, returning false if there is
+    /// nothing to redo. Any new command clears what can be redone
+    pub fn redo(&mut self) -> bool {
+        self.redo_changes().is_some()
+    }
+
+    /// returns true if there is a command to undo
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    /// returns true if there is an undone command to redo
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    /// returns how many commands can be undone, 100 by default
+    pub fn history_limit(&self) -> usize {
+        self.history.limit()
+    }
+
+    /// Set/Change how many commands can be undone, dropping the oldest ones
+    /// beyond the limit. With 0 nothing is recorded
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    /// forget every command, so none can be undone or redone
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    /// undo the latest command, returning the changes that undid it
+    pub(crate) fn undo_changes(&mut self) -> Option<Vec<Change>> {
+        let changes = self.history.undo()?.undo.clone();
+        for change in changes.iter().cloned() {
+            self.restore(change);
+        }
+        Some(changes)
+    }
+
+    /// make the latest undone command again, returning its changes
+    pub(crate) fn redo_changes(&mut self) -> Option<Vec<Change>> {
+        let changes = self.history.redo()?.changes.clone();
+        for change in changes.iter().cloned() {
+            self.restore(change);
+        }
+        Some(changes)
+    }
+
+    /// the changes made so far by the command being recorded
+    pub(crate) fn pending_changes(&self) -> Vec<Change> {
+        let Some(before) = &self.before else {
+            return Vec::new();
+        };
+        before
+            .iter()
+            .map(|(key, before)| (self.current(*key), before))
+            .filter(|(after, before)| after != *before)
+            .map(|(after, _)| after)
+            .collect()
+    }
+
+    /// run `f` as a single command unless it is part of one already, so
+    /// it can be undone
+    fn recorded<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        if self.before.is_some() || self.history.limit() == 0 {
+            return f(self);
+        }
+        self.before = Some(BTreeMap::new());
+        let result = f(self);
+        let command = self.take_command();
+        self.history.push(command);
+        result
+    }
+
+    /// stop recording the command, returning its changes along with the
+    /// changes that undo them
+    fn take_command(&mut self) -> Command {
+        let mut changes = Vec::new();
+        let mut undo = Vec::new();
+        for (key, before) in self.before.take().unwrap_or_default() {
+            let after = self.current(key);
+            if after != before {
+                changes.push(after);
+                undo.push(before);
+            }
+        }
+        Command { changes, undo }
+    }
+
+    /// remember how an item was before it is changed, if changes are being
+    /// recorded
+    fn touch(&mut self, key: Key) {
+        if self.before.as_ref().is_some_and(|b| !b.contains_key(&key)) {
+            let current = self.current(key);
+            if let Some(before) = &mut self.before {
+                before.insert(key, current);
+            }
+        }
+    }
+
+    /// the change that puts an item back as it is now
+    fn current(&self, key: Key) -> Change {
+        let current = match key {
+            Key::Event(id, None) => self.ids.get(&id).map(|e| Change::PutEvent(Event::clone(e))),
+            Key::Event(id, Some(recurrence_id)) => self
+                .get_override(id, recurrence_id)
+                .map(|e| Change::PutEvent(Event::clone(e))),
+            Key::Task(id) => self.tasks.get(&id).cloned().map(Change::PutTask),
+            Key::Journal(id) => self.journals.get(&id).cloned().map(Change::PutJournal),
+        };
+        current.unwrap_or_else(|| key.removal())
+    }
+}
+
+// calendars are written as the list of their events followed by the
+// overrides, the index and lookup tables are rebuilt when reading them back
+#[derive(Serialize)]
+struct CalendarRef<'a> {
+    events: Vec<&'a Event>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tasks: Vec<&'a Task>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    journals: Vec<&'a Journal>,
+    #[serde(skip_serializing_if = "OverlapPolicy::is_allow")]
+    policy: OverlapPolicy,
+}
+
+#[derive(Deserialize)]
+struct CalendarFields {
+    events: Vec<Event>,
+    #[serde(default)]
+    tasks: Vec<Task>,
+    #[serde(default)]
+    journals: Vec<Journal>,
+    #[serde(default)]
+    policy: OverlapPolicy,
+}
+
+impl Serialize for EventCalendar {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let overrides = self.overrides.values().flat_map(|o| o.values());
+        CalendarRef {
+            events: self.evts.iter().chain(overrides).map(|e| &**e).collect(),
+            tasks: self.tasks.values().collect(),
+            journals: self.journals.values().collect(),
+            policy: self.policy,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EventCalendar {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = CalendarFields::deserialize(deserializer)?;
+        // the events were all allowed in when they were saved
+        let mut cal = EventCalendar::default();
+        for event in fields.events {
+            cal.insert(event);
+        }
+        for task in fields.tasks {
+            cal.insert_task(task);
+        }
+        for journal in fields.journals {
+            cal.insert_journal(journal);
+        }
+        cal.policy = fields.policy;
+        Ok(cal)
+    }
 }
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::{Mutex, PoisonError};
+
+/// Where the current time comes from, so anything that depends on it can be
+/// driven by a clock under the caller's control
+pub trait Clock {
+    /// returns the current instant
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that only moves when told to, for tests and simulations. It can
+/// be shared by reference and moved while others are holding it
+///
+/// # Examples
+/// ```
+/// use calib::{Clock, ManualClock};
+/// use chrono::{Duration, TimeZone, Utc};
+///
+/// let clock = ManualClock::new(Utc.with_ymd_and_hms(2023, 1, 2, 9, 0, 0).unwrap());
+/// clock.advance(Duration::minutes(30));
+/// assert_eq!(clock.now(), Utc.with_ymd_and_hms(2023, 1, 2, 9, 30, 0).unwrap());
+/// ```
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl ManualClock {
+    /// create a clock stopped at `now`
+    pub fn new(now: DateTime<Utc>) -> Self {
+        ManualClock {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// move the clock to `now`, which may be in the past
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) = now;
+    }
+
+    /// move the clock forward by `by`, or back if it is negative, stopping
+    /// at the first or last instant that can be represented
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(PoisonError::into_inner);
+        *now = now
+            .checked_add_signed(by)
+            .unwrap_or(match by < Duration::zero() {
+                true => DateTime::<Utc>::MIN_UTC,
+                false => DateTime::<Utc>::MAX_UTC,
+            });
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
@@ -1,17 +1,220 @@
 use super::*;
-use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
-use serde::Serialize;
+use chrono::Utc;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::iter::Peekable;
 use uuid::Uuid;
 
 // NOTE: Keep fields in order based on how comparisons should go,
 // see Ord/PartialOrd Trait derive documentation
 /// Struct to represent a given event on the calendar
-#[derive(PartialOrd, Ord, PartialEq, Eq, Debug, Serialize, Clone)]
+#[derive(PartialOrd, Ord, PartialEq, Eq, Debug, Deserialize, Clone)]
+#[serde(try_from = "EventFields")]
 pub struct Event {
     start: NaiveDateTime,
     end: NaiveDateTime,
     name: String,
     id: Uuid,
+    rrule: Option<RRule>,
+    rdates: BTreeSet<NaiveDateTime>,
+    exdates: BTreeSet<NaiveDateTime>,
+    recurrence_id: Option<NaiveDateTime>,
+    related_to: Option<Uuid>,
+    tz: EventTz,
+    description: Option<String>,
+    location: Option<String>,
+    status: Option<Status>,
+    transparency: Transparency,
+    priority: Option<u8>,
+    url: Option<String>,
+    organizer: Option<String>,
+    attendees: Vec<Attendee>,
+    alarms: Vec<Alarm>,
+    // start and end are midnights and the event lasts whole days
+    all_day: bool,
+}
+
+/// A start or end as it is serialized, all day events are written with
+/// dates alone
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(untagged)]
+enum When {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+}
+
+impl When {
+    fn new(dt: NaiveDateTime, all_day: bool) -> Self {
+        match all_day {
+            true => When::Date(dt.date()),
+            false => When::DateTime(dt),
+        }
+    }
+
+    fn date_time(self) -> NaiveDateTime {
+        match self {
+            When::Date(date) => NaiveDateTime::new(date, NaiveTime::MIN),
+            When::DateTime(dt) => dt,
+        }
+    }
+}
+
+/// The fields of an event as they are serialized
+#[derive(Serialize)]
+struct EventRef<'a> {
+    start: When,
+    end: When,
+    name: &'a str,
+    id: &'a Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rrule: &'a Option<RRule>,
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    rdates: &'a BTreeSet<NaiveDateTime>,
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    exdates: &'a BTreeSet<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recurrence_id: &'a Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    related_to: &'a Option<Uuid>,
+    #[serde(skip_serializing_if = "EventTz::is_floating")]
+    tz: &'a EventTz,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: &'a Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: &'a Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: &'a Option<Status>,
+    #[serde(skip_serializing_if = "Transparency::is_opaque")]
+    transparency: &'a Transparency,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: &'a Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: &'a Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    organizer: &'a Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attendees: &'a Vec<Attendee>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alarms: &'a Vec<Alarm>,
+}
+
+impl Serialize for Event {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        EventRef {
+            start: When::new(self.start, self.all_day),
+            end: When::new(self.end, self.all_day),
+            name: &self.name,
+            id: &self.id,
+            rrule: &self.rrule,
+            rdates: &self.rdates,
+            exdates: &self.exdates,
+            recurrence_id: &self.recurrence_id,
+            related_to: &self.related_to,
+            tz: &self.tz,
+            description: &self.description,
+            location: &self.location,
+            status: &self.status,
+            transparency: &self.transparency,
+            priority: &self.priority,
+            url: &self.url,
+            organizer: &self.organizer,
+            attendees: &self.attendees,
+            alarms: &self.alarms,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// The fields of an event as they are deserialized, before the start and
+/// end have been checked
+#[derive(Deserialize)]
+struct EventFields {
+    start: When,
+    end: When,
+    name: String,
+    id: Uuid,
+    #[serde(default)]
+    rrule: Option<RRule>,
+    #[serde(default)]
+    rdates: BTreeSet<NaiveDateTime>,
+    #[serde(default)]
+    exdates: BTreeSet<NaiveDateTime>,
+    #[serde(default)]
+    recurrence_id: Option<NaiveDateTime>,
+    #[serde(default)]
+    related_to: Option<Uuid>,
+    #[serde(default)]
+    tz: EventTz,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    location: Option<String>,
+    #[serde(default)]
+    status: Option<Status>,
+    #[serde(default)]
+    transparency: Transparency,
+    #[serde(default)]
+    priority: Option<u8>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    organizer: Option<String>,
+    #[serde(default)]
+    attendees: Vec<Attendee>,
+    #[serde(default)]
+    alarms: Vec<Alarm>,
+}
+
+impl TryFrom<EventFields> for Event {
+    type Error = EventError;
+
+    fn try_from(fields: EventFields) -> Result<Self, Self::Error> {
+        // an event given by dates alone lasts whole days
+        let all_day = matches!((fields.start, fields.end), (When::Date(_), When::Date(_)));
+        let (start, end) = (fields.start.date_time(), fields.end.date_time());
+        if !Event::start_end_times_valid(&start, &end) {
+            return Err(EventError::InvalidEndTime);
+        }
+        // the same checks as the setters, but addresses are not rewritten
+        if let Some(priority) = fields.priority {
+            props::check_priority(priority)?;
+        }
+        if let Some(url) = &fields.url {
+            props::check_uri("URL", url)?;
+        }
+        if let Some(organizer) = &fields.organizer {
+            props::check_uri("ORGANIZER", organizer)?;
+        }
+        for attendee in &fields.attendees {
+            props::check_uri("ATTENDEE", attendee.address())?;
+        }
+        for alarm in &fields.alarms {
+            alarm.check()?;
+        }
+        Ok(Event {
+            start,
+            end,
+            name: fields.name,
+            id: fields.id,
+            rrule: fields.rrule,
+            rdates: fields.rdates,
+            exdates: fields.exdates,
+            recurrence_id: fields.recurrence_id,
+            related_to: fields.related_to,
+            tz: fields.tz,
+            description: fields.description,
+            location: fields.location,
+            status: fields.status,
+            transparency: fields.transparency,
+            priority: fields.priority.filter(|p| *p != 0),
+            url: fields.url,
+            organizer: fields.organizer,
+            attendees: fields.attendees,
+            alarms: fields.alarms,
+            all_day,
+        })
+    }
 }
 
 impl Event {
@@ -40,32 +243,663 @@ impl Event {
         &self.id
     }
 
-    /// Create an Event with a name and date, defaults to an
-    /// all day event starting at 00:00:00 and ending at 23:59:59
+    /// returns true if the event overlaps the half-open range [start, end),
+    /// an event that ends exactly when the range starts does not overlap it
+    pub fn overlaps(&self, start: NaiveDateTime, end: NaiveDateTime) -> bool {
+        self.start < end && self.end > start
+    }
+
+    /// returns true if the event is taking place at the given instant,
+    /// the end of an event is exclusive
+    pub fn contains(&self, instant: NaiveDateTime) -> bool {
+        self.start <= instant && instant < self.end
+    }
+
+    /// returns the time zone the event's start and end are written in
+    pub fn tz(&self) -> EventTz {
+        self.tz
+    }
+
+    /// Set/Change the time zone of the event, keeping its wall clock times.
+    /// A 09:00 event stays at 09:00 but in the new zone
+    pub fn set_tz(self, tz: EventTz) -> Self {
+        Event { tz, ..self }
+    }
+
+    /// Move the event into another time zone, keeping the instants it takes
+    /// place at, so its wall clock times change. Floating events have no
+    /// instant to keep and are only relabeled, as are all day events.
+    /// Converting to floating keeps the wall clock time of the old zone
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Disambiguation, Event, EventTz};
+    /// use chrono::{NaiveDate, NaiveTime};
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 7, 1).unwrap();
+    /// let event = Event::new("Call".into(), &date)
+    ///     .set_start_time(NaiveTime::from_hms_opt(9, 0, 0).unwrap())
+    ///     .unwrap()
+    ///     .set_tz("America/New_York".parse().unwrap());
+    ///
+    /// let event = event.convert_tz("Europe/Paris".parse().unwrap(), Disambiguation::Reject).unwrap();
+    /// assert_eq!(event.start().time(), NaiveTime::from_hms_opt(15, 0, 0).unwrap());
+    /// ```
+    pub fn convert_tz(self, tz: EventTz, policy: Disambiguation) -> Result<Self, EventError> {
+        // the days of an all day event are the same everywhere
+        if self.tz.is_floating() || tz.is_floating() || self.all_day {
+            return Ok(self.set_tz(tz));
+        }
+
+        let from = self.tz;
+        let convert = |local: NaiveDateTime| {
+            Ok::<_, EventError>(tz.from_utc(&from.to_utc(local, &Utc, policy)?, &Utc))
+        };
+        let rrule = match self.rrule {
+            Some(rule) => match rule.until() {
+                Some(until) => Some(rule.unbounded().set_until(convert(until)?)?),
+                None => Some(rule),
+            },
+            None => None,
+        };
+
+        Ok(Event {
+            start: convert(self.start)?,
+            end: convert(self.end)?,
+            rrule,
+            rdates: self
+                .rdates
+                .iter()
+                .copied()
+                .map(convert)
+                .collect::<Result<_, _>>()?,
+            exdates: self
+                .exdates
+                .iter()
+                .copied()
+                .map(convert)
+                .collect::<Result<_, _>>()?,
+            recurrence_id: self.recurrence_id.map(convert).transpose()?,
+            tz,
+            ..self
+        })
+    }
+
+    /// returns the start of the event as an instant in `zone`, floating
+    /// events are read as wall clock time in `zone`
+    pub fn start_in<Z: TimeZone>(
+        &self,
+        zone: &Z,
+        policy: Disambiguation,
+    ) -> Result<DateTime<Z>, EventError> {
+        Ok(self
+            .tz
+            .to_utc(self.start, zone, policy)?
+            .with_timezone(zone))
+    }
+
+    /// returns the end of the event as an instant in `zone`, floating
+    /// events are read as wall clock time in `zone`
+    pub fn end_in<Z: TimeZone>(
+        &self,
+        zone: &Z,
+        policy: Disambiguation,
+    ) -> Result<DateTime<Z>, EventError> {
+        Ok(self.tz.to_utc(self.end, zone, policy)?.with_timezone(zone))
+    }
+
+    /// returns how long each occurrence of the event lasts
+    pub fn duration(&self) -> Duration {
+        self.end.signed_duration_since(self.start)
+    }
+
+    /// returns the recurrence rule of the event, if it repeats
+    pub fn recurrence(&self) -> Option<&RRule> {
+        self.rrule.as_ref()
+    }
+
+    /// returns true if the event repeats, through a rule or extra dates
+    pub fn is_recurring(&self) -> bool {
+        self.rrule.is_some() || !self.rdates.is_empty()
+    }
+
+    /// Set/Change/Clear the rule the event repeats with, the event's start
+    /// and end become those of the first occurrence in the series
+    pub fn set_recurrence(self, rrule: Option<RRule>) -> Self {
+        Event { rrule, ..self }
+    }
+
+    /// returns the extra occurrence starts added on top of the rule (RDATE)
+    pub fn rdates(&self) -> impl Iterator<Item = &NaiveDateTime> {
+        self.rdates.iter()
+    }
+
+    /// returns the occurrence starts excluded from the series (EXDATE)
+    pub fn exdates(&self) -> impl Iterator<Item = &NaiveDateTime> {
+        self.exdates.iter()
+    }
+
+    /// Add an extra occurrence to the series starting at `start`
+    pub fn add_rdate(mut self, start: NaiveDateTime) -> Self {
+        self.rdates.insert(start);
+        self
+    }
+
+    /// Remove an extra occurrence previously added with [`Event::add_rdate`]
+    pub fn remove_rdate(mut self, start: NaiveDateTime) -> Self {
+        self.rdates.remove(&start);
+        self
+    }
+
+    /// Exclude the occurrence starting at `start` from the series
+    pub fn add_exdate(mut self, start: NaiveDateTime) -> Self {
+        self.exdates.insert(start);
+        self
+    }
+
+    /// Restore an occurrence previously excluded with [`Event::add_exdate`]
+    pub fn remove_exdate(mut self, start: NaiveDateTime) -> Self {
+        self.exdates.remove(&start);
+        self
+    }
+
+    /// returns the original start of the occurrence this event overrides
+    /// (RECURRENCE-ID), only set for overrides of a recurring series
+    pub fn recurrence_id(&self) -> Option<NaiveDateTime> {
+        self.recurrence_id
+    }
+
+    /// returns the id of the event this one is related to (RELATED-TO), e.g.
+    /// the series a recurring event was split from
+    pub fn related_to(&self) -> Option<&Uuid> {
+        self.related_to.as_ref()
+    }
+
+    /// Set/Change/Clear the id of the event this one is related to
+    pub fn set_related_to(self, related_to: Option<Uuid>) -> Self {
+        Event { related_to, ..self }
+    }
+
+    /// returns true if the event takes up time, it is neither transparent
+    /// nor cancelled
+    pub fn is_busy(&self) -> bool {
+        self.transparency.is_opaque() && self.status != Some(Status::Cancelled)
+    }
+
+    /// returns the longer description of the event
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Set/Change/Clear the description of the event
+    pub fn set_description(self, description: Option<String>) -> Self {
+        Event {
+            description,
+            ..self
+        }
+    }
+
+    /// returns where the event takes place
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// Set/Change/Clear where the event takes place
+    pub fn set_location(self, location: Option<String>) -> Self {
+        Event { location, ..self }
+    }
+
+    /// returns whether the event is tentative, confirmed or cancelled, if
+    /// that has been given
+    pub fn status(&self) -> Option<Status> {
+        self.status
+    }
+
+    /// Set/Change/Clear the status of the event
+    pub fn set_status(self, status: Option<Status>) -> Self {
+        Event { status, ..self }
+    }
+
+    /// returns whether the event's time is busy or still free
+    pub fn transparency(&self) -> Transparency {
+        self.transparency
+    }
+
+    /// Set/Change whether the event's time is busy or still free
+    pub fn set_transparency(self, transparency: Transparency) -> Self {
+        Event {
+            transparency,
+            ..self
+        }
+    }
+
+    /// returns the priority of the event from 1 (highest) to 9 (lowest),
+    /// None when no priority has been given
+    pub fn priority(&self) -> Option<u8> {
+        self.priority
+    }
+
+    /// Set/Change/Clear the priority of the event, it must be from 1
+    /// (highest) to 9 (lowest). 0 clears it, as it does in RFC 5545
+    pub fn set_priority(self, priority: Option<u8>) -> Result<Self, EventError> {
+        if let Some(priority) = priority {
+            props::check_priority(priority)?;
+        }
+        Ok(Event {
+            priority: priority.filter(|p| *p != 0),
+            ..self
+        })
+    }
+
+    /// returns a link to more about the event
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Set/Change/Clear the link to more about the event, it must be an
+    /// absolute URI such as `https://example.com/standup`
+    pub fn set_url(self, url: Option<String>) -> Result<Self, EventError> {
+        if let Some(url) = &url {
+            props::check_uri("URL", url)?;
+        }
+        Ok(Event { url, ..self })
+    }
+
+    /// returns the calendar address of whoever organizes the event, such as
+    /// `mailto:alice@example.com`
+    pub fn organizer(&self) -> Option<&str> {
+        self.organizer.as_deref()
+    }
+
+    /// Set/Change/Clear who organizes the event. Takes a calendar address
+    /// URI, a bare email address is turned into a `mailto:` one
+    ///
+    /// ```
+    /// use calib::{Event, EventError};
+    /// use chrono::NaiveDate;
+    ///
+    /// let evt = Event::new("Standup".to_string(), &NaiveDate::from_ymd_opt(2023, 1, 2).unwrap())
+    ///     .set_organizer(Some("alice@example.com"))
+    ///     .unwrap();
+    /// assert_eq!(evt.organizer(), Some("mailto:alice@example.com"));
+    ///
+    /// assert!(matches!(evt.set_organizer(Some("alice")), Err(EventError::InvalidProperty(..))));
+    /// ```
+    pub fn set_organizer(self, organizer: Option<&str>) -> Result<Self, EventError> {
+        let organizer = organizer
+            .map(|address| props::cal_address("ORGANIZER", address))
+            .transpose()?;
+        Ok(Event { organizer, ..self })
+    }
+
+    /// returns everyone invited to the event
+    pub fn attendees(&self) -> &[Attendee] {
+        &self.attendees
+    }
+
+    /// returns the attendee with the given calendar address
+    pub fn attendee(&self, address: &str) -> Option<&Attendee> {
+        self.attendees.iter().find(|a| a.is(address))
+    }
+
+    /// returns true if someone with the given address is invited and has
+    /// not declined
+    pub fn is_attending(&self, address: &str) -> bool {
+        self.attendee(address).is_some_and(Attendee::is_attending)
+    }
+
+    /// Add/Change an attendee, replacing any attendee with the same address
+    pub fn add_attendee(mut self, attendee: Attendee) -> Self {
+        match self.attendees.iter_mut().find(|a| a.is(attendee.address())) {
+            Some(existing) => *existing = attendee,
+            None => self.attendees.push(attendee),
+        }
+        self
+    }
+
+    /// Remove the attendee with the given address, if they are invited
+    pub fn remove_attendee(mut self, address: &str) -> Self {
+        self.attendees.retain(|a| !a.is(address));
+        self
+    }
+
+    /// record a reply from the attendee with the given address made at `at`
+    ///
+    /// ```
+    /// use calib::{Attendee, Event, PartStat};
+    /// use chrono::{NaiveDate, TimeZone, Utc};
+    ///
+    /// let evt = Event::new("Standup".to_string(), &NaiveDate::from_ymd_opt(2023, 1, 2).unwrap())
+    ///     .add_attendee(Attendee::new("bob@example.com").unwrap().set_rsvp(true));
+    ///
+    /// let at = Utc.with_ymd_and_hms(2022, 12, 30, 16, 0, 0).unwrap();
+    /// let evt = evt.respond("mailto:bob@example.com", PartStat::Declined, at).unwrap();
+    /// let bob = evt.attendee("bob@example.com").unwrap();
+    /// assert_eq!(bob.status(), PartStat::Declined);
+    /// assert_eq!(bob.replied_at(), Some(at));
+    /// assert!(!evt.is_attending("bob@example.com"));
+    /// ```
+    pub fn respond(
+        mut self,
+        address: &str,
+        status: PartStat,
+        at: DateTime<Utc>,
+    ) -> Result<Self, EventError> {
+        let Some(index) = self.attendees.iter().position(|a| a.is(address)) else {
+            return Err(EventError::NotAnAttendee(self.id, address.to_string()));
+        };
+        let attendee = self.attendees.remove(index);
+        self.attendees.insert(index, attendee.respond(status, at));
+        Ok(self)
+    }
+
+    /// returns the alarms of the event, in the order they were added
+    pub fn alarms(&self) -> &[Alarm] {
+        &self.alarms
+    }
+
+    /// Add an alarm to the event, relative alarms go off for every
+    /// occurrence of a recurring event
+    pub fn add_alarm(mut self, alarm: Alarm) -> Self {
+        self.alarms.push(alarm);
+        self
+    }
+
+    /// Remove every alarm from the event
+    pub fn clear_alarms(mut self) -> Self {
+        self.alarms.clear();
+        self
+    }
+
+    /// split a series at the occurrence starting at `start`, returning the
+    /// series truncated to the occurrences before it, if there are any, and a
+    /// new series continuing from it. The new series has its own id and is
+    /// related to this one unless nothing is left of this series, in which
+    /// case it keeps this series' id. A half left with no occurrences of the
+    /// rule, such as a new series starting at an RDATE after the rule ends,
+    /// has no rule
+    pub(crate) fn split_at(
+        &self,
+        start: NaiveDateTime,
+    ) -> Result<(Option<Event>, Event), EventError> {
+        // DTSTART is always the first occurrence of the rule, so a head has
+        // at least one
+        let before = self.rrule.as_ref().map_or(0, |rule| {
+            rule.occurrences(self.start)
+                .take_while(|s| *s < start)
+                .count() as u32
+        });
+
+        let head = if self.start < start {
+            let rrule = match self.rrule.clone() {
+                Some(rule) if rule.count().is_some() => Some(rule.unbounded().set_count(before)?),
+                Some(rule) => Some(rule.unbounded().set_until(start - Duration::seconds(1))?),
+                None => None,
+            };
+            Some(Event {
+                rrule,
+                rdates: self.rdates.range(..start).copied().collect(),
+                exdates: self.exdates.range(..start).copied().collect(),
+                ..self.clone()
+            })
+        } else {
+            None
+        };
+
+        let rrule = match self.rrule.clone() {
+            Some(rule) => match (rule.count(), rule.until()) {
+                (Some(count), _) if count > before => {
+                    Some(rule.unbounded().set_count(count - before)?)
+                }
+                (Some(_), _) => None,
+                (None, Some(until)) if until < start => None,
+                _ => Some(rule),
+            },
+            None => None,
+        };
+        let (id, related_to) = match head {
+            Some(_) => (Uuid::new_v4(), Some(self.id)),
+            None => (self.id, self.related_to),
+        };
+        let tail = Event {
+            start,
+            end: start + self.duration(),
+            id,
+            related_to,
+            rrule,
+            rdates: self.rdates.range(start..).copied().collect(),
+            exdates: self.exdates.range(start..).copied().collect(),
+            ..self.clone()
+        };
+
+        Ok((head, tail))
+    }
+
+    /// move every RDATE, EXDATE and the UNTIL of the rule by `delta`, keeping
+    /// them in step with a series whose start has moved
+    pub(crate) fn shift_exceptions(self, delta: Duration) -> Self {
+        let rrule = self.rrule.map(|rule| match rule.until() {
+            Some(until) => rule
+                .unbounded()
+                .set_until(until + delta)
+                .expect("an unbounded rule accepts UNTIL"),
+            None => rule,
+        });
+        Event {
+            rrule,
+            rdates: self.rdates.iter().map(|dt| *dt + delta).collect(),
+            exdates: self.exdates.iter().map(|dt| *dt + delta).collect(),
+            ..self
+        }
+    }
+
+    /// the first of all events starting at `start` in the order they are
+    /// kept in, for looking events up by start. It is not a valid event
+    pub(crate) fn probe(start: NaiveDateTime) -> Self {
+        Event {
+            start,
+            end: NaiveDateTime::MIN,
+            name: String::new(),
+            id: Uuid::nil(),
+            ..Event::new(String::new(), &start.date())
+        }
+    }
+
+    /// give the event a specific id, for events read back from storage
+    pub(crate) fn with_id(self, id: Uuid) -> Self {
+        Event { id, ..self }
+    }
+
+    /// return a standalone copy of the occurrence starting at `start`, as
+    /// an override of that occurrence
+    pub(crate) fn occurrence_at(&self, start: NaiveDateTime) -> Self {
+        Event {
+            start,
+            end: start + self.duration(),
+            ..self.clone()
+        }
+        .into_override(self.id, start)
+    }
+
+    /// turn an event into an override of the occurrence of `series` starting
+    /// at `recurrence_id`, an override shares the id of its series
+    pub(crate) fn into_override(self, series: Uuid, recurrence_id: NaiveDateTime) -> Self {
+        Event {
+            id: series,
+            rrule: None,
+            rdates: BTreeSet::new(),
+            exdates: BTreeSet::new(),
+            recurrence_id: Some(recurrence_id),
+            ..self
+        }
+    }
+
+    /// return an iterator over the start of each occurrence of the event in
+    /// chronological order, a non-recurring event has exactly one occurrence
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Event, Frequency, RRule};
+    /// use chrono::NaiveDate;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let rule = RRule::new(Frequency::Weekly).set_count(3).unwrap();
+    /// let standup = Event::new("Standup".into(), &date).set_recurrence(Some(rule));
+    ///
+    /// let days: Vec<_> = standup.occurrences().map(|dt| dt.date()).collect();
+    /// assert_eq!(days, vec![date, date + chrono::Duration::weeks(1), date + chrono::Duration::weeks(2)]);
+    /// ```
+    pub fn occurrences(&self) -> OccurrenceStarts<'_> {
+        self.occurrences_from(NaiveDateTime::MIN)
+    }
+
+    /// returns the starts of the occurrences from around `from` on, without
+    /// expanding the series from its start where the rule allows it. Some
+    /// occurrences starting before `from` may still be included
+    pub(crate) fn occurrences_from(&self, from: NaiveDateTime) -> OccurrenceStarts<'_> {
+        let base: Box<dyn Iterator<Item = NaiveDateTime>> = match &self.rrule {
+            Some(rule) => Box::new(rule.occurrences(self.start).seek(from)),
+            None => Box::new(std::iter::once(self.start)),
+        };
+
+        OccurrenceStarts {
+            base: base.peekable(),
+            rdates: self.rdates.range(from..).copied().peekable(),
+            exdates: &self.exdates,
+            last: None,
+        }
+    }
+
+    /// returns the start of the first occurrence, which is before the start
+    /// of the event when it has an earlier extra occurrence
+    pub(crate) fn span_start(&self) -> NaiveDateTime {
+        self.rdates
+            .first()
+            .map_or(self.start, |rdate| self.start.min(*rdate))
+    }
+
+    /// returns the end of the last occurrence, NaiveDateTime::MAX for
+    /// series that repeat forever
+    pub(crate) fn span_end(&self) -> NaiveDateTime {
+        let rule_end = match &self.rrule {
+            None => self.end,
+            Some(rule) if rule.until().is_none() && rule.count().is_none() => NaiveDateTime::MAX,
+            Some(rule) => rule
+                .occurrences(self.start)
+                .last()
+                .and_then(|start| start.checked_add_signed(self.duration()))
+                .unwrap_or(self.end),
+        };
+        let rdate_end = self
+            .rdates
+            .last()
+            .and_then(|start| start.checked_add_signed(self.duration()));
+
+        rdate_end.map_or(rule_end, |end| end.max(rule_end))
+    }
+
+    /// Create an Event with a name and date, starting at 00:00:00 and ending
+    /// at 23:59:59 ready for its times to be set. Use [`Event::all_day`] for
+    /// an event lasting the whole day
     pub fn new(name: String, date: &NaiveDate) -> Self {
         Self {
             name,
             start: NaiveDateTime::new(*date, day_start()),
             end: NaiveDateTime::new(*date, day_end()),
             id: Uuid::new_v4(),
+            rrule: None,
+            rdates: BTreeSet::new(),
+            exdates: BTreeSet::new(),
+            recurrence_id: None,
+            related_to: None,
+            tz: EventTz::Floating,
+            description: None,
+            location: None,
+            status: None,
+            transparency: Transparency::Opaque,
+            priority: None,
+            url: None,
+            organizer: None,
+            attendees: Vec::new(),
+            alarms: Vec::new(),
+            all_day: false,
         }
     }
 
-    /// Set/Change the date and time of the start field
+    /// Create an all day event lasting from the `start` date up to but not
+    /// including the `end` date, so a single day ends on the next one. Its
+    /// start and end are the midnights the days begin at
+    ///
+    /// ```
+    /// use calib::{Event, EventError};
+    /// use chrono::{Duration, NaiveDate};
+    ///
+    /// let date = |d| NaiveDate::from_ymd_opt(2023, 8, d).unwrap();
+    /// let trip = Event::all_day("Holiday".into(), date(7), date(12)).unwrap();
+    /// assert!(trip.is_all_day());
+    /// assert_eq!(trip.dates(), Some((date(7), date(12))));
+    /// assert_eq!(trip.duration(), Duration::days(5));
+    ///
+    /// assert!(matches!(
+    ///     Event::all_day("Nothing".into(), date(7), date(7)),
+    ///     Err(EventError::InvalidEndTime)
+    /// ));
+    /// ```
+    pub fn all_day(name: String, start: NaiveDate, end: NaiveDate) -> Result<Self, EventError> {
+        Event::new(name, &start).set_dates(start, end)
+    }
+
+    /// returns true if the event lasts whole days rather than running
+    /// between two times
+    pub fn is_all_day(&self) -> bool {
+        self.all_day
+    }
+
+    /// returns the first day of an all day event and the day after its
+    /// last, None for events with times
+    pub fn dates(&self) -> Option<(NaiveDate, NaiveDate)> {
+        self.all_day.then(|| (self.start.date(), self.end.date()))
+    }
+
+    /// Set/Change the days the event lasts, from `start` up to but not
+    /// including `end`, making it an all day event
+    pub fn set_dates(self, start: NaiveDate, end: NaiveDate) -> Result<Self, EventError> {
+        let (start, end) = (
+            NaiveDateTime::new(start, NaiveTime::MIN),
+            NaiveDateTime::new(end, NaiveTime::MIN),
+        );
+        if !Event::start_end_times_valid(&start, &end) {
+            return Err(EventError::InvalidEndTime);
+        }
+        Ok(Event {
+            start,
+            end,
+            all_day: true,
+            ..self
+        })
+    }
+
+    /// Set/Change the date and time of the start field, an all day event
+    /// becomes one with times
     pub fn set_start(self, start: NaiveDateTime) -> Result<Self, EventError> {
         // check how many seconds from the start time the end time is, if the value
         // is negative that means the start time is AFTER the end time which
         // results in an InvalidStartTime error, on success returns the new start time
         if Event::start_end_times_valid(&start, &self.end) {
             // lol literally the first time ive used this syntax
-            Ok(Event { start, ..self })
+            Ok(Event {
+                start,
+                all_day: false,
+                ..self
+            })
         } else {
             // if the new start time is invalid then return an error
             Err(EventError::InvalidStartTime)
         }
     }
 
-    /// Set/Change an event's start time
+    /// Set/Change an event's start time, an all day event becomes one with
+    /// times
     pub fn set_start_time(self, start: NaiveTime) -> Result<Self, EventError> {
         // check how many seconds from the start time the end time is, if the value
         // is negative that means the start time is AFTER the end time which
@@ -75,6 +909,7 @@ impl Event {
             // lol literally the first time ive used this syntax
             Ok(Event {
                 start: new_start,
+                all_day: false,
                 ..self
             })
         } else {
@@ -101,20 +936,26 @@ impl Event {
         }
     }
 
-    /// Set/Change the date and time of the end field
+    /// Set/Change the date and time of the end field, an all day event
+    /// becomes one with times
     pub fn set_end(self, end: NaiveDateTime) -> Result<Self, EventError> {
         // check how many seconds from the end time the start time is, if the value
         // is negative that means the start time is AFTER the end time which
         // results in an InvalidEndTime error, on success returns new end time
         if Event::start_end_times_valid(&self.start, &end) {
             // previous end time is overwritten
-            Ok(Event { end, ..self })
+            Ok(Event {
+                end,
+                all_day: false,
+                ..self
+            })
         } else {
             Err(EventError::InvalidEndTime)
         }
     }
 
-    /// Set/Change the time of the end field
+    /// Set/Change the time of the end field, an all day event becomes one
+    /// with times
     pub fn set_end_time(self, end: NaiveTime) -> Result<Self, EventError> {
         // check how many seconds from the end time the start time is, if the value
         // is negative that means the start time is AFTER the end time which
@@ -124,6 +965,7 @@ impl Event {
             // previous end time is overwritten
             Ok(Event {
                 end: new_end,
+                all_day: false,
                 ..self
             })
         } else {
@@ -156,4 +998,55 @@ impl Event {
     pub fn serialize(&self) -> String {
         serde_json::to_string(&self).unwrap()
     }
+
+    /// read an event written by [`Event::serialize`]. An end that is not
+    /// after the start is an [`EventError::InvalidEndTime`] just as it is
+    /// when building an event
+    ///
+    /// ```
+    /// use calib::{Event, EventError};
+    /// use chrono::NaiveDate;
+    ///
+    /// let evt = Event::new("Dentist".to_string(), &NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+    /// assert_eq!(Event::deserialize(&evt.serialize()).unwrap(), evt);
+    ///
+    /// let backwards = evt.serialize().replace("2023-01-02T23:59:59", "2023-01-01T00:00:00");
+    /// assert!(matches!(Event::deserialize(&backwards), Err(EventError::InvalidEndTime)));
+    /// ```
+    pub fn deserialize(input: &str) -> Result<Event, EventError> {
+        serde_json::from_str::<EventFields>(input)
+            .map_err(|e| EventError::Json(e.to_string()))?
+            .try_into()
+    }
+}
+
+/// Iterator over the occurrence starts of an event, returned by
+/// [`Event::occurrences`]. Merges the recurrence rule with RDATEs and
+/// leaves out EXDATEs
+pub struct OccurrenceStarts<'a> {
+    base: Peekable<Box<dyn Iterator<Item = NaiveDateTime> + 'a>>,
+    rdates: Peekable<std::iter::Copied<std::collections::btree_set::Range<'a, NaiveDateTime>>>,
+    exdates: &'a BTreeSet<NaiveDateTime>,
+    last: Option<NaiveDateTime>,
+}
+
+impl Iterator for OccurrenceStarts<'_> {
+    type Item = NaiveDateTime;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let next = match (self.base.peek(), self.rdates.peek()) {
+                (Some(a), Some(b)) if b < a => self.rdates.next(),
+                (Some(_), _) => self.base.next(),
+                (None, _) => self.rdates.next(),
+            }?;
+
+            // an RDATE may duplicate an occurrence of the rule
+            if self.last == Some(next) || self.exdates.contains(&next) {
+                continue;
+            }
+            self.last = Some(next);
+            return Some(next);
+        }
+    }
 }
@@ -0,0 +1,136 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{ical, EventError, EventTz};
+
+/// A half-open span of time [start, end)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Period {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+}
+
+impl Period {
+    /// create a period, returns None unless start is before end
+    pub fn new(start: NaiveDateTime, end: NaiveDateTime) -> Option<Self> {
+        (start < end).then_some(Period { start, end })
+    }
+
+    /// returns when the period starts
+    pub fn start(&self) -> NaiveDateTime {
+        self.start
+    }
+
+    /// returns when the period ends, this instant is not part of it
+    pub fn end(&self) -> NaiveDateTime {
+        self.end
+    }
+
+    /// returns how long the period lasts
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// When a calendar is busy and free over a window of time, returned by
+/// [`EventCalendar::free_busy`](crate::EventCalendar::free_busy)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FreeBusy {
+    window: Period,
+    busy: Vec<Period>,
+    free: Vec<Period>,
+    tz: EventTz,
+}
+
+impl FreeBusy {
+    /// merge the busy times into sorted, non-overlapping periods within the
+    /// window [start, end), the rest of the window is free. Periods that
+    /// touch are merged too
+    pub(crate) fn new(
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+        busy: impl IntoIterator<Item = (NaiveDateTime, NaiveDateTime)>,
+        tz: EventTz,
+    ) -> Self {
+        let window = Period {
+            start,
+            end: end.max(start),
+        };
+        let mut times: Vec<_> = busy
+            .into_iter()
+            .filter_map(|(start, end)| Period::new(start.max(window.start), end.min(window.end)))
+            .collect();
+        times.sort();
+
+        let mut merged: Vec<Period> = Vec::with_capacity(times.len());
+        for period in times {
+            match merged.last_mut() {
+                Some(last) if period.start <= last.end => last.end = last.end.max(period.end),
+                _ => merged.push(period),
+            }
+        }
+
+        let mut free = Vec::with_capacity(merged.len() + 1);
+        let mut from = window.start;
+        for period in &merged {
+            free.extend(Period::new(from, period.start));
+            from = period.end;
+        }
+        free.extend(Period::new(from, window.end));
+
+        FreeBusy {
+            window,
+            busy: merged,
+            free,
+            tz,
+        }
+    }
+
+    /// returns the window of time this covers
+    pub fn window(&self) -> Period {
+        self.window
+    }
+
+    /// returns the busy periods in order
+    pub fn busy(&self) -> &[Period] {
+        &self.busy
+    }
+
+    /// returns the free periods in order, the gaps between busy ones
+    pub fn free(&self) -> &[Period] {
+        &self.free
+    }
+
+    /// returns the zone the periods are written in, UTC or floating wall
+    /// clock time
+    pub fn tz(&self) -> EventTz {
+        self.tz
+    }
+
+    /// returns true if no busy period overlaps [start, end)
+    pub fn is_free(&self, start: NaiveDateTime, end: NaiveDateTime) -> bool {
+        !self.busy.iter().any(|p| p.start < end && p.end > start)
+    }
+
+    /// write the busy periods as a VFREEBUSY in iCalendar (.ics) text,
+    /// stamped with the current time. RFC 5545 wants the periods in UTC, so
+    /// only free/busy time from
+    /// [`EventCalendar::free_busy_tz`](crate::EventCalendar::free_busy_tz)
+    /// can be written, floating time fails with [`EventError::InvalidProperty`]
+    pub fn to_ics(&self) -> Result<String, EventError> {
+        self.to_ics_with_stamp(Utc::now())
+    }
+
+    /// write the busy periods as a VFREEBUSY in iCalendar (.ics) text with
+    /// DTSTAMP set to `dtstamp`, so the output is the same each time. See
+    /// [`FreeBusy::to_ics`]
+    pub fn to_ics_with_stamp(&self, dtstamp: DateTime<Utc>) -> Result<String, EventError> {
+        match self.tz {
+            EventTz::Utc => Ok(ical::write_free_busy(self, dtstamp)),
+            tz => Err(EventError::InvalidProperty(
+                "free/busy time zone",
+                tz.name().to_string(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::VecDeque;
+
+use super::Change;
+
+/// how many commands a calendar can undo, by default
+const HISTORY_LIMIT: usize = 100;
+
+/// A change to a calendar that can be undone, made up of the changes to
+/// each item it touched and the changes that put them back
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Command {
+    pub(crate) changes: Vec<Change>,
+    pub(crate) undo: Vec<Change>,
+}
+
+/// The commands made to a calendar that can be undone and those undone that
+/// can be made again. Only the latest commands are kept
+#[derive(Debug, Clone)]
+pub(crate) struct History {
+    done: VecDeque<Command>,
+    undone: Vec<Command>,
+    limit: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            done: VecDeque::new(),
+            undone: Vec::new(),
+            limit: HISTORY_LIMIT,
+        }
+    }
+}
+
+impl History {
+    /// record a new command, which can no longer be followed by the ones
+    /// that were undone
+    pub(crate) fn push(&mut self, command: Command) {
+        if command.changes.is_empty() {
+            return;
+        }
+        self.undone.clear();
+        self.done.push_back(command);
+        self.trim();
+    }
+
+    /// take the latest command to undo it
+    pub(crate) fn undo(&mut self) -> Option<&Command> {
+        let command = self.done.pop_back()?;
+        self.undone.push(command);
+        self.undone.last()
+    }
+
+    /// take the latest undone command to make it again
+    pub(crate) fn redo(&mut self) -> Option<&Command> {
+        let command = self.undone.pop()?;
+        self.done.push_back(command);
+        self.done.back()
+    }
+
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// keep at most `limit` commands, dropping the oldest
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+        // the commands undone first are the furthest from being made again
+        let extra = self.undone.len().saturating_sub(limit);
+        self.undone.drain(..extra);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+
+    fn trim(&mut self) {
+        while self.done.len() > self.limit {
+            self.done.pop_front();
+        }
+    }
+}
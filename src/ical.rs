@@ -0,0 +1,1249 @@
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Months, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Utc,
+};
+use chrono_tz::{OffsetComponents, OffsetName, Tz, TzOffset};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+use super::{
+    Alarm, AlarmAction, Attendee, Disambiguation, Event, EventCalendar, EventError, EventTz,
+    FreeBusy, Journal, RRule, Task, Trigger, WeekdayNum,
+};
+
+/// format of DATE-TIME values
+const DATE_TIME: &str = "%Y%m%dT%H%M%S";
+
+// Reading of RFC 5545 iCalendar text. Content lines are unfolded, split into
+// name, parameters and value, then grouped into components. VEVENT with its
+// VALARMs, VTODO, VJOURNAL and VTIMEZONE are understood, every other
+// component is skipped
+
+/// A single unfolded content line
+#[derive(Debug)]
+struct Property {
+    line: usize,
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn error(&self, reason: impl Into<String>) -> EventError {
+        EventError::Ics {
+            line: self.line,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// A BEGIN/END block with its properties and nested components
+#[derive(Debug)]
+struct Component {
+    line: usize,
+    name: String,
+    props: Vec<Property>,
+    children: Vec<Component>,
+}
+
+impl Component {
+    fn prop(&self, name: &str) -> Option<&Property> {
+        self.props.iter().find(|p| p.name == name)
+    }
+
+    fn props<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> {
+        self.props.iter().filter(move |p| p.name == name)
+    }
+}
+
+/// A DATE or DATE-TIME value together with the zone it was written in
+#[derive(Debug, Clone, Copy)]
+struct Value {
+    dt: NaiveDateTime,
+    tz: EventTz,
+    date_only: bool,
+}
+
+/// A RECURRENCE-ID value and the property it was read from
+type RecurrenceId<'a> = (Value, &'a Property);
+
+/// The events, tasks and journal entries read from iCalendar text
+type Items = (Vec<Event>, Vec<Task>, Vec<Journal>);
+
+/// read every VEVENT, VTODO and VJOURNAL in iCalendar text, overrides of
+/// recurring events are returned as events with a recurrence id
+pub(crate) fn read_items(input: &str) -> Result<Items, EventError> {
+    let root = parse_components(input)?;
+    let calendars: Vec<_> = root.iter().filter(|c| c.name == "VCALENDAR").collect();
+    if calendars.is_empty() {
+        return Err(EventError::Ics {
+            line: root.first().map_or(1, |c| c.line),
+            reason: "no VCALENDAR found".into(),
+        });
+    }
+
+    let mut events = Vec::new();
+    let mut overrides = Vec::new();
+    let mut tasks = Vec::new();
+    let mut journals = Vec::new();
+    for cal in calendars {
+        let zones = read_timezones(cal)?;
+        for comp in cal.children.iter().filter(|c| c.name == "VEVENT") {
+            match read_event(comp, &zones)? {
+                Some((event, None)) => events.push(event),
+                Some((event, Some(recurrence_id))) => overrides.push((event, recurrence_id)),
+                None => {}
+            }
+        }
+        for comp in cal.children.iter().filter(|c| c.name == "VTODO") {
+            tasks.push(read_task(comp, &zones)?);
+        }
+        for comp in cal.children.iter().filter(|c| c.name == "VJOURNAL") {
+            journals.extend(read_journal(comp, &zones)?);
+        }
+    }
+
+    let series_tz: HashMap<_, _> = events.iter().map(|evt| (*evt.id(), evt.tz())).collect();
+    for (event, (recurrence_id, prop)) in overrides {
+        let tz = series_tz.get(event.id()).copied().unwrap_or(event.tz());
+        let recurrence_id = in_zone(recurrence_id, tz, prop)?;
+        let id = *event.id();
+        events.push(event.into_override(id, recurrence_id));
+    }
+    Ok((events, tasks, journals))
+}
+
+/// split text into its components, unfolding lines as we go
+fn parse_components(input: &str) -> Result<Vec<Component>, EventError> {
+    let mut stack: Vec<Component> = Vec::new();
+    let mut done = Vec::new();
+
+    for prop in unfold(input) {
+        let prop = parse_line(prop.0, &prop.1)?;
+        match prop.name.as_str() {
+            "BEGIN" => stack.push(Component {
+                line: prop.line,
+                name: prop.value.to_ascii_uppercase(),
+                props: Vec::new(),
+                children: Vec::new(),
+            }),
+            "END" => {
+                let comp = stack
+                    .pop()
+                    .ok_or_else(|| prop.error(format!("END:{} without BEGIN", prop.value)))?;
+                if !comp.name.eq_ignore_ascii_case(&prop.value) {
+                    return Err(prop.error(format!(
+                        "END:{} does not match BEGIN:{} on line {}",
+                        prop.value, comp.name, comp.line
+                    )));
+                }
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(comp),
+                    None => done.push(comp),
+                }
+            }
+            _ => match stack.last_mut() {
+                Some(comp) => comp.props.push(prop),
+                None => return Err(prop.error(format!("{} outside of a component", prop.name))),
+            },
+        }
+    }
+
+    match stack.pop() {
+        Some(comp) => Err(EventError::Ics {
+            line: comp.line,
+            reason: format!("BEGIN:{} is never closed", comp.name),
+        }),
+        None => Ok(done),
+    }
+}
+
+/// join folded lines, a line starting with a space or tab continues the
+/// previous one. Returns each logical line with the number it starts on
+fn unfold(input: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (i, raw) in input.lines().enumerate() {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        match (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some((_, last))) => last.push_str(rest),
+            _ if raw.is_empty() => {}
+            _ => lines.push((i + 1, raw.to_string())),
+        }
+    }
+    lines
+}
+
+/// split a content line into name, parameters and value
+fn parse_line(line: usize, text: &str) -> Result<Property, EventError> {
+    let error = |reason: &str| EventError::Ics {
+        line,
+        reason: reason.into(),
+    };
+
+    let name_end = text
+        .find([';', ':'])
+        .ok_or_else(|| error("expected NAME:VALUE"))?;
+    let name = text[..name_end].to_ascii_uppercase();
+    if name.is_empty() {
+        return Err(error("property name is empty"));
+    }
+
+    let mut params = Vec::new();
+    let mut rest = &text[name_end..];
+    while let Some(param) = rest.strip_prefix(';') {
+        let eq = param
+            .find('=')
+            .ok_or_else(|| error("expected PARAM=VALUE"))?;
+        let key = param[..eq].to_ascii_uppercase();
+        let mut value = String::new();
+        let mut in_quotes = false;
+        let mut end = param.len() - eq - 1;
+        for (i, c) in param[eq + 1..].char_indices() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                ';' | ':' if !in_quotes => {
+                    end = i;
+                    break;
+                }
+                c => value.push(c),
+            }
+        }
+        if in_quotes {
+            return Err(error("unterminated quoted parameter value"));
+        }
+        params.push((key, value));
+        rest = &param[eq + 1 + end..];
+    }
+
+    let value = rest
+        .strip_prefix(':')
+        .ok_or_else(|| error("expected ':' before the value"))?;
+    Ok(Property {
+        line,
+        name,
+        params,
+        value: value.to_string(),
+    })
+}
+
+/// undo TEXT escaping
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// map each VTIMEZONE's TZID to a zone in the time zone database
+fn read_timezones(cal: &Component) -> Result<HashMap<String, EventTz>, EventError> {
+    let mut zones = HashMap::new();
+    for comp in cal.children.iter().filter(|c| c.name == "VTIMEZONE") {
+        let tzid = comp.prop("TZID").ok_or(EventError::IcsMissingProperty {
+            line: comp.line,
+            property: "TZID",
+        })?;
+        // custom TZIDs like "Eastern Standard Time" often name the real zone
+        // in X-LIC-LOCATION
+        let tz = std::iter::once(tzid)
+            .chain(comp.prop("X-LIC-LOCATION"))
+            .find_map(|p| p.value.parse().ok())
+            .ok_or_else(|| tzid.error(format!("unknown time zone {}", tzid.value)))?;
+        zones.insert(tzid.value.clone(), tz);
+    }
+    Ok(zones)
+}
+
+/// read a VEVENT, along with its RECURRENCE-ID if it overrides an occurrence
+/// of a series. The RECURRENCE-ID is in the zone of the series, which may
+/// not have been read yet. Events that take no time at all are allowed by
+/// RFC 5545 but an [`Event`] cannot, so they are skipped with None
+fn read_event<'a>(
+    comp: &'a Component,
+    zones: &HashMap<String, EventTz>,
+) -> Result<Option<(Event, Option<RecurrenceId<'a>>)>, EventError> {
+    let dtstart = comp.prop("DTSTART").ok_or(EventError::IcsMissingProperty {
+        line: comp.line,
+        property: "DTSTART",
+    })?;
+    let start = read_value(dtstart, zones)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| dtstart.error("DTSTART is empty"))?;
+
+    let end = match (comp.prop("DTEND"), comp.prop("DURATION")) {
+        (Some(dtend), _) => {
+            let end = read_value(dtend, zones)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| dtend.error("DTEND is empty"))?;
+            in_zone(end, start.tz, dtend)?
+        }
+        (None, Some(duration)) => start
+            .dt
+            .checked_add_signed(read_duration(duration)?)
+            .ok_or_else(|| duration.error(format!("duration {} is too long", duration.value)))?,
+        // a date on its own lasts the whole day
+        (None, None) if start.date_only => start
+            .dt
+            .checked_add_signed(Duration::days(1))
+            .ok_or_else(|| dtstart.error("DTSTART is too late"))?,
+        (None, None) => {
+            return Err(dtstart.error("an event with a DTSTART time needs a DTEND or DURATION"))
+        }
+    };
+    if end == start.dt {
+        return Ok(None);
+    }
+
+    let name = comp
+        .prop("SUMMARY")
+        .map(|p| unescape(&p.value))
+        .unwrap_or_default();
+    let id = comp
+        .prop("UID")
+        .map_or_else(Uuid::new_v4, |uid| uid_to_uuid(&uid.value));
+
+    let mut event = Event::new(name, &start.dt.date())
+        .set_end(end)
+        .and_then(|evt| evt.set_start(start.dt))
+        .map_err(|e| dtstart.error(e.to_string()))?
+        .with_id(id)
+        .set_tz(start.tz);
+    // events given by dates last whole days
+    if start.date_only && end.time() == NaiveTime::MIN {
+        event = event
+            .set_dates(start.dt.date(), end.date())
+            .map_err(|e| dtstart.error(e.to_string()))?;
+    }
+
+    if let Some(rrule) = comp.prop("RRULE") {
+        let rule = rrule
+            .value
+            .parse::<RRule>()
+            .map_err(|e| rrule.error(e.to_string()))?;
+        // an UNTIL in UTC is moved into the zone of DTSTART
+        let utc_until = rrule.value.split(';').any(|part| {
+            part.strip_prefix("UNTIL=")
+                .is_some_and(|until| until.ends_with('Z'))
+        });
+        let rule = match rule.until() {
+            Some(until) if utc_until => {
+                let value = Value {
+                    dt: until,
+                    tz: EventTz::Utc,
+                    date_only: false,
+                };
+                let until = in_zone(value, start.tz, rrule)?;
+                rule.unbounded()
+                    .set_until(until)
+                    .map_err(|e| rrule.error(e.to_string()))?
+            }
+            _ => rule,
+        };
+        event = event.set_recurrence(Some(rule));
+    }
+    for prop in comp.props("RDATE") {
+        for value in read_value(prop, zones)? {
+            event = event.add_rdate(in_zone(value, start.tz, prop)?);
+        }
+    }
+    for prop in comp.props("EXDATE") {
+        for value in read_value(prop, zones)? {
+            event = event.add_exdate(in_zone(value, start.tz, prop)?);
+        }
+    }
+    if let Some(related) = comp.prop("RELATED-TO") {
+        event = event.set_related_to(Some(uid_to_uuid(&related.value)));
+    }
+    event = event
+        .set_description(comp.prop("DESCRIPTION").map(|p| unescape(&p.value)))
+        .set_location(comp.prop("LOCATION").map(|p| unescape(&p.value)));
+    if let Some(prop) = comp.prop("STATUS") {
+        let status = prop
+            .value
+            .parse()
+            .map_err(|e: EventError| prop.error(e.to_string()))?;
+        event = event.set_status(Some(status));
+    }
+    if let Some(prop) = comp.prop("TRANSP") {
+        let transp = prop
+            .value
+            .parse()
+            .map_err(|e: EventError| prop.error(e.to_string()))?;
+        event = event.set_transparency(transp);
+    }
+    if let Some(prop) = comp.prop("PRIORITY") {
+        let priority = prop
+            .value
+            .parse()
+            .map_err(|_| prop.error(format!("invalid priority {}", prop.value)))?;
+        event = event
+            .set_priority(Some(priority))
+            .map_err(|e| prop.error(e.to_string()))?;
+    }
+    if let Some(prop) = comp.prop("URL") {
+        event = event
+            .set_url(Some(prop.value.clone()))
+            .map_err(|e| prop.error(e.to_string()))?;
+    }
+    if let Some(prop) = comp.prop("ORGANIZER") {
+        event = event
+            .set_organizer(Some(&prop.value))
+            .map_err(|e| prop.error(e.to_string()))?;
+    }
+    for prop in comp.props("ATTENDEE") {
+        event = event.add_attendee(read_attendee(prop)?);
+    }
+    for alarm in comp.children.iter().filter(|c| c.name == "VALARM") {
+        if let Some(alarm) = read_alarm(alarm)? {
+            event = event.add_alarm(alarm);
+        }
+    }
+    let recurrence_id = match comp.prop("RECURRENCE-ID") {
+        Some(prop) => {
+            let value = read_value(prop, zones)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| prop.error("RECURRENCE-ID is empty"))?;
+            Some((value, prop))
+        }
+        None => None,
+    };
+
+    Ok(Some((event, recurrence_id)))
+}
+
+/// read a VTODO, its DTSTART is moved into the zone of its DUE. A task
+/// given by dates alone keeps them as dates
+fn read_task(comp: &Component, zones: &HashMap<String, EventTz>) -> Result<Task, EventError> {
+    let first_value = |prop: &Property| {
+        read_value(prop, zones)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| prop.error(format!("{} is empty", prop.name)))
+    };
+    let due = comp
+        .prop("DUE")
+        .map(|p| Ok((first_value(p)?, p)))
+        .transpose()?;
+    let start = comp
+        .prop("DTSTART")
+        .map(|p| Ok((first_value(p)?, p)))
+        .transpose()?;
+    let tz = due
+        .or(start)
+        .map_or(EventTz::Floating, |(value, _)| value.tz);
+
+    let name = comp
+        .prop("SUMMARY")
+        .map(|p| unescape(&p.value))
+        .unwrap_or_default();
+    let mut task = Task::new(name).set_tz(tz);
+    if let Some(uid) = comp.prop("UID") {
+        task = task.with_id(uid_to_uuid(&uid.value));
+    }
+    if let Some((value, prop)) = due {
+        task = task
+            .set_due(Some(value.dt))
+            .map_err(|e| prop.error(e.to_string()))?;
+    }
+    if let Some((value, prop)) = start {
+        task = task
+            .set_start(Some(in_zone(value, tz, prop)?))
+            .map_err(|e| prop.error(e.to_string()))?;
+    }
+    if let Some((_, prop)) = due.or(start) {
+        if [due, start]
+            .iter()
+            .flatten()
+            .all(|(value, _)| value.date_only)
+        {
+            let date = |when: Option<(Value, &Property)>| when.map(|(value, _)| value.dt.date());
+            task = task
+                .set_dates(date(start), date(due))
+                .map_err(|e| prop.error(e.to_string()))?;
+        }
+    }
+    if let Some(prop) = comp.prop("PERCENT-COMPLETE") {
+        let percent = prop
+            .value
+            .parse()
+            .map_err(|_| prop.error(format!("invalid percent {}", prop.value)))?;
+        task = task
+            .set_percent_complete(percent)
+            .map_err(|e| prop.error(e.to_string()))?;
+    }
+    if let Some(prop) = comp.prop("PRIORITY") {
+        let priority = prop
+            .value
+            .parse()
+            .map_err(|_| prop.error(format!("invalid priority {}", prop.value)))?;
+        task = task
+            .set_priority(Some(priority))
+            .map_err(|e| prop.error(e.to_string()))?;
+    }
+    if let Some(prop) = comp.prop("STATUS") {
+        let status = prop
+            .value
+            .parse()
+            .map_err(|e: EventError| prop.error(e.to_string()))?;
+        task = task.set_status(status);
+    }
+    if let Some(prop) = comp.prop("COMPLETED") {
+        let value = first_value(prop)?;
+        if value.tz != EventTz::Utc {
+            return Err(prop.error("COMPLETED must be in UTC"));
+        }
+        task = task.with_completed(Some(value.dt.and_utc()));
+    }
+    Ok(task.set_description(comp.prop("DESCRIPTION").map(|p| unescape(&p.value))))
+}
+
+/// read a VJOURNAL, a DTSTART with a time is taken for its date. An entry
+/// may have several DESCRIPTIONs, which become paragraphs of its body
+fn read_journal(
+    comp: &Component,
+    zones: &HashMap<String, EventTz>,
+) -> Result<Option<Journal>, EventError> {
+    // DTSTART is optional, an entry without one is for the day it was
+    // written. One without either can't be placed on any day and is skipped
+    let Some(dtstart) = comp.prop("DTSTART").or_else(|| comp.prop("DTSTAMP")) else {
+        return Ok(None);
+    };
+    let date = read_value(dtstart, zones)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| dtstart.error(format!("{} is empty", dtstart.name)))?
+        .dt
+        .date();
+
+    let name = comp
+        .prop("SUMMARY")
+        .map(|p| unescape(&p.value))
+        .unwrap_or_default();
+    let body: Vec<_> = comp
+        .props("DESCRIPTION")
+        .map(|p| unescape(&p.value))
+        .collect();
+    let mut journal = Journal::new(date, name)
+        .set_body(body.join("\n\n"))
+        .set_related_to(comp.prop("RELATED-TO").map(|p| uid_to_uuid(&p.value)));
+    if let Some(uid) = comp.prop("UID") {
+        journal = journal.with_id(uid_to_uuid(&uid.value));
+    }
+    Ok(Some(journal))
+}
+
+/// read a DATE or DATE-TIME property, which may hold a list of values
+fn read_value(prop: &Property, zones: &HashMap<String, EventTz>) -> Result<Vec<Value>, EventError> {
+    let zone = match prop.param("TZID") {
+        Some(tzid) => Some(
+            zones
+                .get(tzid)
+                .copied()
+                .or_else(|| tzid.trim_start_matches('/').parse().ok())
+                .ok_or_else(|| prop.error(format!("unknown time zone {tzid}")))?,
+        ),
+        None => None,
+    };
+
+    prop.value
+        .split(',')
+        .filter(|v| !v.is_empty())
+        .map(|text| {
+            if prop.param("VALUE") == Some("DATE") || text.len() == 8 {
+                let date = NaiveDate::parse_from_str(text, "%Y%m%d")
+                    .map_err(|_| prop.error(format!("invalid date {text}")))?;
+                return Ok(Value {
+                    dt: NaiveDateTime::new(date, NaiveTime::MIN),
+                    tz: EventTz::Floating,
+                    date_only: true,
+                });
+            }
+
+            let (text, utc) = match text.strip_suffix('Z') {
+                Some(text) => (text, true),
+                None => (text, false),
+            };
+            let dt = NaiveDateTime::parse_from_str(text, DATE_TIME)
+                .map_err(|_| prop.error(format!("invalid date-time {text}")))?;
+            let tz = match (utc, zone) {
+                (true, _) => EventTz::Utc,
+                (false, Some(tz)) => tz,
+                (false, None) => EventTz::Floating,
+            };
+            Ok(Value {
+                dt,
+                tz,
+                date_only: false,
+            })
+        })
+        .collect()
+}
+
+/// express a value read in one zone as wall clock time in `tz`
+fn in_zone(value: Value, tz: EventTz, prop: &Property) -> Result<NaiveDateTime, EventError> {
+    if value.tz == tz || value.tz.is_floating() || tz.is_floating() {
+        return Ok(value.dt);
+    }
+    let instant = value
+        .tz
+        .to_utc(value.dt, &Utc, Disambiguation::Compatible)
+        .map_err(|e| prop.error(e.to_string()))?;
+    Ok(tz.from_utc(&instant, &Utc))
+}
+
+/// read an ATTENDEE and its CN, ROLE, PARTSTAT and RSVP parameters
+fn read_attendee(prop: &Property) -> Result<Attendee, EventError> {
+    let error = |e: EventError| prop.error(e.to_string());
+    let mut attendee = Attendee::new(&prop.value)
+        .map_err(error)?
+        .set_name(prop.param("CN").map(str::to_string))
+        .set_rsvp(
+            prop.param("RSVP")
+                .is_some_and(|rsvp| rsvp.eq_ignore_ascii_case("TRUE")),
+        );
+    if let Some(role) = prop.param("ROLE") {
+        attendee = attendee.set_role(role.parse().map_err(error)?);
+    }
+    if let Some(status) = prop.param("PARTSTAT") {
+        attendee = attendee.with_status(status.parse().map_err(error)?);
+    }
+    Ok(attendee)
+}
+
+/// read a VALARM, alarms with an action other than DISPLAY, EMAIL or AUDIO
+/// are skipped
+fn read_alarm(comp: &Component) -> Result<Option<Alarm>, EventError> {
+    let missing = |property| EventError::IcsMissingProperty {
+        line: comp.line,
+        property,
+    };
+    let action = comp.prop("ACTION").ok_or_else(|| missing("ACTION"))?;
+    let Ok(action) = action.value.parse::<AlarmAction>() else {
+        return Ok(None);
+    };
+    let trigger = comp.prop("TRIGGER").ok_or_else(|| missing("TRIGGER"))?;
+    let trigger = match trigger.param("VALUE") {
+        Some(value) if value.eq_ignore_ascii_case("DATE-TIME") => {
+            let value = read_value(trigger, &HashMap::new())?
+                .into_iter()
+                .next()
+                .filter(|value| value.tz == EventTz::Utc)
+                .ok_or_else(|| trigger.error("an absolute TRIGGER must be in UTC"))?;
+            Trigger::At(value.dt.and_utc())
+        }
+        _ => match trigger.param("RELATED") {
+            Some(related) if related.eq_ignore_ascii_case("END") => {
+                Trigger::End(read_duration(trigger)?)
+            }
+            _ => Trigger::Start(read_duration(trigger)?),
+        },
+    };
+
+    let mut alarm = Alarm::new(action, trigger)
+        .set_description(comp.prop("DESCRIPTION").map(|p| unescape(&p.value)));
+    if let Some(prop) = comp.prop("REPEAT") {
+        let repeat = prop
+            .value
+            .parse()
+            .map_err(|_| prop.error(format!("invalid repeat count {}", prop.value)))?;
+        let interval = comp.prop("DURATION").ok_or_else(|| missing("DURATION"))?;
+        alarm = alarm
+            .set_repeat(repeat, read_duration(interval)?)
+            .map_err(|e| interval.error(e.to_string()))?;
+    }
+    Ok(Some(alarm))
+}
+
+/// read an RFC 5545 DURATION such as `PT1H30M`, `P1D` or `-P2W`
+fn read_duration(prop: &Property) -> Result<Duration, EventError> {
+    let error = || prop.error(format!("invalid duration {}", prop.value));
+    let text = prop.value.as_str();
+    let (sign, text) = match text.as_bytes().first() {
+        Some(b'-') => (-1, &text[1..]),
+        Some(b'+') => (1, &text[1..]),
+        _ => (1, text),
+    };
+    let text = text.strip_prefix('P').ok_or_else(error)?;
+
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in text.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if number.is_empty() => in_time = true,
+            unit => {
+                let n: i64 = number.parse().map_err(|_| error())?;
+                number.clear();
+                let part = match (unit, in_time) {
+                    ('W', false) => Duration::try_weeks(n),
+                    ('D', false) => Duration::try_days(n),
+                    ('H', true) => Duration::try_hours(n),
+                    ('M', true) => Duration::try_minutes(n),
+                    ('S', true) => Duration::try_seconds(n),
+                    _ => return Err(error()),
+                };
+                total = part
+                    .and_then(|part| total.checked_add(&part))
+                    .ok_or_else(error)?;
+            }
+        }
+    }
+    if !number.is_empty() {
+        return Err(error());
+    }
+    total.checked_mul(sign).ok_or_else(error)
+}
+
+/// the id an event with the given UID is stored under, UIDs that are
+/// already UUIDs are kept as they are
+fn uid_to_uuid(uid: &str) -> Uuid {
+    Uuid::parse_str(uid).unwrap_or_else(|_| Uuid::new_v5(&Uuid::NAMESPACE_OID, uid.as_bytes()))
+}
+
+// Writing of RFC 5545 iCalendar text
+
+/// write a calendar's events, with DTSTAMP set to `dtstamp`
+pub(crate) fn write_calendar(cal: &EventCalendar, dtstamp: DateTime<Utc>) -> String {
+    let mut out = String::new();
+    write_header(&mut out);
+
+    // every zone in use, with the years its events and tasks take place in.
+    // Zones only have rules up to now, so series running on past the year of
+    // `dtstamp` are left to the yearly rules written for it
+    let mut zones: BTreeMap<&str, (Tz, i32, i32)> = BTreeMap::new();
+    let events = cal
+        .events()
+        .chain(cal.events().flat_map(|evt| cal.overrides(evt.id())))
+        .map(|evt| {
+            let last = evt.span_end().year().min(dtstamp.year());
+            (evt.tz(), Some((evt.start().year(), last)))
+        });
+    let tasks = cal.tasks().map(|task| {
+        let at = task.start().or(task.due());
+        (task.tz(), at.map(|at| (at.year(), at.year())))
+    });
+    for (tz, years) in events.chain(tasks) {
+        if let (EventTz::Zone(tz), Some((first, last))) = (tz, years) {
+            let last = last.max(first);
+            let entry = zones.entry(tz.name()).or_insert((tz, first, last));
+            entry.1 = entry.1.min(first);
+            entry.2 = entry.2.max(last);
+        }
+    }
+    for (tz, first, last) in zones.into_values() {
+        write_timezone(&mut out, tz, first, last);
+    }
+
+    for evt in cal.events() {
+        write_event(&mut out, evt, evt, dtstamp);
+        for over in cal.overrides(evt.id()) {
+            write_event(&mut out, over, evt, dtstamp);
+        }
+    }
+    for task in cal.tasks() {
+        write_task(&mut out, task, dtstamp);
+    }
+    for journal in cal.journals() {
+        write_journal(&mut out, journal, dtstamp);
+    }
+
+    write_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// write a VFREEBUSY listing the busy periods of `fb`, which are in UTC
+pub(crate) fn write_free_busy(fb: &FreeBusy, dtstamp: DateTime<Utc>) -> String {
+    let window = fb.window();
+    let value = |dt: NaiveDateTime| format!("{}Z", dt.format(DATE_TIME));
+    let periods: Vec<_> = fb
+        .busy()
+        .iter()
+        .map(|p| format!("{}/{}", value(p.start()), value(p.end())))
+        .collect();
+    // the same free/busy time always gets the same UID
+    let uid = format!(
+        "{}/{}:{}",
+        value(window.start()),
+        value(window.end()),
+        periods.join(",")
+    );
+    let uid = Uuid::new_v5(&Uuid::NAMESPACE_OID, uid.as_bytes());
+
+    let mut out = String::new();
+    write_header(&mut out);
+    write_line(&mut out, "BEGIN:VFREEBUSY");
+    write_line(&mut out, &format!("UID:{uid}"));
+    write_line(&mut out, &format!("DTSTAMP:{}Z", dtstamp.format(DATE_TIME)));
+    write_line(&mut out, &format!("DTSTART:{}", value(window.start())));
+    write_line(&mut out, &format!("DTEND:{}", value(window.end())));
+    if !periods.is_empty() {
+        write_line(
+            &mut out,
+            &format!("FREEBUSY;FBTYPE=BUSY:{}", periods.join(",")),
+        );
+    }
+    write_line(&mut out, "END:VFREEBUSY");
+    write_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// write the start of a VCALENDAR
+fn write_header(out: &mut String) {
+    write_line(out, "BEGIN:VCALENDAR");
+    write_line(out, "VERSION:2.0");
+    write_line(
+        out,
+        &format!("PRODID:-//calib//calib {}//EN", env!("CARGO_PKG_VERSION")),
+    );
+    write_line(out, "CALSCALE:GREGORIAN");
+}
+
+/// write a VEVENT, `series` is the series an override belongs to, whose
+/// DTSTART its RECURRENCE-ID is written like. Events are their own series
+fn write_event(out: &mut String, evt: &Event, series: &Event, dtstamp: DateTime<Utc>) {
+    let tz = evt.tz();
+    // all day events are written with DATE values
+    let values = |evt: &Event, values: &[NaiveDateTime]| match evt.is_all_day() {
+        true => dates(values),
+        false => date_time(evt.tz(), values),
+    };
+    write_line(out, "BEGIN:VEVENT");
+    write_line(out, &format!("UID:{}", evt.id()));
+    write_line(out, &format!("DTSTAMP:{}Z", dtstamp.format(DATE_TIME)));
+    write_line(out, &format!("DTSTART{}", values(evt, &[evt.start()])));
+    write_line(out, &format!("DTEND{}", values(evt, &[evt.end()])));
+    write_line(out, &format!("SUMMARY:{}", escape(evt.name())));
+
+    if let Some(recurrence_id) = evt.recurrence_id() {
+        write_line(
+            out,
+            &format!("RECURRENCE-ID{}", values(series, &[recurrence_id])),
+        );
+    }
+    if let Some(rule) = evt.recurrence() {
+        let rule = match evt.is_all_day() {
+            true => rule_in_dates(rule),
+            false => rule_in_utc(rule, tz),
+        };
+        write_line(out, &format!("RRULE:{rule}"));
+    }
+    let rdates: Vec<_> = evt.rdates().copied().collect();
+    if !rdates.is_empty() {
+        write_line(out, &format!("RDATE{}", values(evt, &rdates)));
+    }
+    let exdates: Vec<_> = evt.exdates().copied().collect();
+    if !exdates.is_empty() {
+        write_line(out, &format!("EXDATE{}", values(evt, &exdates)));
+    }
+    if let Some(related) = evt.related_to() {
+        write_line(out, &format!("RELATED-TO:{related}"));
+    }
+    if let Some(description) = evt.description() {
+        write_line(out, &format!("DESCRIPTION:{}", escape(description)));
+    }
+    if let Some(location) = evt.location() {
+        write_line(out, &format!("LOCATION:{}", escape(location)));
+    }
+    if let Some(status) = evt.status() {
+        write_line(out, &format!("STATUS:{status}"));
+    }
+    if !evt.transparency().is_opaque() {
+        write_line(out, &format!("TRANSP:{}", evt.transparency()));
+    }
+    if let Some(priority) = evt.priority() {
+        write_line(out, &format!("PRIORITY:{priority}"));
+    }
+    if let Some(url) = evt.url() {
+        write_line(out, &format!("URL:{url}"));
+    }
+    if let Some(organizer) = evt.organizer() {
+        write_line(out, &format!("ORGANIZER:{organizer}"));
+    }
+    for attendee in evt.attendees() {
+        let mut line = String::from("ATTENDEE");
+        if let Some(name) = attendee.name() {
+            line.push_str(&format!(";CN={}", param_value(name)));
+        }
+        line.push_str(&format!(
+            ";ROLE={};PARTSTAT={}",
+            attendee.role(),
+            attendee.status()
+        ));
+        if attendee.rsvp() {
+            line.push_str(";RSVP=TRUE");
+        }
+        line.push_str(&format!(":{}", attendee.address()));
+        write_line(out, &line);
+    }
+    for alarm in evt.alarms() {
+        write_alarm(out, alarm);
+    }
+
+    write_line(out, "END:VEVENT");
+}
+
+/// write a VTODO
+fn write_task(out: &mut String, task: &Task, dtstamp: DateTime<Utc>) {
+    write_line(out, "BEGIN:VTODO");
+    write_line(out, &format!("UID:{}", task.id()));
+    write_line(out, &format!("DTSTAMP:{}Z", dtstamp.format(DATE_TIME)));
+    let value = |dt| match task.is_all_day() {
+        true => dates(&[dt]),
+        false => date_time(task.tz(), &[dt]),
+    };
+    if let Some(start) = task.start() {
+        write_line(out, &format!("DTSTART{}", value(start)));
+    }
+    if let Some(due) = task.due() {
+        write_line(out, &format!("DUE{}", value(due)));
+    }
+    write_line(out, &format!("SUMMARY:{}", escape(task.name())));
+    write_line(out, &format!("STATUS:{}", task.status()));
+    if task.percent_complete() > 0 {
+        write_line(
+            out,
+            &format!("PERCENT-COMPLETE:{}", task.percent_complete()),
+        );
+    }
+    if let Some(completed) = task.completed() {
+        write_line(out, &format!("COMPLETED:{}Z", completed.format(DATE_TIME)));
+    }
+    if let Some(priority) = task.priority() {
+        write_line(out, &format!("PRIORITY:{priority}"));
+    }
+    if let Some(description) = task.description() {
+        write_line(out, &format!("DESCRIPTION:{}", escape(description)));
+    }
+    write_line(out, "END:VTODO");
+}
+
+/// write a VJOURNAL
+fn write_journal(out: &mut String, journal: &Journal, dtstamp: DateTime<Utc>) {
+    let date = NaiveDateTime::new(journal.date(), NaiveTime::MIN);
+    write_line(out, "BEGIN:VJOURNAL");
+    write_line(out, &format!("UID:{}", journal.id()));
+    write_line(out, &format!("DTSTAMP:{}Z", dtstamp.format(DATE_TIME)));
+    write_line(out, &format!("DTSTART{}", dates(&[date])));
+    write_line(out, &format!("SUMMARY:{}", escape(journal.name())));
+    if !journal.body().is_empty() {
+        write_line(out, &format!("DESCRIPTION:{}", escape(journal.body())));
+    }
+    if let Some(related) = journal.related_to() {
+        write_line(out, &format!("RELATED-TO:{related}"));
+    }
+    write_line(out, "END:VJOURNAL");
+}
+
+/// write a VALARM
+fn write_alarm(out: &mut String, alarm: &Alarm) {
+    write_line(out, "BEGIN:VALARM");
+    write_line(out, &format!("ACTION:{}", alarm.action()));
+    let trigger = match alarm.trigger() {
+        Trigger::Start(offset) => format!("TRIGGER:{}", duration(offset)),
+        Trigger::End(offset) => format!("TRIGGER;RELATED=END:{}", duration(offset)),
+        Trigger::At(at) => format!("TRIGGER;VALUE=DATE-TIME:{}Z", at.format(DATE_TIME)),
+    };
+    write_line(out, &trigger);
+    if alarm.repeat() > 0 {
+        write_line(out, &format!("REPEAT:{}", alarm.repeat()));
+        write_line(out, &format!("DURATION:{}", duration(alarm.interval())));
+    }
+    if let Some(description) = alarm.description() {
+        write_line(out, &format!("DESCRIPTION:{}", escape(description)));
+    }
+    write_line(out, "END:VALARM");
+}
+
+/// an RFC 5545 DURATION such as `-PT10M` or `P1DT12H`
+fn duration(duration: Duration) -> String {
+    let sign = if duration < Duration::zero() { "-" } else { "" };
+    let seconds = duration.num_seconds().unsigned_abs();
+    let (days, rest) = (seconds / 86_400, seconds % 86_400);
+    let (hours, minutes, seconds) = (rest / 3600, rest % 3600 / 60, rest % 60);
+
+    let mut text = format!("{sign}P");
+    if days > 0 {
+        text.push_str(&format!("{days}D"));
+    }
+    if rest > 0 || days == 0 {
+        text.push('T');
+        if hours > 0 {
+            text.push_str(&format!("{hours}H"));
+        }
+        if minutes > 0 {
+            text.push_str(&format!("{minutes}M"));
+        }
+        if seconds > 0 || rest == 0 {
+            text.push_str(&format!("{seconds}S"));
+        }
+    }
+    text
+}
+
+/// the parameters and value of a DATE-TIME property, like
+/// `;TZID=Europe/Paris:20230102T090000`
+fn date_time(tz: EventTz, values: &[NaiveDateTime]) -> String {
+    let values: Vec<_> = values
+        .iter()
+        .map(|dt| dt.format(DATE_TIME).to_string())
+        .collect();
+    let values = values.join(",");
+    match tz {
+        EventTz::Floating => format!(":{values}"),
+        EventTz::Utc => format!(":{}Z", values.replace(',', "Z,")),
+        EventTz::Zone(tz) => format!(";TZID={}:{values}", tz.name()),
+    }
+}
+
+/// the parameters and value of a DATE property, like
+/// `;VALUE=DATE:20230102`
+fn dates(values: &[NaiveDateTime]) -> String {
+    let values: Vec<_> = values
+        .iter()
+        .map(|dt| dt.format("%Y%m%d").to_string())
+        .collect();
+    format!(";VALUE=DATE:{}", values.join(","))
+}
+
+/// RFC 5545 wants UNTIL as a DATE when DTSTART is one, occurrences of an
+/// all day event start at midnight so the time can be dropped
+fn rule_in_dates(rule: &RRule) -> String {
+    let Some(until) = rule.until() else {
+        return rule.to_string();
+    };
+    let local = format!("UNTIL={}", until.format(DATE_TIME));
+    rule.to_string()
+        .replace(&local, &format!("UNTIL={}", until.format("%Y%m%d")))
+}
+
+/// RFC 5545 wants UNTIL in UTC whenever DTSTART has a time zone
+fn rule_in_utc(rule: &RRule, tz: EventTz) -> String {
+    let Some(until) = rule.until().filter(|_| !tz.is_floating()) else {
+        return rule.to_string();
+    };
+    let utc = tz
+        .to_utc(until, &Utc, Disambiguation::Compatible)
+        .map_or(until, |dt| dt.naive_utc());
+    let local = format!("UNTIL={}", until.format(DATE_TIME));
+    rule.to_string()
+        .replace(&local, &format!("UNTIL={}Z", utc.format(DATE_TIME)))
+}
+
+/// A change of offset in a time zone that may happen the same way in the
+/// years after it, written as a STANDARD or DAYLIGHT observance
+struct Observance {
+    daylight: bool,
+    from: FixedOffset,
+    to: FixedOffset,
+    name: Option<String>,
+    // the month and weekday of the month it happens on, if it can be told
+    rule: Option<(u32, WeekdayNum)>,
+    // the local time of the first change and of the last, and the last in UTC
+    first: NaiveDateTime,
+    last: NaiveDateTime,
+    last_utc: NaiveDateTime,
+    count: usize,
+}
+
+impl Observance {
+    /// a change at `utc` from the offset `before` to `after`
+    fn new(utc: NaiveDateTime, before: TzOffset, after: TzOffset) -> Self {
+        // observances start at the local time of the offset they replace
+        let local = utc + Duration::seconds(before.fix().local_minus_utc() as i64);
+        let day = local.day() as i64;
+        let days_in_month = local
+            .date()
+            .with_day(1)
+            .and_then(|first| first.checked_add_months(Months::new(1)))
+            .map_or(31, |next| {
+                next.pred_opt().map_or(31, |last| last.day() as i64)
+            });
+        // changes late in the month are taken to happen on its last weekday
+        let n = match day + 7 > days_in_month {
+            true => -1,
+            false => (day + 6) / 7,
+        };
+        Observance {
+            daylight: !after.dst_offset().is_zero(),
+            from: before.fix(),
+            to: after.fix(),
+            name: after.abbreviation().map(str::to_string),
+            rule: WeekdayNum::nth(n as i8, local.weekday())
+                .ok()
+                .map(|weekday| (local.month(), weekday)),
+            first: local,
+            last: local,
+            last_utc: utc,
+            count: 1,
+        }
+    }
+
+    /// add `next` to the observance if it is the same change a year later
+    fn extend(&mut self, next: &Observance) -> bool {
+        let same = self.rule.is_some()
+            && (self.from, self.to, &self.name, self.rule)
+                == (next.from, next.to, &next.name, next.rule)
+            && self.first.time() == next.first.time()
+            && self.last.year() + 1 == next.first.year();
+        if same {
+            self.last = next.first;
+            self.last_utc = next.last_utc;
+            self.count += 1;
+        }
+        same
+    }
+}
+
+/// write a VTIMEZONE describing `tz` between the start of `first` and the
+/// end of `last`. Changes that happen the same way every year are written
+/// as a single observance with a yearly rule, which is left to run on if it
+/// still holds in `last`
+fn write_timezone(out: &mut String, tz: Tz, first: i32, last: i32) {
+    write_line(out, "BEGIN:VTIMEZONE");
+    write_line(out, &format!("TZID:{}", tz.name()));
+
+    let offset_at = |utc: NaiveDateTime| tz.offset_from_utc_datetime(&utc);
+    // the offset in use when `first` starts comes from a change the year before
+    let start = NaiveDate::from_ymd_opt(first - 1, 1, 1).map(|d| d.and_time(NaiveTime::MIN));
+    let end = NaiveDate::from_ymd_opt(last + 1, 1, 1).map(|d| d.and_time(NaiveTime::MIN));
+    let (Some(start), Some(end)) = (start, end) else {
+        write_line(out, "END:VTIMEZONE");
+        return;
+    };
+
+    // find each change of offset by stepping a week at a time, then narrowing
+    // down to the second it happens at
+    let mut observances: Vec<Observance> = Vec::new();
+    let mut week = start;
+    while week < end {
+        let next = week + Duration::weeks(1);
+        if offset_at(week) != offset_at(next) {
+            let (mut lo, mut hi) = (week, next);
+            while hi - lo > Duration::seconds(1) {
+                let mid = lo + (hi - lo) / 2;
+                match offset_at(mid) == offset_at(lo) {
+                    true => lo = mid,
+                    false => hi = mid,
+                }
+            }
+            let change = Observance::new(hi, offset_at(lo), offset_at(hi));
+            let latest = observances
+                .iter_mut()
+                .rev()
+                .find(|o| o.daylight == change.daylight);
+            if !latest.is_some_and(|o| o.extend(&change)) {
+                observances.push(change);
+            }
+        }
+        week = next;
+    }
+
+    if observances.is_empty() {
+        let offset = offset_at(start);
+        let mut only = Observance::new(start, offset, offset);
+        only.first = start;
+        only.rule = None;
+        observances.push(only);
+    }
+    for obs in observances {
+        let kind = match obs.daylight {
+            true => "DAYLIGHT",
+            false => "STANDARD",
+        };
+        write_line(out, &format!("BEGIN:{kind}"));
+        write_line(out, &format!("DTSTART:{}", obs.first.format(DATE_TIME)));
+        if let Some((month, weekday)) = obs.rule.filter(|_| obs.count > 1) {
+            let rule = format!("RRULE:FREQ=YEARLY;BYMONTH={month};BYDAY={weekday}");
+            match obs.last.year() == last {
+                true => write_line(out, &rule),
+                false => write_line(
+                    out,
+                    &format!("{rule};UNTIL={}Z", obs.last_utc.format(DATE_TIME)),
+                ),
+            }
+        }
+        write_line(out, &format!("TZOFFSETFROM:{}", utc_offset(obs.from)));
+        write_line(out, &format!("TZOFFSETTO:{}", utc_offset(obs.to)));
+        if let Some(name) = obs.name {
+            write_line(out, &format!("TZNAME:{}", escape(&name)));
+        }
+        write_line(out, &format!("END:{kind}"));
+    }
+
+    write_line(out, "END:VTIMEZONE");
+}
+
+/// a UTC-OFFSET value such as `+0100` or `-0430`
+fn utc_offset(offset: FixedOffset) -> String {
+    let secs = offset.local_minus_utc();
+    let sign = if secs < 0 { '-' } else { '+' };
+    let secs = secs.abs();
+    match secs % 60 {
+        0 => format!("{sign}{:02}{:02}", secs / 3600, secs % 3600 / 60),
+        s => format!("{sign}{:02}{:02}{s:02}", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// a parameter value, quoted if it has characters that would end it.
+/// Parameter values cannot hold double quotes at all so they are dropped
+fn param_value(value: &str) -> String {
+    let value = value.replace('"', "");
+    match value.contains([':', ';', ',']) {
+        true => format!("\"{value}\""),
+        false => value,
+    }
+}
+
+/// escape TEXT values
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// write a content line, folding it so no line is longer than 75 octets
+fn write_line(out: &mut String, line: &str) {
+    const MAX_OCTETS: usize = 75;
+
+    let mut len = 0;
+    for c in line.chars() {
+        // never split a multi-byte character across lines
+        if len + c.len_utf8() > MAX_OCTETS {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
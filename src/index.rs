@@ -0,0 +1,258 @@
+use chrono::NaiveDateTime;
+use std::cmp::{max, Ordering};
+use std::sync::Arc;
+
+use super::event::Event;
+
+// An augmented AVL tree ordered by the start of each event's first
+// occurrence, then the same way as the events themselves, where every node
+// also remembers the latest end time found in its subtree. That lets overlap
+// queries skip any subtree that finishes before the query window starts, and
+// stop entirely once events begin after it ends
+
+type Link = Option<Box<Node>>;
+
+struct Node {
+    evt: Arc<Event>,
+    end: NaiveDateTime,
+    max_end: NaiveDateTime,
+    height: u8,
+    left: Link,
+    right: Link,
+}
+
+impl Node {
+    fn new(evt: Arc<Event>, end: NaiveDateTime) -> Box<Self> {
+        Box::new(Node {
+            evt,
+            end,
+            max_end: end,
+            height: 1,
+            left: None,
+            right: None,
+        })
+    }
+
+    /// recompute height and max_end from the children
+    fn update(&mut self) {
+        self.height = 1 + max(height(&self.left), height(&self.right));
+        self.max_end = [&self.left, &self.right]
+            .into_iter()
+            .flatten()
+            .fold(self.end, |acc, child| max(acc, child.max_end));
+    }
+
+    fn balance_factor(&self) -> i16 {
+        height(&self.left) as i16 - height(&self.right) as i16
+    }
+}
+
+/// the order of events in the tree. An extra occurrence may come before the
+/// start of an event, so events are kept in order of their first occurrence
+fn order(a: &Event, b: &Event) -> Ordering {
+    a.span_start().cmp(&b.span_start()).then_with(|| a.cmp(b))
+}
+
+fn height(link: &Link) -> u8 {
+    link.as_ref().map_or(0, |n| n.height)
+}
+
+fn rotate_right(mut node: Box<Node>) -> Box<Node> {
+    let mut pivot = node
+        .left
+        .take()
+        .expect("rotate_right requires a left child");
+    node.left = pivot.right.take();
+    node.update();
+    pivot.right = Some(node);
+    pivot.update();
+    pivot
+}
+
+fn rotate_left(mut node: Box<Node>) -> Box<Node> {
+    let mut pivot = node
+        .right
+        .take()
+        .expect("rotate_left requires a right child");
+    node.right = pivot.left.take();
+    node.update();
+    pivot.left = Some(node);
+    pivot.update();
+    pivot
+}
+
+fn rebalance(mut node: Box<Node>) -> Box<Node> {
+    node.update();
+    match node.balance_factor() {
+        2.. => {
+            if node.left.as_ref().is_some_and(|l| l.balance_factor() < 0) {
+                node.left = node.left.take().map(rotate_left);
+            }
+            rotate_right(node)
+        }
+        ..=-2 => {
+            if node.right.as_ref().is_some_and(|r| r.balance_factor() > 0) {
+                node.right = node.right.take().map(rotate_right);
+            }
+            rotate_left(node)
+        }
+        _ => node,
+    }
+}
+
+fn insert(link: Link, evt: Arc<Event>, end: NaiveDateTime) -> (Box<Node>, bool) {
+    let Some(mut node) = link else {
+        return (Node::new(evt, end), true);
+    };
+
+    let inserted = match order(&evt, &node.evt) {
+        Ordering::Less => {
+            let (child, inserted) = insert(node.left.take(), evt, end);
+            node.left = Some(child);
+            inserted
+        }
+        Ordering::Greater => {
+            let (child, inserted) = insert(node.right.take(), evt, end);
+            node.right = Some(child);
+            inserted
+        }
+        Ordering::Equal => false,
+    };
+
+    (rebalance(node), inserted)
+}
+
+/// detach the leftmost node of a subtree, returning it and what remains
+fn take_min(mut node: Box<Node>) -> (Box<Node>, Link) {
+    match node.left.take() {
+        Some(left) => {
+            let (min, rest) = take_min(left);
+            node.left = rest;
+            (min, Some(rebalance(node)))
+        }
+        None => {
+            let rest = node.right.take();
+            (node, rest)
+        }
+    }
+}
+
+fn remove(link: Link, evt: &Event) -> (Link, Option<Arc<Event>>) {
+    let Some(mut node) = link else {
+        return (None, None);
+    };
+
+    let removed = match order(evt, &node.evt) {
+        Ordering::Less => {
+            let (child, removed) = remove(node.left.take(), evt);
+            node.left = child;
+            removed
+        }
+        Ordering::Greater => {
+            let (child, removed) = remove(node.right.take(), evt);
+            node.right = child;
+            removed
+        }
+        Ordering::Equal => {
+            let replacement = match (node.left.take(), node.right.take()) {
+                (None, None) => None,
+                (Some(child), None) | (None, Some(child)) => Some(child),
+                (Some(left), Some(right)) => {
+                    let (mut successor, rest) = take_min(right);
+                    successor.left = Some(left);
+                    successor.right = rest;
+                    Some(rebalance(successor))
+                }
+            };
+            return (replacement, Some(node.evt));
+        }
+    };
+
+    (Some(rebalance(node)), removed)
+}
+
+/// Index of events supporting overlap queries in O(log n + k)
+#[derive(Default)]
+pub(crate) struct IntervalIndex {
+    root: Link,
+}
+
+impl IntervalIndex {
+    /// index an event covering [evt.span_start(), end), returns false if the
+    /// event was already indexed
+    pub fn insert(&mut self, evt: Arc<Event>, end: NaiveDateTime) -> bool {
+        let (root, inserted) = insert(self.root.take(), evt, end);
+        self.root = Some(root);
+        inserted
+    }
+
+    /// remove an event from the index, returning it if it was present
+    pub fn remove(&mut self, evt: &Event) -> Option<Arc<Event>> {
+        let (root, removed) = remove(self.root.take(), evt);
+        self.root = root;
+        removed
+    }
+
+    /// the earliest start of any indexed event, None when the index is empty
+    pub fn min_start(&self) -> Option<NaiveDateTime> {
+        let mut node = self.root.as_deref()?;
+        while let Some(left) = node.left.as_deref() {
+            node = left;
+        }
+        Some(node.evt.span_start())
+    }
+
+    /// the latest end of any indexed event, None when the index is empty
+    pub fn max_end(&self) -> Option<NaiveDateTime> {
+        self.root.as_ref().map(|root| root.max_end)
+    }
+
+    /// iterate in chronological order over the indexed events whose interval
+    /// overlaps the half-open range [start, end)
+    pub fn overlapping(&self, start: NaiveDateTime, end: NaiveDateTime) -> Overlapping<'_> {
+        let mut iter = Overlapping {
+            stack: Vec::with_capacity(height(&self.root) as usize),
+            start,
+            end,
+        };
+        iter.push_left(self.root.as_deref());
+        iter
+    }
+}
+
+/// Iterator over the results of [`IntervalIndex::overlapping`]
+pub(crate) struct Overlapping<'a> {
+    stack: Vec<&'a Node>,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+}
+
+impl<'a> Overlapping<'a> {
+    /// walk down the left spine, ignoring subtrees that all end too early
+    fn push_left(&mut self, mut node: Option<&'a Node>) {
+        while let Some(n) = node.filter(|n| n.max_end > self.start) {
+            self.stack.push(n);
+            node = n.left.as_deref();
+        }
+    }
+}
+
+impl<'a> Iterator for Overlapping<'a> {
+    type Item = &'a Arc<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            // nodes come out in order, so nothing after this can overlap
+            if node.evt.span_start() >= self.end {
+                self.stack.clear();
+                return None;
+            }
+
+            self.push_left(node.right.as_deref());
+            if node.end > self.start {
+                return Some(&node.evt);
+            }
+        }
+        None
+    }
+}
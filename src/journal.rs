@@ -0,0 +1,95 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Notes kept for a day, like a VJOURNAL. The body is free text that may
+/// run over many lines and paragraphs
+///
+/// # Examples
+/// ```
+/// use calib::{EventCalendar, Journal};
+/// use chrono::NaiveDate;
+///
+/// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+/// let notes = Journal::new(date, "Planning".into())
+///     .set_body("Decisions:\n- ship on Friday\n- skip the retro".into());
+///
+/// let mut cal = EventCalendar::default();
+/// cal.add_journal(notes);
+/// let found: Vec<_> = cal.journals_on(date).map(|j| j.name()).collect();
+/// assert_eq!(found, ["Planning"]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Journal {
+    date: NaiveDate,
+    name: String,
+    id: Uuid,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    body: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    related_to: Option<Uuid>,
+}
+
+impl Journal {
+    /// create an empty entry for a day
+    pub fn new(date: NaiveDate, name: String) -> Self {
+        Journal {
+            date,
+            name,
+            id: Uuid::new_v4(),
+            body: String::new(),
+            related_to: None,
+        }
+    }
+
+    /// returns the day the entry is for
+    pub fn date(&self) -> NaiveDate {
+        self.date
+    }
+
+    /// Set/Change the day the entry is for
+    pub fn set_date(self, date: NaiveDate) -> Self {
+        Journal { date, ..self }
+    }
+
+    /// returns the title of the entry
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Change the title of the entry
+    pub fn set_name(self, name: String) -> Self {
+        Journal { name, ..self }
+    }
+
+    /// returns the unique id of the entry
+    pub fn id(&self) -> &Uuid {
+        &self.id
+    }
+
+    /// returns the text of the entry
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// Set/Change the text of the entry
+    pub fn set_body(self, body: String) -> Self {
+        Journal { body, ..self }
+    }
+
+    /// returns the id of the event the entry is about, such as the meeting
+    /// the notes were taken in
+    pub fn related_to(&self) -> Option<&Uuid> {
+        self.related_to.as_ref()
+    }
+
+    /// Set/Change/Clear the event the entry is about
+    pub fn set_related_to(self, related_to: Option<Uuid>) -> Self {
+        Journal { related_to, ..self }
+    }
+
+    /// give the entry a specific id, for entries read back from storage
+    pub(crate) fn with_id(self, id: Uuid) -> Self {
+        Journal { id, ..self }
+    }
+}
@@ -1,23 +1,49 @@
 use thiserror::Error;
 
+mod alarm;
+mod attendee;
 mod cal;
+mod clock;
 mod event;
-
-pub use cal::EventCalendar;
-pub use event::Event;
+mod freebusy;
+mod history;
+mod ical;
+mod index;
+mod journal;
+mod log;
+mod props;
+mod recur;
+mod schedule;
+mod shared;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod storage;
+mod task;
+mod tz;
+
+pub use alarm::{Alarm, AlarmAction, AlarmEngine, DueAlarm, Trigger};
+pub use attendee::{Attendee, PartStat, Role};
+pub use cal::{EventCalendar, Occurrence, OverlapPolicy};
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use event::{Event, OccurrenceStarts};
+pub use freebusy::{FreeBusy, Period};
+pub use journal::Journal;
+pub use log::LogStorage;
+pub use props::{Status, Transparency};
+pub use recur::{Frequency, RRule, RuleIter, WeekdayNum};
+pub use schedule::SlotQuery;
+pub use shared::SharedCalendar;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+pub use storage::{Change, MemoryStorage, PersistentCalendar, Storage};
+pub use task::{Task, TaskStatus};
+pub use tz::{Disambiguation, EventTz};
 use uuid::Uuid;
 
 pub trait IntoUuid {
     fn into_uuid(self) -> Uuid;
 }
 
-impl IntoUuid for &str {
-    fn into_uuid(self) -> Uuid {
-        use std::str::FromStr;
-        Uuid::from_str(self).unwrap() // this is bad and will need to be fixed
-    }
-}
-
 impl IntoUuid for &Uuid {
     fn into_uuid(self) -> Uuid {
         *self
@@ -30,6 +56,70 @@ impl IntoUuid for Uuid {
     }
 }
 
+/// Fallible conversion into an event id, for ids that come from untrusted
+/// input. Text may be hyphenated, hyphenless, braced or a `urn:uuid:` URN
+///
+/// ```
+/// use calib::{EventError, TryIntoUuid};
+///
+/// let id = "67e55044-10b1-426f-9247-bb680e5fe0c8".try_into_uuid().unwrap();
+/// assert_eq!("67e5504410b1426f9247bb680e5fe0c8".try_into_uuid().unwrap(), id);
+/// assert_eq!("urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8".try_into_uuid().unwrap(), id);
+/// assert_eq!(id.as_bytes().as_slice().try_into_uuid().unwrap(), id);
+/// assert!(matches!("garbage".try_into_uuid(), Err(EventError::InvalidUuid(..))));
+/// ```
+pub trait TryIntoUuid {
+    fn try_into_uuid(self) -> Result<Uuid, EventError>;
+}
+
+impl TryIntoUuid for &str {
+    fn try_into_uuid(self) -> Result<Uuid, EventError> {
+        Uuid::try_parse(self).map_err(|e| EventError::InvalidUuid(self.to_string(), e.to_string()))
+    }
+}
+
+impl TryIntoUuid for String {
+    fn try_into_uuid(self) -> Result<Uuid, EventError> {
+        self.as_str().try_into_uuid()
+    }
+}
+
+impl TryIntoUuid for &String {
+    fn try_into_uuid(self) -> Result<Uuid, EventError> {
+        self.as_str().try_into_uuid()
+    }
+}
+
+/// 16 raw bytes, or the id written out as ASCII text
+impl TryIntoUuid for &[u8] {
+    fn try_into_uuid(self) -> Result<Uuid, EventError> {
+        if let Ok(bytes) = <[u8; 16]>::try_from(self) {
+            return Ok(Uuid::from_bytes(bytes));
+        }
+        Uuid::try_parse_ascii(self).map_err(|e| {
+            EventError::InvalidUuid(String::from_utf8_lossy(self).into_owned(), e.to_string())
+        })
+    }
+}
+
+impl TryIntoUuid for [u8; 16] {
+    fn try_into_uuid(self) -> Result<Uuid, EventError> {
+        Ok(Uuid::from_bytes(self))
+    }
+}
+
+impl TryIntoUuid for &Uuid {
+    fn try_into_uuid(self) -> Result<Uuid, EventError> {
+        Ok(*self)
+    }
+}
+
+impl TryIntoUuid for Uuid {
+    fn try_into_uuid(self) -> Result<Uuid, EventError> {
+        Ok(self)
+    }
+}
+
 /// Basic Errors that can occur for events
 #[derive(Error, Debug)]
 pub enum EventError {
@@ -40,6 +130,69 @@ pub enum EventError {
     /// Error for invalid end time for an event
     #[error("end time/date cannot be before start time/date")]
     InvalidEndTime,
+
+    /// Error for a malformed or contradictory recurrence rule
+    #[error("invalid recurrence rule: {0}")]
+    InvalidRecurrence(String),
+
+    /// Error for a property value that is out of range or malformed, such as
+    /// a priority above 9 or a URL without a scheme
+    #[error("invalid {0} {1:?}")]
+    InvalidProperty(&'static str, String),
+
+    /// Error for an event rejected because it overlaps the events with these
+    /// ids, see [`OverlapPolicy::Reject`]
+    #[error("event conflicts with {0:?}")]
+    Conflict(Vec<Uuid>),
+
+    /// Error for a reply from someone who is not an attendee of the event
+    #[error("{1} is not an attendee of event {0}")]
+    NotAnAttendee(Uuid, String),
+
+    /// Error for text or bytes that are not an event id
+    #[error("invalid event id {0:?}: {1}")]
+    InvalidUuid(String, String),
+
+    /// Error for an id that does not belong to any event in the calendar
+    #[error("no event with id {0}")]
+    NotFound(Uuid),
+
+    /// Error for an event given the id of another event in the calendar
+    #[error("another event already has id {0}")]
+    DuplicateId(Uuid),
+
+    /// Error for a time zone name that is not in the time zone database
+    #[error("unknown time zone {0}")]
+    UnknownTimeZone(String),
+
+    /// Error for a wall clock time that happens twice in a time zone
+    #[error("{0} is ambiguous in {1}")]
+    AmbiguousTime(chrono::NaiveDateTime, String),
+
+    /// Error for a wall clock time skipped over in a time zone
+    #[error("{0} does not exist in {1}")]
+    NonexistentTime(chrono::NaiveDateTime, String),
+
+    /// Error for malformed iCalendar input
+    #[error("line {line}: {reason}")]
+    Ics { line: usize, reason: String },
+
+    /// Error for an iCalendar component missing a required property
+    #[error("line {line}: missing required property {property}")]
+    IcsMissingProperty { line: usize, property: &'static str },
+
+    /// Error for malformed JSON, or JSON that does not describe an event
+    #[error("invalid JSON: {0}")]
+    Json(String),
+
+    /// Error for a recurrence id that is not an occurrence of the series
+    #[error("event {0} has no occurrence starting at {1}")]
+    NoSuchOccurrence(Uuid, chrono::NaiveDateTime),
+
+    /// Error reading from or writing to the storage behind a
+    /// [`PersistentCalendar`]
+    #[error("storage error: {0}")]
+    Storage(String),
 }
 
 /// returns a NaiveTime of 11:59:59
@@ -158,6 +311,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_invalid_event_time_change() {
         // basic date declaration
         let naive_date = first_day_2023_nd();
@@ -179,6 +333,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn invalid_events_test() {
         // basic date declaration
         let naive_date = first_day_2023_nd();
@@ -301,11 +456,13 @@ mod test {
         cal.add_event(e4);
         cal.add_event(e5);
 
-        let mut iter = cal.events_in_range(range_start, range_end);
+        let mut iter = cal
+            .events_in_range(range_start, range_end)
+            .map(|occ| occ.event());
 
-        assert_eq!(iter.next(), cal.get(&e2_id));
-        assert_eq!(iter.next(), cal.get(&e3_id));
-        assert_eq!(iter.next(), cal.get(&e4_id));
+        assert_eq!(iter.next(), cal.get(e2_id));
+        assert_eq!(iter.next(), cal.get(e3_id));
+        assert_eq!(iter.next(), cal.get(e4_id));
         assert_eq!(iter.next(), None);
     }
 
@@ -325,4 +482,2583 @@ mod test {
             format!("{{\"start\":\"{first_time}\",\"end\":\"{last_time}\",\"name\":\"A\",\"id\":\"{id}\"}}",)
         )
     }
+
+    #[test]
+    fn test_all_day_events() {
+        use chrono::{TimeZone, Utc};
+
+        let trip = Event::all_day("Trip".into(), ymd(2023, 1, 5), ymd(2023, 1, 8)).unwrap();
+        assert!(trip.is_all_day());
+        assert_eq!(
+            trip.start(),
+            NaiveDateTime::new(ymd(2023, 1, 5), first_time_nt())
+        );
+        assert_eq!(
+            trip.end(),
+            NaiveDateTime::new(ymd(2023, 1, 8), first_time_nt())
+        );
+        assert!(matches!(
+            trip.clone().set_dates(ymd(2023, 1, 5), ymd(2023, 1, 4)),
+            Err(EventError::InvalidEndTime)
+        ));
+        // giving it times makes it an ordinary event
+        let timed = trip
+            .clone()
+            .set_start_time(NaiveTime::from_hms_opt(9, 0, 0).unwrap())
+            .unwrap();
+        assert!(!timed.is_all_day());
+        assert_eq!(timed.dates(), None);
+        let moved = trip.clone().set_start_date(ymd(2023, 1, 6)).unwrap();
+        assert_eq!(moved.dates(), Some((ymd(2023, 1, 6), ymd(2023, 1, 8))));
+
+        // the end date is not part of the event
+        let birthday = Event::all_day("Birthday".into(), ymd(2023, 1, 8), ymd(2023, 1, 9))
+            .unwrap()
+            .set_recurrence(Some("FREQ=YEARLY;UNTIL=20250108".parse().unwrap()))
+            .add_exdate(NaiveDateTime::new(ymd(2024, 1, 8), first_time_nt()));
+        let mut cal = EventCalendar::default();
+        cal.add_event(trip.clone());
+        cal.add_event(birthday);
+        let on = |d| -> Vec<_> {
+            cal.events_on(ymd(2023, 1, d))
+                .map(|occ| occ.name().to_string())
+                .collect()
+        };
+        assert_eq!(on(4), Vec::<String>::new());
+        assert_eq!(on(7), ["Trip"]);
+        assert_eq!(on(8), ["Birthday"]);
+        let last_second = NaiveDateTime::new(ymd(2023, 1, 7), last_time_nt());
+        assert_eq!(cal.events_containing(last_second).count(), 1);
+
+        // dates are written without times
+        let json = trip.serialize();
+        assert!(json.starts_with("{\"start\":\"2023-01-05\",\"end\":\"2023-01-08\""));
+        assert_eq!(Event::deserialize(&json).unwrap(), trip);
+        assert!(!Event::deserialize(&timed.serialize()).unwrap().is_all_day());
+
+        let ics = cal.to_ics_with_stamp(Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap());
+        assert!(ics.contains("DTSTART;VALUE=DATE:20230105\r\nDTEND;VALUE=DATE:20230108\r\n"));
+        assert!(ics.contains("UNTIL=20250108\r\n"));
+        assert!(ics.contains("EXDATE;VALUE=DATE:20240108\r\n"));
+        let back = EventCalendar::from_ics(&ics).unwrap();
+        assert!(back.events().eq(cal.events()));
+    }
+
+    #[test]
+    fn test_event_deserialize() {
+        let nd = first_day_2023_nd();
+        let e = Event::new("A".into(), &nd)
+            .set_recurrence(Some("FREQ=DAILY;COUNT=3".parse().unwrap()))
+            .add_exdate(NaiveDateTime::new(nd.with_day(2).unwrap(), first_time_nt()))
+            .set_tz("Europe/Paris".parse().unwrap());
+        assert_eq!(Event::deserialize(&e.serialize()).unwrap(), e);
+        assert_eq!(serde_json::from_str::<Event>(&e.serialize()).unwrap(), e);
+
+        // the start must come before the end
+        let json = e.serialize();
+        let swapped = json
+            .replacen("\"start\"", "\"tmp\"", 1)
+            .replacen("\"end\"", "\"start\"", 1)
+            .replacen("\"tmp\"", "\"end\"", 1);
+        assert!(matches!(
+            Event::deserialize(&swapped),
+            Err(EventError::InvalidEndTime)
+        ));
+        assert!(serde_json::from_str::<Event>(&swapped).is_err());
+
+        assert!(matches!(
+            Event::deserialize(&json.replace("Europe/Paris", "Europe/Nowhere")),
+            Err(EventError::Json(_))
+        ));
+        assert!(matches!(
+            Event::deserialize("{\"name\":\"A\"}"),
+            Err(EventError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn test_event_properties() {
+        let e = Event::new("A".into(), &first_day_2023_nd())
+            .set_description(Some("Quarterly numbers;\nbring slides".into()))
+            .set_location(Some("Room 4, second floor".into()))
+            .set_status(Some(Status::Tentative))
+            .set_transparency(Transparency::Transparent)
+            .set_priority(Some(1))
+            .and_then(|e| e.set_url(Some("https://example.com/a".into())))
+            .and_then(|e| e.set_organizer(Some("mailto:bob@example.com")))
+            .unwrap();
+        assert_eq!(e.location(), Some("Room 4, second floor"));
+        assert_eq!(e.status(), Some(Status::Tentative));
+        assert_eq!(e.priority(), Some(1));
+
+        // 0 means no priority, above 9 is out of range
+        assert_eq!(e.clone().set_priority(Some(0)).unwrap().priority(), None);
+        let invalid =
+            |r: Result<Event, EventError>| matches!(r, Err(EventError::InvalidProperty(..)));
+        assert!(invalid(e.clone().set_priority(Some(10))));
+        assert!(invalid(e.clone().set_url(Some("example.com".into()))));
+        assert!(invalid(
+            e.clone().set_url(Some("https://exa mple.com".into()))
+        ));
+        assert!(invalid(e.clone().set_url(Some("1http:x".into()))));
+        assert!(invalid(e.clone().set_organizer(Some("bob"))));
+        assert_eq!(e.clone().set_organizer(None).unwrap().organizer(), None);
+
+        let json = e.serialize();
+        assert!(json.contains("\"status\":\"tentative\""));
+        assert!(json.contains("\"transparency\":\"transparent\""));
+        assert_eq!(Event::deserialize(&json).unwrap(), e);
+        assert!(invalid(Event::deserialize(
+            &json.replace("\"priority\":1", "\"priority\":12")
+        )));
+
+        let mut cal = EventCalendar::default();
+        cal.add_event(e.clone());
+        let ics = cal.to_ics();
+        assert!(ics.contains("DESCRIPTION:Quarterly numbers\\;\\nbring slides\r\n"));
+        assert!(ics.contains("TRANSP:TRANSPARENT\r\n"));
+        assert_eq!(
+            EventCalendar::from_ics(&ics)
+                .unwrap()
+                .first_event()
+                .map(|e| &**e),
+            Some(&e)
+        );
+    }
+
+    #[test]
+    fn test_attendees() {
+        use chrono::{TimeZone, Utc};
+
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let at = |d| Utc.with_ymd_and_hms(2022, 12, d, 12, 0, 0).unwrap();
+        let bob = Attendee::new("bob@example.com").unwrap().set_rsvp(true);
+        let carol = Attendee::new("mailto:carol@example.com")
+            .unwrap()
+            .set_name(Some("Smith, Carol".into()))
+            .set_role(Role::Optional);
+
+        let series = Event::new("Sync".into(), &ymd(2023, 1, 2))
+            .set_start_time(nine)
+            .unwrap()
+            .set_recurrence(Some("FREQ=WEEKLY;COUNT=4".parse().unwrap()))
+            .add_attendee(bob.clone())
+            .add_attendee(carol.clone());
+        let series_id = *series.id();
+        let lunch = Event::new("Lunch".into(), &ymd(2023, 1, 3)).add_attendee(bob);
+        let lunch_id = *lunch.id();
+        assert_eq!(series.attendees().len(), 2);
+        assert!(!series.attendee("carol@example.com").unwrap().is_required());
+
+        let mut cal = EventCalendar::default();
+        cal.add_event(series);
+        cal.add_event(lunch);
+
+        let start = NaiveDateTime::new(ymd(2023, 1, 1), first_time_nt());
+        let end = NaiveDateTime::new(ymd(2023, 2, 1), first_time_nt());
+        let attending = |cal: &EventCalendar, who| {
+            cal.events_attended_by(who, start, end)
+                .map(|occ| (occ.name().to_string(), occ.start().day()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(attending(&cal, "BOB@example.com").len(), 5);
+        assert_eq!(attending(&cal, "dave@example.com"), vec![]);
+
+        // bob declines the series but accepts the third occurrence
+        cal.respond(series_id, "bob@example.com", PartStat::Declined, at(1))
+            .unwrap();
+        let third = NaiveDateTime::new(ymd(2023, 1, 16), nine);
+        cal.respond_to_occurrence(
+            series_id,
+            third,
+            "bob@example.com",
+            PartStat::Accepted,
+            at(2),
+        )
+        .unwrap();
+        assert_eq!(
+            attending(&cal, "bob@example.com"),
+            vec![("Lunch".to_string(), 3), ("Sync".to_string(), 16)]
+        );
+        let replied = cal.get_override(series_id, third).unwrap();
+        let replied = replied.attendee("bob@example.com").unwrap();
+        assert_eq!(replied.status(), PartStat::Accepted);
+        assert_eq!(replied.replied_at(), Some(at(2)));
+        assert!(replied.rsvp());
+        assert_eq!(attending(&cal, "carol@example.com").len(), 4);
+
+        assert!(matches!(
+            cal.respond(lunch_id, "dave@example.com", PartStat::Accepted, at(3)),
+            Err(EventError::NotAnAttendee(id, _)) if id == lunch_id
+        ));
+
+        // attendees survive JSON, and iCalendar apart from the reply time
+        let json = serde_json::to_string(&cal).unwrap();
+        let back: EventCalendar = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.get(series_id), cal.get(series_id));
+
+        let back = EventCalendar::from_ics(&cal.to_ics()).unwrap();
+        let sync = back.get(series_id).unwrap();
+        let bob = sync.attendee("bob@example.com").unwrap();
+        assert_eq!((bob.status(), bob.replied_at()), (PartStat::Declined, None));
+        assert_eq!(
+            sync.attendee("carol@example.com").unwrap().name(),
+            Some("Smith, Carol")
+        );
+        assert_eq!(
+            sync.attendee("carol@example.com").unwrap().role(),
+            Role::Optional
+        );
+        assert_eq!(
+            attending(&back, "bob@example.com"),
+            attending(&cal, "bob@example.com")
+        );
+    }
+
+    #[test]
+    fn test_free_busy() {
+        use chrono::{TimeZone, Utc};
+        use chrono_tz::{America::New_York, Europe::Paris};
+
+        let day = ymd(2023, 1, 2);
+        let at = |d: u32, h: u32, m: u32| {
+            NaiveDateTime::new(ymd(2023, 1, d), NaiveTime::from_hms_opt(h, m, 0).unwrap())
+        };
+        let meeting = |name: &str, start, end| {
+            Event::new(name.into(), &day)
+                .set_end(end)
+                .and_then(|e| e.set_start(start))
+                .unwrap()
+        };
+
+        // a daily 09:00-10:00 standup, cancelled on the 3rd
+        let standup = meeting("Standup", at(2, 9, 0), at(2, 10, 0))
+            .set_recurrence(Some("FREQ=DAILY;COUNT=3".parse().unwrap()));
+        let standup_id = *standup.id();
+        let mut cal = EventCalendar::default();
+        cal.add_event(standup);
+        cal.override_occurrence(standup_id, at(3, 9, 0), |e| {
+            Ok(e.set_status(Some(Status::Cancelled)))
+        })
+        .unwrap();
+        cal.add_event(meeting("Review", at(2, 9, 30), at(2, 11, 0)));
+        cal.add_event(meeting("Lunch", at(2, 11, 0), at(2, 12, 0)));
+        cal.add_event(
+            meeting("Focus", at(2, 14, 0), at(3, 18, 0))
+                .set_transparency(Transparency::Transparent),
+        );
+
+        let fb = cal.free_busy(at(2, 8, 0), at(4, 9, 30));
+        let spans = |periods: &[Period]| {
+            periods
+                .iter()
+                .map(|p| (p.start(), p.end()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            spans(fb.busy()),
+            vec![(at(2, 9, 0), at(2, 12, 0)), (at(4, 9, 0), at(4, 9, 30))]
+        );
+        assert_eq!(
+            spans(fb.free()),
+            vec![(at(2, 8, 0), at(2, 9, 0)), (at(2, 12, 0), at(4, 9, 0))]
+        );
+        assert!(fb.is_free(at(3, 9, 0), at(3, 10, 0)));
+        assert!(!fb.is_free(at(2, 11, 59), at(2, 13, 0)));
+
+        // busy times are clipped to the window
+        let fb = cal.free_busy(at(2, 10, 0), at(2, 10, 30));
+        assert_eq!(spans(fb.busy()), vec![(at(2, 10, 0), at(2, 10, 30))]);
+        assert!(fb.free().is_empty());
+        assert!(cal.free_busy(at(5, 0, 0), at(4, 0, 0)).busy().is_empty());
+
+        // in real time, with a Paris event added, reported in UTC
+        cal.add_event(meeting("Call", at(2, 15, 0), at(2, 16, 0)).set_tz(Paris.into()));
+        let start = New_York.with_ymd_and_hms(2023, 1, 2, 8, 0, 0).unwrap();
+        let end = New_York.with_ymd_and_hms(2023, 1, 2, 12, 0, 0).unwrap();
+        let fb = cal
+            .free_busy_tz(&start, &end, Disambiguation::Reject)
+            .unwrap();
+        assert_eq!(fb.tz(), EventTz::Utc);
+        // 09:00-12:00 New York and 15:00-16:00 Paris are 14:00-17:00 UTC
+        assert_eq!(spans(fb.busy()), vec![(at(2, 14, 0), at(2, 17, 0))]);
+
+        let ics = fb
+            .to_ics_with_stamp(Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap())
+            .unwrap();
+        assert!(ics.contains("BEGIN:VFREEBUSY\r\n"));
+        assert!(ics.contains("DTSTART:20230102T130000Z\r\nDTEND:20230102T170000Z\r\n"));
+        assert!(ics.contains("FREEBUSY;FBTYPE=BUSY:20230102T140000Z/20230102T170000Z\r\n"));
+        assert_eq!(
+            ics,
+            fb.to_ics_with_stamp(Utc.with_ymd_and_hms(2023, 1, 1, 0, 0, 0).unwrap())
+                .unwrap()
+        );
+        // floating time can't be written, RFC 5545 wants UTC
+        assert!(matches!(
+            cal.free_busy(at(2, 8, 0), at(2, 17, 0)).to_ics(),
+            Err(EventError::InvalidProperty(..))
+        ));
+    }
+
+    #[test]
+    fn test_find_slots() {
+        use chrono::{Duration, TimeZone, Utc, Weekday};
+        use chrono_tz::{America::New_York, Europe::Paris};
+
+        let hour = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        // Friday the 6th to Monday the 9th of January 2023
+        let at = |d, h, m| NaiveDateTime::new(ymd(2023, 1, d), hour(h, m));
+        let meeting = |name: &str, start, end| {
+            Event::new(name.into(), &ymd(2023, 1, 6))
+                .set_end(end)
+                .and_then(|e| e.set_start(start))
+                .unwrap()
+        };
+
+        let mut alice = EventCalendar::default();
+        alice.add_event(
+            meeting("Standup", at(6, 9, 0), at(6, 9, 30))
+                .set_recurrence(Some("FREQ=DAILY".parse().unwrap())),
+        );
+        alice.add_event(meeting("Review", at(6, 10, 0), at(6, 12, 0)));
+        let mut bob = EventCalendar::default();
+        bob.add_event(meeting("Lunch", at(6, 12, 0), at(6, 13, 0)));
+        bob.add_event(meeting("Offsite", at(6, 14, 0), at(6, 17, 0)));
+        bob.add_event(
+            meeting("Reminder", at(6, 13, 0), at(6, 14, 0))
+                .set_transparency(Transparency::Transparent),
+        );
+        let mut carol = EventCalendar::default();
+        let calendars = [&alice, &bob, &carol];
+        let find = |query: &SlotQuery, calendars: [&EventCalendar; 3], start, end| {
+            let (start, end) = (Utc.from_utc_datetime(&start), Utc.from_utc_datetime(&end));
+            query
+                .find(calendars, &start, &end, Disambiguation::Reject)
+                .unwrap()
+        };
+
+        let starts = |slots: Vec<Period>| slots.iter().map(|s| s.start()).collect::<Vec<_>>();
+        let query = SlotQuery::new(Duration::hours(1))
+            .unwrap()
+            .set_working_hours(hour(9, 0), hour(17, 0))
+            .unwrap()
+            .set_working_days(&[
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ])
+            .set_count(3);
+        let window = (at(6, 0, 0), at(10, 0, 0));
+
+        // the transparent reminder does not block 13:00, the weekend is skipped
+        assert_eq!(
+            starts(find(&query, calendars, window.0, window.1)),
+            vec![at(6, 13, 0), at(9, 9, 30), at(9, 9, 45)]
+        );
+
+        // 15 minutes either side of every event, on the half hour
+        let buffered = query
+            .clone()
+            .set_buffers(Duration::minutes(15), Duration::minutes(15))
+            .set_step(Duration::minutes(30))
+            .unwrap();
+        assert_eq!(
+            starts(find(&buffered, calendars, window.0, window.1)),
+            vec![at(9, 10, 0), at(9, 10, 30), at(9, 11, 0)]
+        );
+
+        // afternoons first, then the closest to them
+        let preferred = query
+            .clone()
+            .set_preferred(hour(14, 0), hour(17, 0))
+            .unwrap()
+            .set_count(2);
+        assert_eq!(
+            starts(find(&preferred, calendars, window.0, window.1)),
+            vec![at(9, 14, 0), at(9, 14, 15)]
+        );
+
+        // a slot must fit entirely in the window
+        assert!(find(&query, calendars, at(6, 13, 0), at(6, 13, 59)).is_empty());
+        assert!(matches!(
+            SlotQuery::new(Duration::zero()),
+            Err(EventError::InvalidProperty(..))
+        ));
+        assert!(matches!(
+            query.clone().set_step(Duration::minutes(-15)),
+            Err(EventError::InvalidProperty(..))
+        ));
+        assert!(matches!(
+            query.clone().set_step(Duration::milliseconds(500)),
+            Err(EventError::InvalidProperty(..))
+        ));
+        assert!(query
+            .clone()
+            .set_working_hours(hour(17, 0), hour(9, 0))
+            .is_err());
+
+        // buffers reaching past the times chrono can represent
+        let buffered = query
+            .clone()
+            .set_buffers(Duration::hours(1), Duration::hours(1));
+        let (min, max) = (
+            chrono::DateTime::<Utc>::MIN_UTC,
+            chrono::DateTime::<Utc>::MAX_UTC,
+        );
+        assert!(matches!(
+            buffered.find(calendars, &min, &max, Disambiguation::Reject),
+            Err(EventError::InvalidProperty(..))
+        ));
+
+        // every calendar is read in the zone of the query, 15:00 in Paris
+        // is 09:00 in New York and floating events are New York time
+        carol.add_event(meeting("Call", at(9, 15, 0), at(9, 16, 0)).set_tz(Paris.into()));
+        let start = New_York.with_ymd_and_hms(2023, 1, 9, 0, 0, 0).unwrap();
+        let end = New_York.with_ymd_and_hms(2023, 1, 10, 0, 0, 0).unwrap();
+        let slots = query
+            .set_count(1)
+            .find([&alice, &bob, &carol], &start, &end, Disambiguation::Reject)
+            .unwrap();
+        assert_eq!(starts(slots), vec![at(9, 10, 0)]);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let at =
+            |d, h| NaiveDateTime::new(ymd(2023, 1, d), NaiveTime::from_hms_opt(h, 0, 0).unwrap());
+        let meeting = |name: &str, start, end| {
+            Event::new(name.into(), &ymd(2023, 1, 2))
+                .set_end(end)
+                .and_then(|e| e.set_start(start))
+                .unwrap()
+        };
+
+        let standup = meeting("Standup", at(2, 9), at(2, 10))
+            .set_recurrence(Some("FREQ=DAILY;COUNT=5".parse().unwrap()));
+        let standup_id = *standup.id();
+        let review = meeting("Review", at(3, 13), at(3, 15));
+        let review_id = *review.id();
+        let mut cal = EventCalendar::default();
+        cal.add_event(standup);
+        cal.add_event(review);
+        cal.add_event(
+            meeting("Reminder", at(4, 9), at(4, 10)).set_transparency(Transparency::Transparent),
+        );
+
+        // touching is not overlapping
+        assert!(cal
+            .conflicts(&meeting("A", at(2, 10), at(2, 11)))
+            .is_empty());
+        let found: Vec<_> = cal
+            .conflicts(&meeting("B", at(3, 9), at(3, 14)))
+            .iter()
+            .map(|occ| (*occ.event().id(), occ.start()))
+            .collect();
+        assert_eq!(found, vec![(standup_id, at(3, 9)), (review_id, at(3, 13))]);
+
+        // every occurrence of a recurring candidate is checked
+        let weekly =
+            meeting("C", at(2, 8), at(2, 11)).set_recurrence(Some("FREQ=WEEKLY".parse().unwrap()));
+        assert_eq!(cal.conflicts(&weekly).len(), 1);
+        let daily =
+            meeting("D", at(2, 8), at(2, 11)).set_recurrence(Some("FREQ=DAILY".parse().unwrap()));
+        assert_eq!(cal.conflicts(&daily).len(), 5);
+        // cancelled candidates and occurrences never conflict
+        assert!(cal
+            .conflicts(&meeting("E", at(2, 9), at(2, 10)).set_status(Some(Status::Cancelled)))
+            .is_empty());
+        cal.override_occurrence(standup_id, at(5, 9), |e| {
+            Ok(e.set_status(Some(Status::Cancelled)))
+        })
+        .unwrap();
+        assert_eq!(cal.conflicts(&daily).len(), 4);
+        // nor does a series with its own occurrences
+        let moved = cal.get(standup_id).unwrap().occurrence_at(at(6, 9));
+        assert!(cal.conflicts(&moved).is_empty());
+
+        // allow
+        assert_eq!(cal.policy(), OverlapPolicy::Allow);
+        assert_eq!(
+            cal.try_add_event(meeting("F", at(3, 14), at(3, 16)))
+                .unwrap(),
+            Vec::<Uuid>::new()
+        );
+
+        // warn
+        cal.set_policy(OverlapPolicy::Warn);
+        let g = meeting("G", at(6, 9), at(6, 12));
+        let g_id = *g.id();
+        assert_eq!(cal.try_add_event(g).unwrap(), vec![standup_id]);
+        assert!(cal.get(g_id).is_some());
+
+        // reject
+        cal.set_policy(OverlapPolicy::Reject);
+        let h = meeting("H", at(3, 9), at(3, 14));
+        let h_id = *h.id();
+        match cal.try_add_event(h.clone()) {
+            Err(EventError::Conflict(ids)) => {
+                let mut expected = vec![standup_id, review_id];
+                expected.sort();
+                assert_eq!(ids, expected);
+            }
+            other => panic!("expected a conflict, got {other:?}"),
+        }
+        assert!(!cal.add_event(h));
+        assert!(cal.get(h_id).is_none());
+        let i = meeting("I", at(7, 9), at(7, 10));
+        let i_id = *i.id();
+        assert!(cal.add_event(i));
+
+        // edits are held to the policy too, but never conflict with
+        // the event they change
+        let mut expected = vec![standup_id, g_id];
+        expected.sort();
+        assert!(matches!(
+            cal.update(i_id, |e| e.set_start(at(6, 9))),
+            Err(EventError::Conflict(ids)) if ids == expected
+        ));
+        assert_eq!(cal.get(i_id).unwrap().start(), at(7, 9));
+        cal.update(i_id, |mut e| {
+            e.set_name("Renamed".into());
+            Ok(e)
+        })
+        .unwrap();
+        assert!(matches!(
+            cal.override_occurrence(standup_id, at(2, 9), |e| {
+                e.set_end(at(7, 10))?.set_start(at(7, 9))
+            }),
+            Err(EventError::Conflict(ids)) if ids == vec![i_id]
+        ));
+        assert!(cal.get_override(standup_id, at(2, 9)).is_none());
+
+        // a rejected series is imported without its overrides
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   BEGIN:VEVENT\r\n\
+                   UID:weekly@example.com\r\n\
+                   DTSTART:20230102T090000\r\n\
+                   DURATION:PT1H\r\n\
+                   RRULE:FREQ=WEEKLY;COUNT=2\r\n\
+                   END:VEVENT\r\n\
+                   BEGIN:VEVENT\r\n\
+                   UID:weekly@example.com\r\n\
+                   RECURRENCE-ID:20230109T090000\r\n\
+                   DTSTART:20230301T090000\r\n\
+                   DURATION:PT1H\r\n\
+                   END:VEVENT\r\n\
+                   END:VCALENDAR\r\n";
+        let count = cal.events().count();
+        assert_eq!(cal.import_ics(ics).unwrap(), 0);
+        assert_eq!(cal.events().count(), count);
+        assert_eq!(
+            cal.events_in_range(at(28, 0), at(28, 0) + chrono::Duration::days(60))
+                .count(),
+            0
+        );
+
+        // the policy is kept when the calendar is saved
+        let back: EventCalendar =
+            serde_json::from_str(&serde_json::to_string(&cal).unwrap()).unwrap();
+        assert_eq!(back.policy(), OverlapPolicy::Reject);
+        assert_eq!(back.events().count(), cal.events().count());
+
+        // a conflict long after the series starts is still found
+        let mut cal = EventCalendar::default();
+        cal.set_policy(OverlapPolicy::Reject);
+        let later = Event::new("Later".into(), &ymd(2026, 6, 1));
+        let later_id = *later.id();
+        cal.add_event(later);
+        let daily = Event::new("Daily".into(), &ymd(2022, 1, 1))
+            .set_recurrence(Some("FREQ=DAILY".parse().unwrap()));
+        assert!(matches!(
+            cal.try_add_event(daily),
+            Err(EventError::Conflict(ids)) if ids == vec![later_id]
+        ));
+
+        // edits that only drop occurrences of a series that already
+        // overlapped are not rejected, and keep its overrides
+        let mut cal = EventCalendar::default();
+        let series = Event::new("Series".into(), &ymd(2023, 1, 2))
+            .set_recurrence(Some("FREQ=DAILY;COUNT=6".parse().unwrap()));
+        let series_id = *series.id();
+        let first = series.start();
+        cal.add_event(series);
+        cal.add_event(Event::new("Clash".into(), &ymd(2023, 1, 2)));
+        for day in [3, 6] {
+            cal.override_occurrence(
+                series_id,
+                first + chrono::Duration::days(day - 2),
+                |mut e| {
+                    e.set_name(format!("Override {day}"));
+                    Ok(e)
+                },
+            )
+            .unwrap();
+        }
+        cal.set_policy(OverlapPolicy::Reject);
+        cal.cancel_occurrence(series_id, first + chrono::Duration::days(1))
+            .unwrap();
+        assert_eq!(cal.overrides(series_id).count(), 1);
+        let tail = cal
+            .split_series(series_id, first + chrono::Duration::days(2), Ok)
+            .unwrap();
+        assert_eq!(cal.overrides(series_id).count(), 0);
+        assert_eq!(cal.overrides(*tail.id()).count(), 1);
+    }
+
+    #[test]
+    fn test_calendar_json() {
+        let cal = EventCalendar::from_ics(SAMPLE_ICS).unwrap();
+        let json = serde_json::to_string(&cal).unwrap();
+        let back: EventCalendar = serde_json::from_str(&json).unwrap();
+
+        assert!(back.events().eq(cal.events()));
+        for evt in cal.events() {
+            assert!(back.overrides(evt.id()).eq(cal.overrides(evt.id())));
+        }
+        let start = NaiveDateTime::new(ymd(2023, 1, 1), first_time_nt());
+        let end = NaiveDateTime::new(ymd(2023, 2, 1), first_time_nt());
+        assert!(back
+            .events_in_range(start, end)
+            .eq(cal.events_in_range(start, end)));
+
+        // a single bad event fails the whole calendar
+        let bad = json.replacen("\"end\":\"2023-01-06\"", "\"end\":\"2023-01-04\"", 1);
+        assert_ne!(bad, json);
+        assert!(serde_json::from_str::<EventCalendar>(&bad).is_err());
+    }
+
+    #[test]
+    fn test_calendar_remove() {
+        let nd = first_day_2023_nd();
+        let e1 = Event::new("A".into(), &nd);
+        let e1_id = *e1.id();
+        let e2 = Event::new("B".into(), &nd.with_day(2).unwrap());
+
+        let mut cal = EventCalendar::default();
+        cal.add_event(e1);
+        cal.add_event(e2);
+
+        let removed = cal.remove(e1_id).unwrap();
+        assert_eq!(removed.name(), "A");
+        assert_eq!(cal.get(e1_id), None);
+        assert_eq!(cal.first_event().unwrap().name(), "B");
+
+        // removing twice does nothing
+        assert!(cal.remove(e1_id).is_none());
+    }
+
+    #[test]
+    fn test_untrusted_ids() {
+        let mut cal = EventCalendar::default();
+        let e = Event::new("A".into(), &first_day_2023_nd());
+        let id = *e.id();
+        cal.add_event(e);
+
+        let forms = [
+            id.hyphenated().to_string(),
+            id.simple().to_string(),
+            id.urn().to_string(),
+            id.braced().to_string(),
+        ];
+        for form in &forms {
+            assert_eq!(cal.try_get(form).unwrap().id(), &id);
+            assert_eq!(form.as_bytes().try_into_uuid().unwrap(), id);
+        }
+        assert_eq!(id.into_bytes().try_into_uuid().unwrap(), id);
+
+        // bad input is an error, never a panic
+        for bad in ["", "garbage", "67e55044-10b1-426f-9247"] {
+            assert!(matches!(cal.try_get(bad), Err(EventError::InvalidUuid(..))));
+            assert!(matches!(
+                cal.update(bad, Ok),
+                Err(EventError::InvalidUuid(..))
+            ));
+            assert!(matches!(
+                bad.as_bytes().try_into_uuid(),
+                Err(EventError::InvalidUuid(..))
+            ));
+        }
+        assert!(matches!(
+            cal.try_remove(Uuid::nil()),
+            Err(EventError::NotFound(_))
+        ));
+
+        assert_eq!(cal.try_remove(forms[1].clone()).unwrap().id(), &id);
+        assert!(cal.get(id).is_none());
+    }
+
+    #[test]
+    fn test_calendar_update_and_replace() {
+        let nd = first_day_2023_nd();
+        let e1 = Event::new("A".into(), &nd);
+        let e1_id = *e1.id();
+        let e2 = Event::new("B".into(), &nd.with_day(2).unwrap());
+        let e2_id = *e2.id();
+
+        let mut cal = EventCalendar::default();
+        cal.add_event(e1);
+        cal.add_event(e2);
+
+        // move A after B, the ordered set must follow
+        let old = cal
+            .update(e1_id, |evt| {
+                evt.set_end_date(nd.with_day(3).unwrap())?
+                    .set_start_date(nd.with_day(3).unwrap())
+            })
+            .unwrap();
+        assert_eq!(old.start().date(), nd);
+        assert_eq!(
+            cal.get(e1_id).unwrap().start().date(),
+            nd.with_day(3).unwrap()
+        );
+        assert_eq!(cal.first_event().unwrap().name(), "B");
+
+        // a failing edit leaves the calendar untouched
+        assert!(cal
+            .update(e1_id, |evt| evt.set_end_time(first_time_nt()))
+            .is_err());
+        assert_eq!(
+            cal.get(e1_id).unwrap().start().date(),
+            nd.with_day(3).unwrap()
+        );
+
+        // replacing keeps both indexes consistent
+        let e3 = Event::new("C".into(), &nd.with_day(10).unwrap());
+        let e3_id = *e3.id();
+        let old = cal.replace(e1_id, e3).unwrap();
+        assert_eq!(old.name(), "A");
+        assert_eq!(cal.get(e1_id), None);
+        assert_eq!(cal.get(e3_id).unwrap().name(), "C");
+
+        // an event can't be replaced by another one already in the calendar
+        let mut b = Event::clone(cal.get(e2_id).unwrap());
+        b.set_name("B2".into());
+        assert!(matches!(
+            cal.replace(e3_id, b),
+            Err(EventError::DuplicateId(id)) if id == e2_id
+        ));
+        assert_eq!(cal.get(e2_id).unwrap().name(), "B");
+        assert_eq!(cal.get(e3_id).unwrap().name(), "C");
+
+        let missing = Uuid::new_v4();
+        assert!(matches!(
+            cal.update(missing, Ok),
+            Err(EventError::NotFound(id)) if id == missing
+        ));
+    }
+
+    #[test]
+    fn test_event_range_overlap() {
+        let nd = first_day_2023_nd();
+        let at = |day: u32, hour: u32| {
+            NaiveDateTime::new(
+                nd.with_day(day).unwrap(),
+                NaiveTime::from_hms_opt(hour, 0, 0).unwrap(),
+            )
+        };
+
+        // multi-day event spanning the whole query window
+        let spanning = Event::new("Conference".into(), &nd)
+            .set_end(at(10, 0))
+            .unwrap();
+        let spanning_id = *spanning.id();
+
+        // ends exactly when the window starts
+        let before = Event::new("Before".into(), &nd.with_day(4).unwrap())
+            .set_end(at(5, 0))
+            .unwrap();
+
+        // starts exactly when the window ends
+        let after = Event::new("After".into(), &nd.with_day(6).unwrap());
+
+        let mut cal = EventCalendar::default();
+        cal.add_event(spanning);
+        cal.add_event(before);
+        cal.add_event(after);
+
+        let names = |iter: &mut dyn Iterator<Item = Occurrence>| {
+            iter.map(|occ| occ.name().to_string()).collect::<Vec<_>>()
+        };
+
+        let mut iter = cal
+            .events_in_range(at(5, 0), at(6, 0))
+            .map(|occ| occ.event());
+        assert_eq!(iter.next(), cal.get(spanning_id));
+        assert_eq!(iter.next(), None);
+
+        // touching at the end of the range
+        assert_eq!(
+            names(&mut cal.events_in_range(at(5, 0), at(6, 1))),
+            vec!["Conference", "After"]
+        );
+
+        // touching at the start of the range
+        assert_eq!(
+            names(&mut cal.events_in_range(at(4, 23), at(5, 0))),
+            vec!["Conference", "Before"]
+        );
+
+        // an event contains its start but not its end
+        assert_eq!(
+            names(&mut cal.events_containing(at(5, 0))),
+            vec!["Conference"]
+        );
+        assert_eq!(
+            names(&mut cal.events_containing(at(4, 0))),
+            vec!["Conference", "Before"]
+        );
+
+        // the spanning event is not fully within the window
+        assert_eq!(
+            names(&mut cal.events_fully_within(at(4, 0), at(7, 0))),
+            vec!["Before", "After"]
+        );
+        assert_eq!(
+            names(&mut cal.events_fully_within(at(1, 0), at(10, 0))),
+            vec!["Conference", "Before", "After"]
+        );
+    }
+
+    #[test]
+    fn test_interval_index_matches_scan() {
+        // small deterministic LCG so the test does not need a rand dependency
+        let mut seed = 0x2545_f491_u64;
+        let mut rand = move |bound: i64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            ((seed >> 33) % bound as u64) as i64
+        };
+
+        let base = first_day_2023_ndt();
+        let mut cal = EventCalendar::default();
+        let mut events = Vec::new();
+
+        for i in 0..500 {
+            let start = base + chrono::Duration::minutes(rand(60 * 24 * 30));
+            let end = start + chrono::Duration::minutes(1 + rand(60 * 24 * 3));
+            let evt = Event::new(i.to_string(), &start.date())
+                .set_end(end)
+                .unwrap()
+                .set_start(start)
+                .unwrap();
+            events.push(evt.clone());
+            cal.add_event(evt);
+        }
+
+        // remove every third event to exercise rebalancing on removal
+        for evt in events.iter().step_by(3) {
+            cal.remove(evt.id());
+        }
+        let events: Vec<_> = events
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, evt)| (i % 3 != 0).then_some(evt))
+            .collect();
+
+        for _ in 0..200 {
+            let start = base + chrono::Duration::minutes(rand(60 * 24 * 35));
+            let end = start + chrono::Duration::minutes(rand(60 * 24 * 2));
+
+            let mut expected: Vec<_> = events.iter().filter(|e| e.overlaps(start, end)).collect();
+            expected.sort();
+            let found: Vec<_> = cal
+                .events_in_range(start, end)
+                .map(|occ| &**occ.event())
+                .collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    /// start of each occurrence of `rule` for a series starting at `dtstart`
+    fn expand(rule: &str, dtstart: NaiveDateTime, limit: usize) -> Vec<NaiveDateTime> {
+        rule.parse::<RRule>()
+            .unwrap()
+            .occurrences(dtstart)
+            .take(limit)
+            .collect()
+    }
+
+    #[test]
+    fn test_shared_calendar_threads() {
+        use std::thread;
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<EventCalendar>();
+        assert_send_sync::<SharedCalendar>();
+
+        let cal = SharedCalendar::default();
+        let start = first_day_2023_ndt();
+        let end = NaiveDateTime::new(ymd(2024, 1, 1), first_time_nt());
+
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let cal = cal.clone();
+                thread::spawn(move || {
+                    let ids: Vec<_> = (0..100)
+                        .map(|i| {
+                            let day = first_day_2023_nd() + chrono::Duration::days(i);
+                            let e = Event::new(format!("{t}-{i}"), &day);
+                            let id = *e.id();
+                            assert!(cal.add_event(e));
+                            id
+                        })
+                        .collect();
+                    // remove a quarter of them and rename another quarter
+                    for (i, id) in ids.iter().enumerate() {
+                        match i % 4 {
+                            0 => assert!(cal.remove(id).is_some()),
+                            1 => {
+                                cal.update(id, |mut e| {
+                                    e.set_name("renamed".into());
+                                    Ok(e)
+                                })
+                                .unwrap();
+                            }
+                            _ => assert_eq!(cal.get(id).unwrap().id(), id),
+                        }
+                    }
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let cal = cal.clone();
+                thread::spawn(move || {
+                    for _ in 0..200 {
+                        // a reader never sees the index and the event list
+                        // disagree, whatever the writers are doing
+                        let guard = cal.read();
+                        assert_eq!(
+                            guard.events_in_range(start, end).count(),
+                            guard.events().count()
+                        );
+                        drop(guard);
+                        assert!(cal.events_in_range(start, end).len() <= 400);
+                    }
+                })
+            })
+            .collect();
+        for handle in writers.into_iter().chain(readers) {
+            handle.join().unwrap();
+        }
+
+        let found = cal.events_in_range(start, end);
+        assert_eq!(found.len(), 300);
+        assert_eq!(found.iter().filter(|e| e.name() == "renamed").count(), 100);
+
+        // a writer that panics leaves the calendar as it was
+        let panicking = cal.clone();
+        let joined = thread::spawn(move || {
+            panicking.edit::<(), _>(|cal| {
+                cal.add_event(Event::new("lost".into(), &first_day_2023_nd()));
+                cal.try_remove(*found[0].id())?;
+                panic!("writer failed")
+            })
+        })
+        .join();
+        assert!(joined.is_err());
+        assert_eq!(cal.events_in_range(start, end).len(), 300);
+        assert!(cal.read().events().all(|evt| evt.name() != "lost"));
+        cal.edit(|cal| Ok(cal.add_event(Event::new("kept".into(), &first_day_2023_nd()))))
+            .unwrap();
+        assert_eq!(cal.events_in_range(start, end).len(), 301);
+    }
+
+    fn ymd(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_rrule_expansion() {
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let dates = |v: Vec<NaiveDateTime>| v.into_iter().map(|dt| dt.date()).collect::<Vec<_>>();
+
+        // RFC 5545 examples, all at 09:00
+        let tu_th = expand(
+            "FREQ=WEEKLY;UNTIL=19971007T000000Z;WKST=SU;BYDAY=TU,TH",
+            NaiveDateTime::new(ymd(1997, 9, 2), nine),
+            100,
+        );
+        assert!(tu_th.iter().all(|dt| dt.time() == nine));
+        assert_eq!(
+            dates(tu_th),
+            [2, 4, 9, 11, 16, 18, 23, 25, 30]
+                .into_iter()
+                .map(|d| ymd(1997, 9, d))
+                .chain([ymd(1997, 10, 2)])
+                .collect::<Vec<_>>()
+        );
+
+        let first_friday = expand(
+            "FREQ=MONTHLY;COUNT=10;BYDAY=1FR",
+            NaiveDateTime::new(ymd(1997, 9, 5), nine),
+            100,
+        );
+        assert_eq!(
+            dates(first_friday),
+            vec![
+                ymd(1997, 9, 5),
+                ymd(1997, 10, 3),
+                ymd(1997, 11, 7),
+                ymd(1997, 12, 5),
+                ymd(1998, 1, 2),
+                ymd(1998, 2, 6),
+                ymd(1998, 3, 6),
+                ymd(1998, 4, 3),
+                ymd(1998, 5, 1),
+                ymd(1998, 6, 5),
+            ]
+        );
+
+        let last_workday = expand(
+            "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1",
+            NaiveDateTime::new(ymd(1997, 9, 30), nine),
+            4,
+        );
+        assert_eq!(
+            dates(last_workday),
+            vec![
+                ymd(1997, 9, 30),
+                ymd(1997, 10, 31),
+                ymd(1997, 11, 28),
+                ymd(1997, 12, 31)
+            ]
+        );
+
+        let every_other_week = expand(
+            "FREQ=WEEKLY;INTERVAL=2;UNTIL=19971224T000000Z;WKST=SU;BYDAY=MO,WE,FR",
+            NaiveDateTime::new(ymd(1997, 9, 1), nine),
+            100,
+        );
+        assert_eq!(every_other_week.len(), 25);
+        assert_eq!(every_other_week.last().unwrap().date(), ymd(1997, 12, 22));
+
+        let summer = expand(
+            "FREQ=YEARLY;COUNT=10;BYMONTH=6,7",
+            NaiveDateTime::new(ymd(1997, 6, 10), nine),
+            100,
+        );
+        assert_eq!(summer.len(), 10);
+        assert_eq!(summer[1].date(), ymd(1997, 7, 10));
+        assert_eq!(summer[9].date(), ymd(2001, 7, 10));
+
+        // months without a 31st are skipped rather than clamped
+        let month_end = expand(
+            "FREQ=MONTHLY;BYMONTHDAY=31;COUNT=3",
+            NaiveDateTime::new(ymd(2023, 1, 31), nine),
+            100,
+        );
+        assert_eq!(
+            dates(month_end),
+            vec![ymd(2023, 1, 31), ymd(2023, 3, 31), ymd(2023, 5, 31)]
+        );
+
+        // a start that does not match the rule is still the first occurrence
+        let thursdays = expand(
+            "FREQ=WEEKLY;COUNT=3;BYDAY=TH",
+            NaiveDateTime::new(ymd(2023, 1, 2), nine),
+            100,
+        );
+        assert_eq!(
+            dates(thursdays),
+            vec![ymd(2023, 1, 2), ymd(2023, 1, 5), ymd(2023, 1, 12)]
+        );
+
+        // months of empty periods are skipped rather than ending the rule
+        let new_year = expand(
+            "FREQ=MINUTELY;INTERVAL=30;BYMONTH=1;BYMONTHDAY=1",
+            NaiveDateTime::new(ymd(2023, 2, 1), nine),
+            3,
+        );
+        assert_eq!(
+            new_year,
+            vec![
+                NaiveDateTime::new(ymd(2023, 2, 1), nine),
+                NaiveDateTime::new(ymd(2024, 1, 1), NaiveTime::from_hms_opt(0, 0, 0).unwrap()),
+                NaiveDateTime::new(ymd(2024, 1, 1), NaiveTime::from_hms_opt(0, 30, 0).unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rrule_week_start() {
+        let start = NaiveDateTime::new(ymd(1997, 8, 5), NaiveTime::from_hms_opt(9, 0, 0).unwrap());
+        let days = |rule| {
+            expand(rule, start, 100)
+                .into_iter()
+                .map(|dt| dt.day())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            days("FREQ=WEEKLY;INTERVAL=2;COUNT=4;BYDAY=TU,SU;WKST=MO"),
+            vec![5, 10, 19, 24]
+        );
+        assert_eq!(
+            days("FREQ=WEEKLY;INTERVAL=2;COUNT=4;BYDAY=TU,SU;WKST=SU"),
+            vec![5, 17, 19, 31]
+        );
+    }
+
+    #[test]
+    fn test_rrule_invalid() {
+        assert!("FREQ=DAILY;COUNT=2;UNTIL=20230101"
+            .parse::<RRule>()
+            .is_err());
+        assert!("FREQ=YEARLY;BYMONTH=13".parse::<RRule>().is_err());
+        assert!("FREQ=FORTNIGHTLY".parse::<RRule>().is_err());
+        assert!("INTERVAL=2".parse::<RRule>().is_err());
+        assert!("FREQ=WEEKLY;BYDAY=XX".parse::<RRule>().is_err());
+
+        // a rule that can never match ends after DTSTART instead of looping
+        // forever
+        assert_eq!(
+            expand(
+                "FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30",
+                first_day_2023_ndt(),
+                2
+            ),
+            vec![first_day_2023_ndt()]
+        );
+    }
+
+    #[test]
+    fn test_recurring_events_in_range() {
+        let monday = ymd(2023, 1, 2);
+        let standup = Event::new("Standup".into(), &monday)
+            .set_end_time(NaiveTime::from_hms_opt(9, 15, 0).unwrap())
+            .unwrap()
+            .set_start_time(NaiveTime::from_hms_opt(9, 0, 0).unwrap())
+            .unwrap()
+            .set_recurrence(Some("FREQ=WEEKLY;BYDAY=MO,TH".parse().unwrap()));
+        let party = Event::new("Party".into(), &ymd(2023, 3, 9));
+
+        let mut cal = EventCalendar::default();
+        cal.add_event(standup);
+        cal.add_event(party);
+
+        // two months after the series started
+        let start = NaiveDateTime::new(ymd(2023, 3, 6), first_time_nt());
+        let end = NaiveDateTime::new(ymd(2023, 3, 13), first_time_nt());
+        let found: Vec<_> = cal
+            .events_in_range(start, end)
+            .map(|occ| (occ.name(), occ.start().date(), occ.end() - occ.start()))
+            .collect();
+
+        let quarter_hour = chrono::Duration::minutes(15);
+        assert_eq!(
+            found,
+            vec![
+                ("Standup", ymd(2023, 3, 6), quarter_hour),
+                ("Party", ymd(2023, 3, 9), chrono::Duration::seconds(86399)),
+                ("Standup", ymd(2023, 3, 9), quarter_hour),
+            ]
+        );
+
+        let at = NaiveDateTime::new(ymd(2023, 3, 9), NaiveTime::from_hms_opt(9, 10, 0).unwrap());
+        assert_eq!(cal.events_containing(at).count(), 2);
+        assert_eq!(cal.events_containing(at + quarter_hour).count(), 1);
+
+        // far into the series
+        let start = NaiveDateTime::new(ymd(2323, 3, 5), first_time_nt());
+        let found: Vec<_> = cal
+            .events_in_range(start, start + chrono::Duration::days(4))
+            .map(|occ| occ.start())
+            .collect();
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        assert_eq!(
+            found,
+            vec![
+                NaiveDateTime::new(ymd(2323, 3, 5), nine),
+                NaiveDateTime::new(ymd(2323, 3, 8), nine),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exdate_and_rdate() {
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let at = |d| NaiveDateTime::new(ymd(2023, 1, d), nine);
+
+        let series = Event::new("Daily".into(), &ymd(2023, 1, 2))
+            .set_start_time(nine)
+            .unwrap()
+            .set_recurrence(Some("FREQ=DAILY;COUNT=4".parse().unwrap()))
+            .add_exdate(at(3))
+            .add_rdate(at(10))
+            // duplicates an occurrence of the rule
+            .add_rdate(at(4));
+
+        let days: Vec<_> = series.occurrences().map(|dt| dt.day()).collect();
+        assert_eq!(days, vec![2, 4, 5, 10]);
+
+        // RDATEs extend the part of the calendar the series covers
+        let mut cal = EventCalendar::default();
+        cal.add_event(series);
+        let found: Vec<_> = cal
+            .events_in_range(at(9), at(11))
+            .map(|occ| occ.recurrence_id())
+            .collect();
+        assert_eq!(found, vec![Some(at(10))]);
+
+        // as do RDATEs before the start of the series
+        let id = *cal.first_event().unwrap().id();
+        cal.update(id, |evt| Ok(evt.add_rdate(at(1)))).unwrap();
+        let found: Vec<_> = cal
+            .events_in_range(at(1), at(2))
+            .map(|occ| occ.recurrence_id())
+            .collect();
+        assert_eq!(found, vec![Some(at(1))]);
+    }
+
+    #[test]
+    fn test_occurrence_overrides() {
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let at = |d| NaiveDateTime::new(ymd(2023, 1, d), nine);
+
+        let series = Event::new("Standup".into(), &ymd(2023, 1, 2))
+            .set_start_time(nine)
+            .unwrap()
+            .set_recurrence(Some("FREQ=WEEKLY;COUNT=3".parse().unwrap()));
+        let id = *series.id();
+
+        let mut cal = EventCalendar::default();
+        cal.add_event(series);
+
+        // only real occurrences can be overridden
+        assert!(matches!(
+            cal.override_occurrence(id, at(3), Ok),
+            Err(EventError::NoSuchOccurrence(..))
+        ));
+
+        // move the second occurrence from the 9th to the 20th and rename it
+        cal.override_occurrence(id, at(9), |mut evt| {
+            evt.set_name("Moved".into());
+            evt.set_end_date(ymd(2023, 1, 20))?
+                .set_start_date(ymd(2023, 1, 20))
+        })
+        .unwrap();
+
+        fn week(cal: &EventCalendar, day: u32) -> Vec<Occurrence<'_>> {
+            let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+            let start = NaiveDateTime::new(ymd(2023, 1, day), nine);
+            cal.events_in_range(start, start + chrono::Duration::weeks(1))
+                .collect()
+        }
+        assert!(week(&cal, 8).is_empty());
+
+        let found = week(&cal, 16);
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            (found[0].name(), found[0].is_override()),
+            ("Standup", false)
+        );
+        assert_eq!(found[0].start(), at(16));
+        assert_eq!((found[1].name(), found[1].is_override()), ("Moved", true));
+        assert_eq!(found[1].start(), at(20));
+        assert_eq!(found[1].recurrence_id(), Some(at(9)));
+        assert_eq!(found[1].event().id(), &id);
+
+        // overriding again starts from the existing override
+        let old = cal
+            .override_occurrence(id, at(9), |evt| {
+                evt.set_end_time(NaiveTime::from_hms_opt(10, 0, 0).unwrap())
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(old.end().date(), ymd(2023, 1, 20));
+        assert_eq!(cal.get_override(id, at(9)).unwrap().name(), "Moved");
+
+        // cancelling an occurrence drops its override
+        cal.cancel_occurrence(id, at(9)).unwrap();
+        assert_eq!(cal.overrides(id).count(), 0);
+        assert_eq!(week(&cal, 16).len(), 1);
+        assert_eq!(cal.events_in_range(at(1), at(30)).count(), 2);
+
+        // restoring a removed override brings back the original occurrence
+        cal.override_occurrence(id, at(16), |mut evt| {
+            evt.set_name("Renamed".into());
+            Ok(evt)
+        })
+        .unwrap();
+        assert_eq!(week(&cal, 16)[0].name(), "Renamed");
+        cal.remove_override(id, at(16)).unwrap();
+        assert_eq!(week(&cal, 16)[0].name(), "Standup");
+
+        // removing the series removes its overrides too
+        cal.override_occurrence(id, at(2), Ok).unwrap();
+        cal.remove(id).unwrap();
+        assert_eq!(cal.overrides(id).count(), 0);
+        assert_eq!(cal.events_in_range(at(1), at(30)).count(), 0);
+
+        // moving the start of a series moves its overrides along
+        let series = Event::new("Standup".into(), &ymd(2023, 1, 2))
+            .set_start_time(nine)
+            .unwrap()
+            .set_recurrence(Some("FREQ=WEEKLY;COUNT=3".parse().unwrap()));
+        let id = *series.id();
+        cal.add_event(series);
+        cal.override_occurrence(id, at(9), |mut evt| {
+            evt.set_name("Renamed".into());
+            Ok(evt)
+        })
+        .unwrap();
+        cal.update(id, |evt| {
+            evt.set_end_date(ymd(2023, 1, 3))?
+                .set_start_date(ymd(2023, 1, 3))
+        })
+        .unwrap();
+        assert!(cal.get_override(id, at(9)).is_none());
+        assert_eq!(cal.get_override(id, at(10)).unwrap().name(), "Renamed");
+        assert_eq!(cal.events_in_range(at(1), at(30)).count(), 3);
+
+        // and drops those of occurrences the series no longer has
+        cal.update(id, |evt| {
+            Ok(evt.set_recurrence(Some("FREQ=WEEKLY;COUNT=1".parse().unwrap())))
+        })
+        .unwrap();
+        assert_eq!(cal.overrides(id).count(), 0);
+        assert_eq!(cal.events_in_range(at(1), at(30)).count(), 1);
+    }
+
+    #[test]
+    fn test_split_series() {
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let ten = NaiveTime::from_hms_opt(10, 0, 0).unwrap();
+        let at = |d, t| NaiveDateTime::new(ymd(2023, 1, d), t);
+
+        // daily from the 2nd to the 11th, without the 3rd or the 8th
+        let series = Event::new("Standup".into(), &ymd(2023, 1, 2))
+            .set_end_time(NaiveTime::from_hms_opt(9, 15, 0).unwrap())
+            .unwrap()
+            .set_start_time(nine)
+            .unwrap()
+            .set_recurrence(Some("FREQ=DAILY;UNTIL=20230111T090000".parse().unwrap()))
+            .add_exdate(at(3, nine))
+            .add_exdate(at(8, nine));
+        let id = *series.id();
+
+        let mut cal = EventCalendar::default();
+        cal.add_event(series);
+        for day in [4, 9] {
+            cal.override_occurrence(id, at(day, nine), |mut evt| {
+                evt.set_name(format!("Override {day}"));
+                Ok(evt)
+            })
+            .unwrap();
+        }
+
+        // from the 6th on, standups move to 10:00
+        let new = cal
+            .split_series(id, at(6, nine), |evt| {
+                evt.set_end_time(NaiveTime::from_hms_opt(10, 15, 0).unwrap())?
+                    .set_start_time(ten)
+            })
+            .unwrap();
+        let new_id = *new.id();
+        assert_ne!(new_id, id);
+        assert_eq!(new.related_to(), Some(&id));
+
+        let old = cal.get(id).unwrap();
+        let old_days: Vec<_> = old.occurrences().map(|dt| dt.day()).collect();
+        assert_eq!(old_days, vec![2, 4, 5]);
+        assert_eq!(old.exdates().count(), 1);
+
+        let new_days: Vec<_> = new.occurrences().map(|dt| (dt.day(), dt.time())).collect();
+        assert_eq!(new_days, [6, 7, 9, 10, 11].map(|d| (d, ten)).to_vec());
+
+        // overrides stay with their half of the series
+        assert_eq!(cal.overrides(id).count(), 1);
+        assert_eq!(
+            cal.get_override(id, at(4, nine)).unwrap().name(),
+            "Override 4"
+        );
+        assert_eq!(
+            cal.get_override(new_id, at(9, ten)).unwrap().name(),
+            "Override 9"
+        );
+
+        let names: Vec<_> = cal
+            .events_in_range(at(1, nine), at(12, nine))
+            .map(|occ| occ.name())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "Standup",
+                "Override 4",
+                "Standup",
+                "Standup",
+                "Standup",
+                "Override 9",
+                "Standup",
+                "Standup"
+            ]
+        );
+
+        // splitting at the first occurrence edits the whole series in place
+        let counted = Event::new("Counted".into(), &ymd(2023, 2, 1))
+            .set_recurrence(Some("FREQ=DAILY;COUNT=3".parse().unwrap()));
+        let counted_id = *counted.id();
+        cal.add_event(counted);
+        let first = cal.get(counted_id).unwrap().start();
+        let edited = cal
+            .split_series(counted_id, first, |mut evt| {
+                evt.set_name("Renamed".into());
+                Ok(evt)
+            })
+            .unwrap();
+        assert_eq!(edited.id(), &counted_id);
+        assert_eq!(edited.related_to(), None);
+        assert_eq!(edited.recurrence().unwrap().count(), Some(3));
+
+        // splitting at an RDATE after the rule ends leaves the rule behind
+        let extra = first + chrono::Duration::days(9);
+        cal.update(counted_id, |evt| Ok(evt.add_rdate(extra)))
+            .unwrap();
+        let moved = cal.split_series(counted_id, extra, Ok).unwrap();
+        assert!(moved.recurrence().is_none());
+        assert_eq!(moved.occurrences().collect::<Vec<_>>(), vec![extra]);
+        let head = cal.get(counted_id).unwrap();
+        assert_eq!(head.recurrence().unwrap().count(), Some(3));
+        assert_eq!(head.occurrences().count(), 3);
+    }
+
+    #[test]
+    fn test_dst_disambiguation() {
+        use chrono::{TimeZone, Utc};
+        use chrono_tz::America::New_York;
+
+        let ny = EventTz::Zone(New_York);
+        let at = |m, d, h, min| {
+            NaiveDateTime::new(ymd(2023, m, d), NaiveTime::from_hms_opt(h, min, 0).unwrap())
+        };
+        let utc = |m, d, h, min| Utc.from_utc_datetime(&at(m, d, h, min));
+
+        // 02:30 on 03/12 is skipped when clocks go forward from 02:00 to 03:00
+        let gap = at(3, 12, 2, 30);
+        assert!(matches!(
+            ny.to_utc(gap, &Utc, Disambiguation::Reject),
+            Err(EventError::NonexistentTime(..))
+        ));
+        // later/compatible read it with the offset from before the gap (-5)
+        assert_eq!(
+            ny.to_utc(gap, &Utc, Disambiguation::Compatible).unwrap(),
+            utc(3, 12, 7, 30)
+        );
+        assert_eq!(
+            ny.to_utc(gap, &Utc, Disambiguation::Later).unwrap(),
+            utc(3, 12, 7, 30)
+        );
+        // earlier reads it with the offset from after the gap (-4)
+        assert_eq!(
+            ny.to_utc(gap, &Utc, Disambiguation::Earlier).unwrap(),
+            utc(3, 12, 6, 30)
+        );
+
+        // 01:30 on 11/05 happens twice when clocks go back from 02:00 to 01:00
+        let overlap = at(11, 5, 1, 30);
+        assert!(matches!(
+            ny.to_utc(overlap, &Utc, Disambiguation::Reject),
+            Err(EventError::AmbiguousTime(..))
+        ));
+        assert_eq!(
+            ny.to_utc(overlap, &Utc, Disambiguation::Compatible)
+                .unwrap(),
+            utc(11, 5, 5, 30)
+        );
+        assert_eq!(
+            ny.to_utc(overlap, &Utc, Disambiguation::Earlier).unwrap(),
+            utc(11, 5, 5, 30)
+        );
+        assert_eq!(
+            ny.to_utc(overlap, &Utc, Disambiguation::Later).unwrap(),
+            utc(11, 5, 6, 30)
+        );
+
+        // the same rules apply when reading an event's times
+        let event = Event::new("Late".into(), &ymd(2023, 3, 12))
+            .set_start_time(NaiveTime::from_hms_opt(2, 30, 0).unwrap())
+            .unwrap()
+            .set_tz(ny);
+        assert!(event.start_in(&Utc, Disambiguation::Reject).is_err());
+        assert_eq!(
+            event
+                .start_in(&New_York, Disambiguation::Compatible)
+                .unwrap()
+                .time(),
+            NaiveTime::from_hms_opt(3, 30, 0).unwrap()
+        );
+
+        assert!(matches!(
+            "Mars/Olympus_Mons".parse::<EventTz>(),
+            Err(EventError::UnknownTimeZone(_))
+        ));
+    }
+
+    #[test]
+    fn test_next_and_previous() {
+        use chrono::{Duration, TimeZone, Utc};
+
+        let at = |d, h, m| {
+            NaiveDateTime::new(ymd(2023, 1, d), NaiveTime::from_hms_opt(h, m, 0).unwrap())
+        };
+        let meeting = |name: &str, start, end| {
+            Event::new(name.into(), &ymd(2023, 1, 1))
+                .set_end(end)
+                .and_then(|evt| evt.set_start(start))
+                .unwrap()
+        };
+
+        // a standup from the 2nd to the 6th, not on the 3rd and later on the 4th
+        let standup = meeting("Standup", at(2, 9, 0), at(2, 10, 0))
+            .set_recurrence(Some("FREQ=DAILY;COUNT=5".parse::<RRule>().unwrap()))
+            .add_exdate(at(3, 9, 0));
+        let standup_id = *standup.id();
+        let mut cal = EventCalendar::default();
+        assert!(cal.next_after(at(1, 0, 0)).is_none());
+        cal.add_event(standup);
+        cal.override_occurrence(standup_id, at(4, 9, 0), |evt| {
+            evt.set_end(at(4, 12, 0))
+                .and_then(|evt| evt.set_start(at(4, 11, 0)))
+        })
+        .unwrap();
+        cal.add_event(meeting("Dentist", at(3, 9, 0), at(3, 9, 45)));
+        cal.add_event(meeting("Review", at(5, 9, 0), at(5, 9, 30)));
+
+        let next = |instant| cal.next_after(instant).map(|occ| (occ.name(), occ.start()));
+        let previous = |instant| {
+            cal.previous_before(instant)
+                .map(|occ| (occ.name(), occ.start()))
+        };
+        assert_eq!(next(at(1, 0, 0)), Some(("Standup", at(2, 9, 0))));
+        assert_eq!(next(at(2, 9, 0)), Some(("Dentist", at(3, 9, 0))));
+        assert_eq!(next(at(3, 9, 0)), Some(("Standup", at(4, 11, 0))));
+        assert!(cal.next_after(at(3, 9, 0)).unwrap().is_override());
+        // occurrences starting together come in the order of events_in_range
+        assert_eq!(next(at(4, 11, 0)), Some(("Review", at(5, 9, 0))));
+        assert_eq!(previous(at(5, 10, 0)), Some(("Standup", at(5, 9, 0))));
+        assert_eq!(previous(at(5, 9, 0)), Some(("Standup", at(4, 11, 0))));
+        assert_eq!(previous(at(4, 11, 0)), Some(("Dentist", at(3, 9, 0))));
+        assert_eq!(next(at(6, 9, 0)), None);
+        assert_eq!(previous(at(2, 9, 0)), None);
+        assert_eq!(previous(at(31, 0, 0)), Some(("Standup", at(6, 9, 0))));
+
+        // 09:30 in Paris comes before 09:00 in UTC
+        cal.add_event(
+            meeting("Paris", at(5, 9, 30), at(5, 10, 0)).set_tz(chrono_tz::Europe::Paris.into()),
+        );
+        let clock = ManualClock::new(Utc.with_ymd_and_hms(2023, 1, 4, 12, 0, 0).unwrap());
+        let names = |found: Vec<Occurrence>| -> Vec<_> {
+            found.iter().map(|occ| occ.name().to_string()).collect()
+        };
+        assert_eq!(
+            names(cal.upcoming(&clock, 3)),
+            ["Paris", "Review", "Standup"]
+        );
+        assert!(cal.upcoming(&clock, 0).is_empty());
+        clock.advance(Duration::days(1));
+        let upcoming = cal.upcoming(&clock, 10);
+        assert_eq!(upcoming.len(), 1);
+        assert_eq!(upcoming[0].start(), at(6, 9, 0));
+    }
+
+    #[test]
+    fn test_events_in_range_tz() {
+        use chrono::TimeZone;
+        use chrono_tz::{Asia::Tokyo, Europe::London};
+
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let hour_at_nine = |name: &str, tz: EventTz| {
+            Event::new(name.into(), &ymd(2023, 1, 10))
+                .set_end_time(NaiveTime::from_hms_opt(10, 0, 0).unwrap())
+                .unwrap()
+                .set_start_time(nine)
+                .unwrap()
+                .set_tz(tz)
+        };
+
+        let mut cal = EventCalendar::default();
+        cal.add_event(hour_at_nine("Tokyo", Tokyo.into()));
+        cal.add_event(hour_at_nine("London", London.into()));
+        cal.add_event(hour_at_nine("UTC", EventTz::Utc));
+        cal.add_event(hour_at_nine("Floating", EventTz::Floating));
+
+        let names = |start, end| {
+            cal.events_in_range_tz(&start, &end, Disambiguation::Reject)
+                .unwrap()
+                .map(|occ| occ.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // 09:00 in Tokyo is 00:00 UTC, London is on UTC in January
+        let tokyo_day = names(
+            Tokyo.with_ymd_and_hms(2023, 1, 10, 0, 0, 0).unwrap(),
+            Tokyo.with_ymd_and_hms(2023, 1, 11, 0, 0, 0).unwrap(),
+        );
+        assert_eq!(tokyo_day, vec!["Floating", "Tokyo", "London", "UTC"]);
+
+        // floating events follow whoever is asking
+        let london_morning = names(
+            London.with_ymd_and_hms(2023, 1, 10, 8, 30, 0).unwrap(),
+            London.with_ymd_and_hms(2023, 1, 10, 9, 30, 0).unwrap(),
+        );
+        assert_eq!(london_morning, vec!["Floating", "London", "UTC"]);
+
+        let tokyo_morning = names(
+            Tokyo.with_ymd_and_hms(2023, 1, 10, 8, 30, 0).unwrap(),
+            Tokyo.with_ymd_and_hms(2023, 1, 10, 9, 30, 0).unwrap(),
+        );
+        assert_eq!(tokyo_morning, vec!["Floating", "Tokyo"]);
+    }
+
+    const SAMPLE_ICS: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Example Corp//Calendar//EN\r
+BEGIN:VTIMEZONE\r
+TZID:Eastern Standard Time\r
+X-LIC-LOCATION:America/New_York\r
+BEGIN:STANDARD\r
+DTSTART:16011104T020000\r
+TZOFFSETFROM:-0400\r
+TZOFFSETTO:-0500\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:weekly-sync@example.com\r
+DTSTART;TZID=\"Eastern Standard Time\":20230102T090000\r
+DTEND;TZID=\"Eastern Standard Time\":20230102T093000\r
+RRULE:FREQ=WEEKLY;COUNT=4\r
+EXDATE;TZID=\"Eastern Standard Time\":20230109T090000,20230116T090000\r
+SUMMARY:Weekly sync\\, with a very long name that has to be folded over more\r
+  than one line\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+TRIGGER:-PT10M\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:weekly-sync@example.com\r
+RECURRENCE-ID:20230123T140000Z\r
+DTSTART:20230123T150000Z\r
+DURATION:PT1H\r
+SUMMARY:Weekly sync (moved)\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:2f1d4a6e-3c50-4c4e-9b67-6a1f0e4f3c2b\r
+DTSTART;VALUE=DATE:20230105\r
+SUMMARY:Holiday\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn test_ics_import() {
+        use chrono_tz::America::New_York;
+
+        let cal = EventCalendar::from_ics(SAMPLE_ICS).unwrap();
+
+        let holiday = cal.try_get("2f1d4a6e-3c50-4c4e-9b67-6a1f0e4f3c2b").unwrap();
+        assert_eq!(holiday.name(), "Holiday");
+        assert_eq!(
+            holiday.start(),
+            NaiveDateTime::new(ymd(2023, 1, 5), first_time_nt())
+        );
+        assert_eq!(holiday.duration(), chrono::Duration::days(1));
+        assert_eq!(holiday.dates(), Some((ymd(2023, 1, 5), ymd(2023, 1, 6))));
+
+        let sync = cal.first_event().unwrap();
+        assert_eq!(
+            sync.name(),
+            "Weekly sync, with a very long name that has to be folded over more than one line"
+        );
+        assert_eq!(sync.tz(), EventTz::Zone(New_York));
+        assert_eq!(sync.duration(), chrono::Duration::minutes(30));
+        assert_eq!(sync.exdates().count(), 2);
+        assert_eq!(
+            sync.alarms(),
+            [Alarm::new(
+                AlarmAction::Display,
+                Trigger::Start(-chrono::Duration::minutes(10))
+            )]
+        );
+
+        // the override was written in UTC but belongs to the New York series
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+        let moved = cal
+            .get_override(sync.id(), NaiveDateTime::new(ymd(2023, 1, 23), nine))
+            .unwrap();
+        assert_eq!(moved.name(), "Weekly sync (moved)");
+        assert_eq!(moved.tz(), EventTz::Utc);
+
+        let found: Vec<_> = cal
+            .events_in_range(
+                NaiveDateTime::new(ymd(2023, 1, 1), first_time_nt()),
+                NaiveDateTime::new(ymd(2023, 2, 1), first_time_nt()),
+            )
+            .map(|occ| (occ.name().to_string(), occ.start().day()))
+            .collect();
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].1, 2);
+        assert_eq!(found[1], ("Holiday".to_string(), 5));
+        assert_eq!(found[2], ("Weekly sync (moved)".to_string(), 23));
+    }
+
+    #[test]
+    fn test_ics_import_errors() {
+        let line_of = |ics: &str| match EventCalendar::from_ics(ics) {
+            Err(EventError::Ics { line, .. })
+            | Err(EventError::IcsMissingProperty { line, .. }) => line,
+            other => panic!("expected an iCalendar error, got {:?}", other.map(|_| ())),
+        };
+
+        // missing DTSTART is reported at the start of the VEVENT
+        assert_eq!(
+            line_of("BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:A\nEND:VEVENT\nEND:VCALENDAR\n"),
+            2
+        );
+        // mismatched END
+        assert_eq!(
+            line_of("BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:20230101\nEND:VTODO\nEND:VCALENDAR\n"),
+            4
+        );
+        // malformed date-time
+        assert_eq!(
+            line_of("BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:2023-01-01T10:00\nEND:VEVENT\nEND:VCALENDAR\n"),
+            3
+        );
+        // unclosed component
+        assert_eq!(
+            line_of("BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:20230101\n"),
+            2
+        );
+        // a line without a value
+        assert_eq!(
+            line_of(
+                "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART;VALUE=DATE\nEND:VEVENT\nEND:VCALENDAR\n"
+            ),
+            3
+        );
+        // an end before the start
+        assert_eq!(
+            line_of("BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:20230102T100000\nDTEND:20230102T090000\nEND:VEVENT\nEND:VCALENDAR\n"),
+            3
+        );
+        // unknown time zone
+        assert_eq!(
+            line_of("BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART;TZID=Nowhere:20230102T100000\nDURATION:PT1H\nEND:VEVENT\nEND:VCALENDAR\n"),
+            3
+        );
+        // durations too long for a date-time are errors, not panics
+        assert_eq!(
+            line_of("BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:20230102T100000\nDURATION:P999999999999W\nEND:VEVENT\nEND:VCALENDAR\n"),
+            4
+        );
+        assert_eq!(
+            line_of("BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:20230102T100000\nDURATION:P99999999D\nEND:VEVENT\nEND:VCALENDAR\n"),
+            4
+        );
+        // an event that takes no time is skipped, not the whole file
+        let cal = EventCalendar::from_ics(
+            "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:20230102T100000\nDTEND:20230102T100000\nEND:VEVENT\n\
+             BEGIN:VEVENT\nDTSTART:20230102T100000\nDURATION:PT0S\nEND:VEVENT\n\
+             BEGIN:VEVENT\nSUMMARY:Kept\nDTSTART:20230102T100000\nDURATION:PT1H\nEND:VEVENT\nEND:VCALENDAR\n",
+        )
+        .unwrap();
+        let names: Vec<_> = cal.events().map(|evt| evt.name()).collect();
+        assert_eq!(names, ["Kept"]);
+        assert!(matches!(
+            EventCalendar::from_ics(
+                "BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:A\nEND:VEVENT\nEND:VCALENDAR\n"
+            ),
+            Err(EventError::IcsMissingProperty {
+                property: "DTSTART",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_ics_round_trip() {
+        use chrono::{TimeZone, Utc};
+        use chrono_tz::Europe::Paris;
+
+        let mut cal = EventCalendar::from_ics(SAMPLE_ICS).unwrap();
+        let rule = "FREQ=DAILY;UNTIL=20230331T090000".parse::<RRule>().unwrap();
+        let name = "Réunion; ordre du jour: \\budget\\, planning\nnotes ".repeat(3);
+        let daily = Event::new(name.clone(), &ymd(2023, 3, 20))
+            .set_start_time(NaiveTime::from_hms_opt(9, 0, 0).unwrap())
+            .and_then(|evt| evt.set_end_time(NaiveTime::from_hms_opt(9, 30, 0).unwrap()))
+            .unwrap()
+            .set_recurrence(Some(rule))
+            .set_tz(Paris.into());
+        let daily_id = *daily.id();
+        cal.add_event(daily);
+        // a series that runs on past a change to the rules of its zone
+        let yearly = Event::new("Anniversary".into(), &ymd(2005, 6, 2))
+            .set_recurrence(Some("FREQ=YEARLY".parse().unwrap()))
+            .set_tz(chrono_tz::America::New_York.into());
+        cal.add_event(yearly);
+
+        let stamp = Utc.with_ymd_and_hms(2023, 1, 1, 12, 0, 0).unwrap();
+        let ics = cal.to_ics_with_stamp(stamp);
+
+        // content lines end in CRLF and are folded at 75 octets
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.split("\r\n").all(|line| line.len() <= 75));
+        assert!(ics.contains("\r\n "));
+        assert!(ics.contains("PRODID:-//calib//calib"));
+        assert!(ics.contains("DTSTAMP:20230101T120000Z\r\n"));
+        assert!(ics.contains("BEGIN:VTIMEZONE\r\nTZID:Europe/Paris\r\n"));
+        assert!(ics.contains("TZOFFSETFROM:+0100\r\nTZOFFSETTO:+0200\r\n"));
+        // observances repeat every year for as long as their rule holds
+        assert!(
+            ics.contains("DTSTART:20220327T020000\r\nRRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r\n")
+        );
+        assert!(ics.contains("RRULE:FREQ=YEARLY;BYMONTH=4;BYDAY=1SU;UNTIL=20060402T070000Z\r\n"));
+        assert!(
+            ics.contains("DTSTART:20070311T020000\r\nRRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=2SU\r\n")
+        );
+        // UNTIL is written in UTC because the series has a time zone
+        assert!(ics.contains("UNTIL=20230331T070000Z"));
+
+        let back = EventCalendar::from_ics(&ics).unwrap();
+        assert!(back.events().eq(cal.events()));
+        for evt in cal.events() {
+            assert!(back.overrides(evt.id()).eq(cal.overrides(evt.id())));
+        }
+        assert_eq!(back.get(daily_id).unwrap().name(), name);
+
+        let start = NaiveDateTime::new(ymd(2023, 1, 1), first_time_nt());
+        let end = NaiveDateTime::new(ymd(2023, 4, 1), first_time_nt());
+        assert!(back
+            .events_in_range(start, end)
+            .map(|occ| (occ.start(), occ.end(), occ.name().to_string()))
+            .eq(cal.events_in_range(start, end).map(|occ| (
+                occ.start(),
+                occ.end(),
+                occ.name().to_string()
+            ))));
+    }
+
+    #[test]
+    fn test_alarms() {
+        use chrono::{Duration, TimeZone, Utc};
+
+        let at = |d, h, m| ManualClock::new(Utc.with_ymd_and_hms(2023, 1, d, h, m, 0).unwrap());
+        let fired = |due: &[DueAlarm]| -> Vec<_> {
+            due.iter()
+                .map(|a| (a.at().day(), a.at().hour(), a.at().minute(), a.repetition()))
+                .collect()
+        };
+
+        assert!(matches!(
+            Alarm::new(AlarmAction::Audio, Trigger::Start(Duration::zero()))
+                .set_repeat(2, Duration::zero()),
+            Err(EventError::InvalidProperty("DURATION", _))
+        ));
+        assert!(matches!(
+            Alarm::new(AlarmAction::Audio, Trigger::Start(Duration::zero()))
+                .set_repeat(u32::MAX, Duration::minutes(5)),
+            Err(EventError::InvalidProperty("REPEAT", _))
+        ));
+
+        // a daily standup at 9:00 reminding 10 minutes before, twice more
+        // five minutes apart
+        let reminder = Alarm::new(AlarmAction::Display, Trigger::Start(-Duration::minutes(10)))
+            .set_repeat(2, Duration::minutes(5))
+            .unwrap()
+            .set_description(Some("Standup soon".into()));
+        let rule = "FREQ=DAILY;COUNT=3".parse::<RRule>().unwrap();
+        let standup = Event::new("Standup".into(), &ymd(2023, 1, 2))
+            .set_start_time(NaiveTime::from_hms_opt(9, 0, 0).unwrap())
+            .and_then(|evt| evt.set_end_time(NaiveTime::from_hms_opt(9, 15, 0).unwrap()))
+            .unwrap()
+            .set_recurrence(Some(rule))
+            .add_alarm(reminder);
+        let standup_id = *standup.id();
+        // a lunch in Paris that reminds when it ends and at a fixed time
+        let lunch = Event::new("Lunch".into(), &ymd(2023, 1, 2))
+            .set_start_time(NaiveTime::from_hms_opt(12, 0, 0).unwrap())
+            .and_then(|evt| evt.set_end_time(NaiveTime::from_hms_opt(13, 0, 0).unwrap()))
+            .unwrap()
+            .set_tz(chrono_tz::Europe::Paris.into())
+            .add_alarm(Alarm::new(
+                AlarmAction::Email,
+                Trigger::End(Duration::zero()),
+            ))
+            .add_alarm(Alarm::new(
+                AlarmAction::Audio,
+                Trigger::At(Utc.with_ymd_and_hms(2023, 1, 1, 18, 0, 0).unwrap()),
+            ));
+        let mut cal = EventCalendar::default();
+        cal.add_event(standup);
+        cal.add_event(lunch.clone());
+
+        let mut engine = AlarmEngine::new(at(1, 0, 0).now());
+        assert_eq!(fired(&engine.due(&cal, &at(1, 20, 0))), vec![(1, 18, 0, 0)]);
+        assert_eq!(fired(&engine.due(&cal, &at(2, 8, 52))), vec![(2, 8, 50, 0)]);
+        let due = engine.due(&cal, &at(2, 9, 1));
+        assert_eq!(fired(&due), vec![(2, 8, 55, 1), (2, 9, 0, 2)]);
+        assert_eq!(due[0].event_id(), &standup_id);
+        assert_eq!(due[0].alarm().description(), Some("Standup soon"));
+        assert_eq!(
+            due[0].recurrence_id(),
+            Some(NaiveDateTime::new(
+                ymd(2023, 1, 2),
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap()
+            ))
+        );
+        // lunch ends at 13:00 in Paris, which is noon UTC
+        let ends = engine.pending(&cal, at(2, 23, 0).now());
+        assert_eq!(fired(&ends), vec![(2, 12, 0, 0)]);
+        assert_eq!(ends[0].name(), "Lunch");
+        engine.due(&cal, &at(2, 23, 0));
+
+        // dismissing stops the repeats
+        let due = engine.due(&cal, &at(3, 8, 50));
+        assert_eq!(fired(&due), vec![(3, 8, 50, 0)]);
+        engine.dismiss(&due[0]);
+        assert!(engine.due(&cal, &at(3, 23, 0)).is_empty());
+
+        // snoozing replaces the repeats with a single alarm
+        let due = engine.due(&cal, &at(4, 8, 50));
+        engine.snooze(&due[0], at(4, 9, 2).now());
+        assert!(engine.due(&cal, &at(4, 9, 1)).is_empty());
+        let due = engine.due(&cal, &at(4, 9, 30));
+        assert_eq!(fired(&due), vec![(4, 9, 2, 0)]);
+        assert!(due[0].is_snoozed());
+        assert!(engine.due(&cal, &at(5, 12, 0)).is_empty());
+
+        // repeats that would run past the end of time stop there
+        let nagging = Alarm::new(AlarmAction::Display, Trigger::Start(Duration::zero()))
+            .set_repeat(i32::MAX as u32, Duration::weeks(1))
+            .unwrap();
+        assert_eq!(
+            nagging
+                .fire_times(at(6, 9, 0).now(), at(6, 10, 0).now())
+                .nth(2),
+            Some(at(20, 9, 0).now())
+        );
+        let mut nagged = EventCalendar::default();
+        nagged.add_event(Event::new("Nag".into(), &ymd(2023, 1, 6)).add_alarm(nagging));
+        let due = engine.due(&nagged, &at(13, 1, 0));
+        assert_eq!(fired(&due), vec![(6, 0, 0, 0), (13, 0, 0, 1)]);
+
+        // as do triggers too far off to represent
+        let never = Alarm::new(AlarmAction::Display, Trigger::Start(Duration::MAX));
+        assert_eq!(
+            never
+                .fire_times(at(6, 9, 0).now(), at(6, 10, 0).now())
+                .count(),
+            0
+        );
+        let never = Alarm::new(AlarmAction::Display, Trigger::End(Duration::MAX));
+        let mut nagged = EventCalendar::default();
+        nagged.add_event(Event::new("Never".into(), &ymd(2023, 1, 6)).add_alarm(never));
+        assert!(engine.due(&nagged, &at(13, 1, 0)).is_empty());
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   BEGIN:VEVENT\r\n\
+                   UID:far@example.com\r\n\
+                   DTSTART:20230106T090000Z\r\n\
+                   DURATION:PT1H\r\n\
+                   BEGIN:VALARM\r\n\
+                   ACTION:DISPLAY\r\n\
+                   TRIGGER:-P9999999W\r\n\
+                   END:VALARM\r\n\
+                   END:VEVENT\r\n\
+                   END:VCALENDAR\r\n";
+        let far = EventCalendar::from_ics(ics).unwrap();
+        assert!(engine.due(&far, &at(13, 1, 0)).is_empty());
+        let clock = at(1, 0, 0);
+        clock.advance(Duration::MAX);
+        assert_eq!(clock.now(), chrono::DateTime::<Utc>::MAX_UTC);
+
+        // alarms survive serialization and iCalendar
+        assert_eq!(Event::deserialize(&lunch.serialize()).unwrap(), lunch);
+        let ics = cal.to_ics_with_stamp(at(1, 0, 0).now());
+        assert!(ics.contains("TRIGGER:-PT10M\r\nREPEAT:2\r\nDURATION:PT5M\r\n"));
+        assert!(ics.contains("TRIGGER;RELATED=END:PT0S\r\n"));
+        assert!(ics.contains("TRIGGER;VALUE=DATE-TIME:20230101T180000Z\r\n"));
+        let back = EventCalendar::from_ics(&ics).unwrap();
+        assert!(back.events().eq(cal.events()));
+    }
+
+    #[test]
+    fn test_tasks() {
+        use chrono::{TimeZone, Utc};
+
+        let at =
+            |d, h| NaiveDateTime::new(ymd(2023, 1, d), NaiveTime::from_hms_opt(h, 0, 0).unwrap());
+        let task = |name: &str, due| Task::new(name.into()).set_due(due).unwrap();
+
+        assert!(matches!(
+            task("Report", Some(at(6, 17))).set_start(Some(at(6, 17))),
+            Err(EventError::InvalidStartTime)
+        ));
+        assert!(matches!(
+            Task::new("Report".into()).set_percent_complete(101),
+            Err(EventError::InvalidProperty("PERCENT-COMPLETE", _))
+        ));
+
+        let report = task("Report", Some(at(6, 17)))
+            .set_start(Some(at(2, 9)))
+            .unwrap()
+            .set_percent_complete(40)
+            .unwrap()
+            .set_status(TaskStatus::InProcess)
+            .set_priority(Some(1))
+            .unwrap()
+            .set_tz(chrono_tz::Europe::Paris.into());
+        let report_id = *report.id();
+        let taxes = task("Taxes", Some(at(3, 12)));
+        let taxes_id = *taxes.id();
+        let someday = task("Learn the cello", None);
+        let invoice = task("Invoice", Some(at(4, 9)))
+            .complete(Utc.with_ymd_and_hms(2023, 1, 3, 10, 0, 0).unwrap());
+
+        let mut cal = EventCalendar::default();
+        for task in [report, taxes, someday, invoice] {
+            assert!(cal.add_task(task));
+        }
+        let names =
+            |tasks: Vec<&Task>| -> Vec<_> { tasks.iter().map(|t| t.name().to_string()).collect() };
+        assert_eq!(
+            names(cal.tasks().collect()),
+            ["Taxes", "Invoice", "Report", "Learn the cello"]
+        );
+        assert_eq!(
+            names(cal.tasks_due_in_range(at(3, 12), at(6, 17)).collect()),
+            ["Taxes", "Invoice"]
+        );
+        // completed tasks are never overdue
+        assert_eq!(names(cal.overdue_tasks(at(5, 0)).collect()), ["Taxes"]);
+        assert_eq!(
+            names(cal.overdue_tasks(at(6, 17)).collect()),
+            ["Taxes", "Report"]
+        );
+
+        // moving the due date keeps the order up to date
+        let done = Utc.with_ymd_and_hms(2023, 1, 3, 11, 0, 0).unwrap();
+        let old = cal
+            .update_task(taxes_id, |task| {
+                task.set_due(Some(at(7, 12)))
+                    .map(|task| task.complete(done))
+            })
+            .unwrap();
+        assert_eq!(old.due(), Some(at(3, 12)));
+        assert_eq!(
+            names(cal.tasks_due_in_range(at(1, 0), at(7, 0)).collect()),
+            ["Invoice", "Report"]
+        );
+        assert!(cal.overdue_tasks(at(5, 0)).next().is_none());
+        let taxes = cal.get_task(taxes_id).unwrap();
+        assert!(taxes.is_completed());
+        assert_eq!(taxes.completed(), Some(done));
+        assert_eq!(taxes.clone().reopen().percent_complete(), 0);
+
+        // tasks are kept in JSON and iCalendar
+        let json = serde_json::to_string(&cal).unwrap();
+        let back: EventCalendar = serde_json::from_str(&json).unwrap();
+        assert!(back.tasks().eq(cal.tasks()));
+
+        let ics = cal.to_ics_with_stamp(done);
+        assert!(ics.contains("BEGIN:VTIMEZONE\r\nTZID:Europe/Paris\r\n"));
+        assert!(ics.contains("DUE;TZID=Europe/Paris:20230106T170000\r\n"));
+        assert!(ics.contains("STATUS:IN-PROCESS\r\nPERCENT-COMPLETE:40\r\n"));
+        assert!(ics.contains("COMPLETED:20230103T110000Z\r\n"));
+        let back = EventCalendar::from_ics(&ics).unwrap();
+        assert!(back.tasks().eq(cal.tasks()));
+        assert_eq!(back.get_task(report_id).unwrap().priority(), Some(1));
+
+        assert!(cal.remove_task(report_id).is_some());
+        assert!(cal.get_task(report_id).is_none());
+        assert_eq!(cal.tasks().count(), 3);
+
+        // tasks given dates alone keep them as dates
+        let rent = Task::new("Rent".into())
+            .set_dates(Some(ymd(2023, 1, 2)), Some(ymd(2023, 1, 8)))
+            .unwrap();
+        assert!(rent.is_all_day());
+        assert_eq!(rent.due(), Some(at(8, 0)));
+        assert!(!rent.clone().set_due(Some(at(8, 17))).unwrap().is_all_day());
+        assert!(matches!(
+            Task::new("Rent".into()).set_dates(Some(ymd(2023, 1, 8)), Some(ymd(2023, 1, 8))),
+            Err(EventError::InvalidEndTime)
+        ));
+        let mut cal = EventCalendar::default();
+        cal.add_task(rent);
+        let ics = cal.to_ics_with_stamp(done);
+        assert!(ics.contains("DTSTART;VALUE=DATE:20230102\r\nDUE;VALUE=DATE:20230108\r\n"));
+        let back = EventCalendar::from_ics(&ics).unwrap();
+        assert!(back.tasks().eq(cal.tasks()));
+        let json = serde_json::to_string(&cal).unwrap();
+        let back: EventCalendar = serde_json::from_str(&json).unwrap();
+        assert!(back.tasks().next().unwrap().is_all_day());
+    }
+
+    #[test]
+    fn test_journals() {
+        let day = |d| NaiveDate::from_ymd_opt(2023, 1, d).unwrap();
+        let standup = Event::new("Standup".into(), &day(2));
+        let notes = Journal::new(day(2), "Standup notes".into())
+            .set_body("Blocked on review; ask, then merge\n\nNext: release".into())
+            .set_related_to(Some(*standup.id()));
+        let notes_id = *notes.id();
+
+        let mut cal = EventCalendar::default();
+        assert!(cal.add_event(standup));
+        assert!(cal.add_journal(notes.clone()));
+        assert!(cal.add_journal(Journal::new(day(4), "Retro".into())));
+        assert!(cal.add_journal(Journal::new(day(1), "Goals".into())));
+
+        let names = |journals: Vec<&Journal>| -> Vec<_> {
+            journals.iter().map(|j| j.name().to_string()).collect()
+        };
+        assert_eq!(
+            names(cal.journals().collect()),
+            ["Goals", "Standup notes", "Retro"]
+        );
+        assert_eq!(
+            names(cal.journals_in_range(day(1), day(4)).collect()),
+            ["Goals", "Standup notes"]
+        );
+        assert_eq!(names(cal.journals_on(day(4)).collect()), ["Retro"]);
+        assert_eq!(cal.journals_in_range(day(5), day(9)).count(), 0);
+
+        // replacing an entry with the same id moves it to its new date
+        assert!(!cal.add_journal(notes.clone().set_date(day(3))));
+        assert_eq!(cal.journals_on(day(2)).count(), 0);
+        assert_eq!(cal.get_journal(notes_id).unwrap().date(), day(3));
+        assert!(!cal.add_journal(notes.clone()));
+
+        // editing keeps the id and moves the entry too
+        let old = cal
+            .update_journal(notes_id, |j| Ok(j.set_date(day(3)).with_id(Uuid::new_v4())))
+            .unwrap();
+        assert_eq!(old, notes);
+        assert_eq!(names(cal.journals_on(day(3)).collect()), ["Standup notes"]);
+        assert!(matches!(
+            cal.update_journal(Uuid::new_v4(), Ok),
+            Err(EventError::NotFound(_))
+        ));
+        cal.update_journal(notes_id, |j| Ok(j.set_date(day(2))))
+            .unwrap();
+
+        let json = serde_json::to_string(&cal).unwrap();
+        let back: EventCalendar = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.get_journal(notes_id), Some(&notes));
+
+        // the body survives escaping in iCalendar text
+        let ics = cal.to_ics();
+        assert!(ics.contains("BEGIN:VJOURNAL"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20230102"));
+        let mut back = EventCalendar::default();
+        assert_eq!(back.import_ics(&ics).unwrap(), 4);
+        assert_eq!(back.get_journal(notes_id), Some(&notes));
+
+        // an entry without DTSTART is for the day it was written
+        let ics = "BEGIN:VCALENDAR\r\n\
+                   BEGIN:VJOURNAL\r\n\
+                   UID:undated@example.com\r\n\
+                   DTSTAMP:20230105T101500Z\r\n\
+                   SUMMARY:Undated\r\n\
+                   END:VJOURNAL\r\n\
+                   BEGIN:VJOURNAL\r\n\
+                   SUMMARY:Nowhere\r\n\
+                   END:VJOURNAL\r\n\
+                   END:VCALENDAR\r\n";
+        let mut back = EventCalendar::default();
+        assert_eq!(back.import_ics(ics).unwrap(), 1);
+        assert_eq!(names(back.journals_on(day(5)).collect()), ["Undated"]);
+
+        assert!(cal.remove_journal(notes_id).is_some());
+        assert!(cal.get_journal(notes_id).is_none());
+        assert_eq!(cal.journals().count(), 2);
+    }
+
+    #[test]
+    fn test_persistent_calendar() {
+        use chrono::Duration;
+
+        // storage whose commits can be made to fail
+        #[derive(Default)]
+        struct Flaky {
+            inner: MemoryStorage,
+            fail: bool,
+        }
+
+        impl Storage for Flaky {
+            fn load(&self) -> Result<Vec<Change>, EventError> {
+                self.inner.load()
+            }
+
+            fn commit(&mut self, changes: &[Change]) -> Result<(), EventError> {
+                if self.fail {
+                    return Err(EventError::Storage("disk full".into()));
+                }
+                self.inner.commit(changes)
+            }
+
+            fn events_in_range(
+                &self,
+                start: NaiveDateTime,
+                end: NaiveDateTime,
+            ) -> Result<Vec<Event>, EventError> {
+                self.inner.events_in_range(start, end)
+            }
+        }
+
+        let day = |d| NaiveDate::from_ymd_opt(2023, 1, d).unwrap();
+        let standup = Event::new("Standup".into(), &day(2))
+            .set_recurrence(Some("FREQ=DAILY;COUNT=5".parse().unwrap()));
+        let (id, second) = (*standup.id(), standup.start() + Duration::days(1));
+        let task = Task::new("Report".into());
+        let task_id = *task.id();
+
+        let mut cal = PersistentCalendar::open(Flaky::default()).unwrap();
+        cal.edit(|cal| {
+            cal.add_event(standup);
+            cal.override_occurrence(id, second, |mut evt| {
+                evt.set_name("Retro".into());
+                Ok(evt)
+            })?;
+            cal.add_task(task);
+            cal.add_journal(Journal::new(day(2), "Notes".into()));
+            Ok(())
+        })
+        .unwrap();
+
+        let names = |cal: &EventCalendar| -> Vec<_> {
+            cal.events_in_range(
+                day(1).and_hms_opt(0, 0, 0).unwrap(),
+                day(9).and_hms_opt(0, 0, 0).unwrap(),
+            )
+            .map(|occ| occ.name().to_string())
+            .collect()
+        };
+        let expected = ["Standup", "Retro", "Standup", "Standup", "Standup"];
+        assert_eq!(names(cal.calendar()), expected);
+
+        // a failed commit leaves the calendar as it was
+        cal.storage_mut().fail = true;
+        let renamed = cal.update(id, |mut evt| {
+            evt.set_name("Sync".into());
+            Ok(evt)
+        });
+        assert!(matches!(renamed, Err(EventError::Storage(_))));
+        assert!(cal.remove(id).is_err());
+        assert_eq!(names(cal.calendar()), expected);
+        cal.storage_mut().fail = false;
+
+        let cal = PersistentCalendar::open(cal.into_storage()).unwrap();
+        assert_eq!(names(cal.calendar()), expected);
+        assert_eq!(cal.calendar().get_task(task_id).unwrap().name(), "Report");
+        assert_eq!(cal.calendar().journals_on(day(2)).count(), 1);
+        let found = cal
+            .storage()
+            .events_in_range(second, second + Duration::hours(1));
+        assert_eq!(found.unwrap().len(), 2);
+
+        // splitting and removing a series reaches the storage too
+        let mut cal = cal;
+        let fourth = second + Duration::days(2);
+        cal.edit(|cal| {
+            cal.split_series(id, fourth, |mut evt| {
+                evt.set_name("Sync".into());
+                Ok(evt)
+            })
+        })
+        .unwrap();
+        let mut cal = PersistentCalendar::open(cal.into_storage()).unwrap();
+        assert_eq!(
+            names(cal.calendar()),
+            ["Standup", "Retro", "Standup", "Sync", "Sync"]
+        );
+        cal.remove(id).unwrap();
+        let cal = PersistentCalendar::open(cal.into_storage()).unwrap();
+        assert_eq!(names(cal.calendar()), ["Sync", "Sync"]);
+        assert_eq!(cal.calendar().overrides(id).count(), 0);
+
+        // the overlap policy is stored, unless the storage fails
+        let mut cal = cal;
+        cal.set_policy(OverlapPolicy::Reject).unwrap();
+        cal.storage_mut().fail = true;
+        assert!(cal.set_policy(OverlapPolicy::Warn).is_err());
+        assert_eq!(cal.calendar().policy(), OverlapPolicy::Reject);
+        cal.storage_mut().fail = false;
+        let cal = PersistentCalendar::open(cal.into_storage()).unwrap();
+        assert_eq!(cal.calendar().policy(), OverlapPolicy::Reject);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_storage() {
+        let path = std::env::temp_dir().join(format!("calib-{}.db", Uuid::new_v4()));
+        let day = |d| NaiveDate::from_ymd_opt(2023, 1, d).unwrap();
+        let at = |d| day(d).and_hms_opt(0, 0, 0).unwrap();
+
+        let storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(storage.schema_version().unwrap(), 2);
+        let standup = Event::new("Standup".into(), &day(2))
+            .set_recurrence(Some("FREQ=WEEKLY".parse().unwrap()));
+        let review = Event::new("Review".into(), &day(4));
+        let (standup_id, review_id) = (*standup.id(), *review.id());
+
+        let mut cal = PersistentCalendar::open(storage).unwrap();
+        cal.add_event(standup).unwrap();
+        cal.add_event(review).unwrap();
+        cal.edit(|cal| {
+            cal.cancel_occurrence(standup_id, at(9))?;
+            cal.add_task(Task::new("Report".into()));
+            Ok(())
+        })
+        .unwrap();
+        cal.set_policy(OverlapPolicy::Warn).unwrap();
+        drop(cal);
+
+        // everything is there when the database is opened again
+        let mut cal = PersistentCalendar::open(SqliteStorage::open(&path).unwrap()).unwrap();
+        assert_eq!(cal.calendar().policy(), OverlapPolicy::Warn);
+        let names: Vec<_> = cal
+            .calendar()
+            .events_in_range(at(1), at(17))
+            .map(|occ| occ.name().to_string())
+            .collect();
+        assert_eq!(names, ["Standup", "Review", "Standup"]);
+        assert_eq!(cal.calendar().tasks().count(), 1);
+
+        // only events that may overlap the range are read
+        let found = cal.storage().events_in_range(at(3), at(4)).unwrap();
+        assert_eq!(found.len(), 1);
+        let found = cal.storage().events_in_range(at(4), at(5)).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(
+            cal.storage().events_in_range(at(1), at(2)).unwrap().len(),
+            0
+        );
+
+        // a failed edit stores nothing
+        assert!(cal
+            .edit(|cal| {
+                cal.remove(review_id);
+                cal.try_remove("not an id")
+            })
+            .is_err());
+        assert!(cal.storage().get(review_id, None).unwrap().is_some());
+        cal.remove(review_id).unwrap();
+        assert!(cal.storage().get(review_id, None).unwrap().is_none());
+        drop(cal);
+
+        // a database from a newer version is refused
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.pragma_update(None, "user_version", 99).unwrap();
+        drop(conn);
+        assert!(matches!(
+            SqliteStorage::open(&path),
+            Err(EventError::Storage(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_log_storage() {
+        let dir = std::env::temp_dir().join(format!("calib-{}", Uuid::new_v4()));
+        let log = dir.join("calendar.log");
+        let day = |d| NaiveDate::from_ymd_opt(2023, 1, d).unwrap();
+        let open = || PersistentCalendar::open(LogStorage::open(&dir).unwrap()).unwrap();
+        let names = |cal: &PersistentCalendar<LogStorage>| -> Vec<_> {
+            cal.calendar()
+                .events()
+                .map(|evt| evt.name().to_string())
+                .collect()
+        };
+
+        let mut cal = open();
+        let standup = Event::new("Standup".into(), &day(2));
+        let id = *standup.id();
+        cal.add_event(standup).unwrap();
+        cal.add_event(Event::new("Review".into(), &day(3))).unwrap();
+        cal.edit(|cal| {
+            cal.add_task(Task::new("Report".into()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(cal.storage().log_records(), 3);
+        drop(cal);
+
+        // a torn write at the end is cut off, losing only that commit
+        let whole = std::fs::metadata(&log).unwrap().len();
+        let mut cal = open();
+        cal.add_event(Event::new("Retro".into(), &day(4))).unwrap();
+        drop(cal);
+        let file = std::fs::OpenOptions::new().write(true).open(&log).unwrap();
+        file.set_len(std::fs::metadata(&log).unwrap().len() - 5)
+            .unwrap();
+        drop(file);
+        let cal = open();
+        assert_eq!(names(&cal), ["Standup", "Review"]);
+        assert_eq!(std::fs::metadata(&log).unwrap().len(), whole);
+        drop(cal);
+
+        // as is a damaged record, or a tail of zeros
+        std::fs::write(&log, [std::fs::read(&log).unwrap(), vec![7; 20]].concat()).unwrap();
+        drop(open());
+        assert_eq!(std::fs::metadata(&log).unwrap().len(), whole);
+        std::fs::write(&log, [std::fs::read(&log).unwrap(), vec![0; 16]].concat()).unwrap();
+        let mut cal = open();
+        assert_eq!(std::fs::metadata(&log).unwrap().len(), whole);
+        assert_eq!(cal.calendar().tasks().count(), 1);
+        cal.remove(id).unwrap();
+        drop(cal);
+
+        // compacting empties the log into the snapshot
+        let cal = open();
+        assert_eq!(names(&cal), ["Review"]);
+        let mut storage = cal.into_storage();
+        storage.compact().unwrap();
+        assert_eq!(storage.log_records(), 0);
+        assert_eq!(std::fs::metadata(&log).unwrap().len(), 0);
+        let mut cal = PersistentCalendar::open(storage.set_compact_after(Some(2))).unwrap();
+        assert_eq!(names(&cal), ["Review"]);
+        assert_eq!(cal.calendar().tasks().count(), 1);
+
+        // and happens by itself once the log is long enough
+        cal.add_event(Event::new("Retro".into(), &day(4))).unwrap();
+        assert_eq!(cal.storage().log_records(), 1);
+        cal.add_event(Event::new("Planning".into(), &day(5)))
+            .unwrap();
+        assert_eq!(cal.storage().log_records(), 0);
+        drop(cal);
+        assert_eq!(names(&open()), ["Review", "Retro", "Planning"]);
+
+        // a damaged record with more after it is an error, not a torn tail
+        let mut cal = open();
+        cal.add_event(Event::new("Lunch".into(), &day(6))).unwrap();
+        cal.add_event(Event::new("Dinner".into(), &day(7))).unwrap();
+        drop(cal);
+        let mut bytes = std::fs::read(&log).unwrap();
+        let len = bytes.len();
+        bytes[10] ^= 0xff;
+        std::fs::write(&log, &bytes).unwrap();
+        assert!(matches!(
+            LogStorage::open(&dir),
+            Err(EventError::Storage(_))
+        ));
+        assert_eq!(std::fs::metadata(&log).unwrap().len(), len as u64);
+
+        // as is a whole record that can't be read, unless it is the last
+        let record = |data: &[u8]| {
+            let mut record = (data.len() as u32).to_le_bytes().to_vec();
+            record.extend(crc32fast::hash(data).to_le_bytes());
+            record.extend(data);
+            record
+        };
+        let unreadable = record(b"[{\"unknown\":1}]");
+        std::fs::write(&log, [unreadable.clone(), record(b"[]")].concat()).unwrap();
+        assert!(matches!(
+            LogStorage::open(&dir),
+            Err(EventError::Storage(_))
+        ));
+        std::fs::write(&log, &unreadable).unwrap();
+        assert_eq!(LogStorage::open(&dir).unwrap().log_records(), 0);
+        assert_eq!(std::fs::metadata(&log).unwrap().len(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        use chrono::Duration;
+
+        let day = |d| NaiveDate::from_ymd_opt(2023, 1, d).unwrap();
+        let names = |cal: &EventCalendar| -> Vec<_> {
+            cal.events_in_range(
+                day(1).and_hms_opt(0, 0, 0).unwrap(),
+                day(9).and_hms_opt(0, 0, 0).unwrap(),
+            )
+            .map(|occ| occ.name().to_string())
+            .collect()
+        };
+        let standup = Event::new("Standup".into(), &day(2))
+            .set_recurrence(Some("FREQ=DAILY;COUNT=3".parse().unwrap()));
+        let (id, second) = (*standup.id(), standup.start() + Duration::days(1));
+
+        let mut cal = EventCalendar::default();
+        assert!(!cal.can_undo());
+        cal.add_event(standup);
+        cal.override_occurrence(id, second, |mut evt| {
+            evt.set_name("Retro".into());
+            Ok(evt)
+        })
+        .unwrap();
+        cal.update(id, |mut evt| {
+            evt.set_name("Sync".into());
+            Ok(evt)
+        })
+        .unwrap();
+        cal.remove(id);
+        assert!(names(&cal).is_empty());
+
+        // each command is undone in turn, overrides and all
+        assert!(cal.undo());
+        assert_eq!(names(&cal), ["Sync", "Retro", "Sync"]);
+        assert!(cal.undo());
+        assert_eq!(names(&cal), ["Standup", "Retro", "Standup"]);
+        assert!(cal.undo());
+        assert_eq!(names(&cal), ["Standup", "Standup", "Standup"]);
+        assert!(cal.redo());
+        assert_eq!(names(&cal), ["Standup", "Retro", "Standup"]);
+
+        // a new command can't be followed by what was undone
+        cal.add_task(Task::new("Report".into()));
+        assert!(!cal.can_redo());
+        assert!(!cal.redo());
+        assert!(cal.undo());
+        assert_eq!(cal.tasks().count(), 0);
+
+        // a transaction is undone as a whole, or not made at all if it fails
+        cal.transaction(|cal| {
+            cal.split_series(id, second, |mut evt| {
+                evt.set_name("Sync".into());
+                Ok(evt)
+            })?;
+            cal.add_journal(Journal::new(day(2), "Notes".into()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(names(&cal), ["Standup", "Retro", "Sync"]);
+        let failed = cal.transaction(|cal| {
+            cal.remove(id);
+            cal.try_remove("not an id")
+        });
+        assert!(failed.is_err());
+        assert_eq!(names(&cal), ["Standup", "Retro", "Sync"]);
+        assert!(cal.undo());
+        assert_eq!(names(&cal), ["Standup", "Retro", "Standup"]);
+        assert_eq!(cal.journals().count(), 0);
+
+        // only the latest commands are kept
+        cal.set_history_limit(2);
+        for d in 3..6 {
+            cal.add_event(Event::new(format!("Day {d}"), &day(d)));
+        }
+        assert!(cal.undo() && cal.undo());
+        assert!(!cal.undo());
+        assert_eq!(cal.events().count(), 2);
+        cal.set_history_limit(0);
+        cal.remove(id);
+        assert!(!cal.can_undo());
+
+        // undoing an edit reaches the storage
+        let mut cal = PersistentCalendar::open(MemoryStorage::default()).unwrap();
+        cal.add_event(Event::new("Review".into(), &day(2))).unwrap();
+        cal.add_event(Event::new("Retro".into(), &day(3))).unwrap();
+        assert!(cal.undo().unwrap());
+        let mut cal = PersistentCalendar::open(cal.into_storage()).unwrap();
+        assert_eq!(names(cal.calendar()), ["Review"]);
+        assert!(!cal.undo().unwrap());
+    }
 }
@@ -0,0 +1,255 @@
+use chrono::NaiveDateTime;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use super::{Change, Event, EventError, MemoryStorage, Storage};
+
+/// the file changes are appended to
+const LOG_FILE: &str = "calendar.log";
+/// the file holding everything in the calendar as of the last compaction
+const SNAPSHOT_FILE: &str = "calendar.snapshot";
+/// where a snapshot is written before it replaces the old one
+const SNAPSHOT_TMP_FILE: &str = "calendar.snapshot.tmp";
+/// each record starts with the length of its contents and their CRC-32
+const HEADER_LEN: usize = 8;
+/// how many records the log holds before it is compacted, by default
+const COMPACT_AFTER: usize = 1000;
+
+/// report an error from the file system
+fn storage_error(err: io::Error) -> EventError {
+    EventError::Storage(err.to_string())
+}
+
+/// Storage in a directory holding an append-only log of changes and a
+/// snapshot. Each commit is appended to the log as a single checksummed
+/// record and everything is read back into memory when the storage is
+/// opened. Once the log holds enough records it is compacted into the
+/// snapshot and emptied.
+///
+/// A record torn by a crash while it was being written is cut off the end of
+/// the log when it is opened, so the calendar is as it was after the last
+/// complete commit. A damaged record anywhere else fails to open with
+/// [`EventError::Storage`] rather than losing the commits after it
+///
+/// # Examples
+/// ```
+/// use calib::{Event, LogStorage, PersistentCalendar};
+/// use chrono::NaiveDate;
+///
+/// let dir = std::env::temp_dir().join(format!("calib-doc-{}", uuid::Uuid::new_v4()));
+/// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+///
+/// let mut cal = PersistentCalendar::open(LogStorage::open(&dir).unwrap()).unwrap();
+/// cal.add_event(Event::new("Standup".into(), &date)).unwrap();
+/// drop(cal);
+///
+/// let cal = PersistentCalendar::open(LogStorage::open(&dir).unwrap()).unwrap();
+/// assert_eq!(cal.calendar().first_event().unwrap().name(), "Standup");
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub struct LogStorage {
+    dir: PathBuf,
+    log: File,
+    // the length of the complete records in the log
+    len: u64,
+    records: usize,
+    compact_after: Option<usize>,
+    items: MemoryStorage,
+}
+
+impl LogStorage {
+    /// open the storage in `dir`, creating the directory if it does not
+    /// exist, and replay its snapshot and log
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, EventError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(storage_error)?;
+
+        let mut items = MemoryStorage::default();
+        match fs::read(dir.join(SNAPSHOT_FILE)) {
+            Ok(bytes) => {
+                // snapshots are renamed into place once complete, so a
+                // damaged one is not a torn write and cannot be cut off
+                let (records, len) = read_records(&bytes)?;
+                if records.len() != 1 || len != bytes.len() {
+                    return Err(EventError::Storage(format!(
+                        "snapshot in {} is damaged",
+                        dir.display()
+                    )));
+                }
+                items.commit(&records[0])?;
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(storage_error(err)),
+        }
+
+        let mut log = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(dir.join(LOG_FILE))
+            .map_err(storage_error)?;
+        let mut bytes = Vec::new();
+        log.read_to_end(&mut bytes).map_err(storage_error)?;
+
+        // changes already in the snapshot may be replayed again if a
+        // compaction was cut short, which leaves them as they were
+        let (records, len) = read_records(&bytes)?;
+        for changes in &records {
+            items.commit(changes)?;
+        }
+        if len < bytes.len() {
+            log.set_len(len as u64).map_err(storage_error)?;
+            log.sync_data().map_err(storage_error)?;
+        }
+
+        Ok(LogStorage {
+            dir,
+            log,
+            len: len as u64,
+            records: records.len(),
+            compact_after: Some(COMPACT_AFTER),
+            items,
+        })
+    }
+
+    /// Set/Change/Clear how many records the log holds before it is
+    /// compacted, 1000 by default. With None it is only compacted by
+    /// [`LogStorage::compact`]
+    pub fn set_compact_after(self, records: Option<usize>) -> Self {
+        LogStorage {
+            compact_after: records,
+            ..self
+        }
+    }
+
+    /// returns how many records have been added to the log since it was
+    /// last compacted
+    pub fn log_records(&self) -> usize {
+        self.records
+    }
+
+    /// write everything to a new snapshot and empty the log
+    pub fn compact(&mut self) -> Result<(), EventError> {
+        let record = to_record(&self.items.load()?)?;
+        let tmp = self.dir.join(SNAPSHOT_TMP_FILE);
+        let mut file = File::create(&tmp).map_err(storage_error)?;
+        file.write_all(&record).map_err(storage_error)?;
+        file.sync_all().map_err(storage_error)?;
+        fs::rename(&tmp, self.dir.join(SNAPSHOT_FILE)).map_err(storage_error)?;
+        // the rename has to reach the disk before the log is emptied, or a
+        // crash could leave the old snapshot with an empty log. Windows can't
+        // open directories as files and makes the rename durable by itself
+        #[cfg(unix)]
+        File::open(&self.dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(storage_error)?;
+
+        self.log.set_len(0).map_err(storage_error)?;
+        self.log.sync_data().map_err(storage_error)?;
+        self.len = 0;
+        self.records = 0;
+        Ok(())
+    }
+
+    /// append a record to the log, cutting off whatever part of it was
+    /// written if it could not all be
+    fn append(&mut self, record: &[u8]) -> io::Result<()> {
+        let written = self
+            .log
+            .seek(SeekFrom::Start(self.len))
+            .and_then(|_| self.log.write_all(record))
+            .and_then(|()| self.log.sync_data());
+        if written.is_err() {
+            let _ = self.log.set_len(self.len);
+        }
+        written
+    }
+}
+
+impl Storage for LogStorage {
+    fn load(&self) -> Result<Vec<Change>, EventError> {
+        self.items.load()
+    }
+
+    fn commit(&mut self, changes: &[Change]) -> Result<(), EventError> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let record = to_record(changes)?;
+        self.append(&record).map_err(storage_error)?;
+        self.len += record.len() as u64;
+        self.records += 1;
+        self.items.commit(changes)?;
+
+        // the changes are safe in the log by now, if compacting fails it is
+        // tried again after the next commit
+        if self.compact_after.is_some_and(|n| self.records >= n) {
+            let _ = self.compact();
+        }
+        Ok(())
+    }
+
+    fn events_in_range(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<Event>, EventError> {
+        self.items.events_in_range(start, end)
+    }
+}
+
+/// a record holding `changes`, as it is written to a file
+fn to_record(changes: &[Change]) -> Result<Vec<u8>, EventError> {
+    let data = serde_json::to_vec(changes).map_err(|e| EventError::Json(e.to_string()))?;
+    let len = u32::try_from(data.len())
+        .map_err(|_| EventError::Storage(format!("record of {} bytes", data.len())))?;
+
+    let mut record = Vec::with_capacity(HEADER_LEN + data.len());
+    record.extend_from_slice(&len.to_le_bytes());
+    record.extend_from_slice(&crc32fast::hash(&data).to_le_bytes());
+    record.extend_from_slice(&data);
+    Ok(record)
+}
+
+/// read records from the start of `bytes`, returning them and how many bytes
+/// they take up. A record cut short, damaged or unreadable at the very end of
+/// `bytes`, or followed by nothing but zeros, was torn while it was being
+/// written and is left out. Any other damaged record is an error since the
+/// records after it can't be trusted or skipped
+fn read_records(bytes: &[u8]) -> Result<(Vec<Vec<Change>>, usize), EventError> {
+    let mut records = Vec::new();
+    let mut pos = 0;
+    while let Some(header) = bytes.get(pos..pos + HEADER_LEN) {
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let start = pos + HEADER_LEN;
+        let Some(data) = bytes.get(start..start + len) else {
+            break;
+        };
+        // a crash can leave the end of the file filled with zeros, which
+        // would pass as an empty record. No record is ever empty
+        let torn = start + len == bytes.len() || bytes[pos..].iter().all(|b| *b == 0);
+        if len == 0 || crc32fast::hash(data) != crc {
+            if torn {
+                break;
+            }
+            return Err(EventError::Storage(format!(
+                "record at byte {pos} is damaged"
+            )));
+        }
+        let changes = match serde_json::from_slice(data) {
+            Ok(changes) => changes,
+            Err(_) if torn => break,
+            Err(e) => {
+                return Err(EventError::Storage(format!(
+                    "record at byte {pos} can't be read: {e}"
+                )))
+            }
+        };
+        records.push(changes);
+        pos = start + len;
+    }
+    Ok((records, pos))
+}
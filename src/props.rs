@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use super::EventError;
+
+/// Whether an event is going ahead, the STATUS of a VEVENT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Tentative,
+    Confirmed,
+    Cancelled,
+}
+
+impl Status {
+    /// returns the name RFC 5545 uses for the status
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Tentative => "TENTATIVE",
+            Status::Confirmed => "CONFIRMED",
+            Status::Cancelled => "CANCELLED",
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Status {
+    type Err = EventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "TENTATIVE" => Ok(Status::Tentative),
+            "CONFIRMED" => Ok(Status::Confirmed),
+            "CANCELLED" => Ok(Status::Cancelled),
+            _ => Err(EventError::InvalidProperty("STATUS", s.to_string())),
+        }
+    }
+}
+
+/// Whether an event takes up time, the TRANSP of a VEVENT
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Transparency {
+    /// the time is busy (the default)
+    #[default]
+    Opaque,
+    /// the time is still free, like a reminder or a holiday
+    Transparent,
+}
+
+impl Transparency {
+    /// returns the name RFC 5545 uses for the transparency
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Transparency::Opaque => "OPAQUE",
+            Transparency::Transparent => "TRANSPARENT",
+        }
+    }
+
+    /// returns true for the default, busy time
+    pub fn is_opaque(&self) -> bool {
+        *self == Transparency::Opaque
+    }
+}
+
+impl fmt::Display for Transparency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Transparency {
+    type Err = EventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "OPAQUE" => Ok(Transparency::Opaque),
+            "TRANSPARENT" => Ok(Transparency::Transparent),
+            _ => Err(EventError::InvalidProperty("TRANSP", s.to_string())),
+        }
+    }
+}
+
+/// the highest PRIORITY, 1 is the most important and 9 the least, 0 means
+/// no priority has been given
+pub(crate) const MAX_PRIORITY: u8 = 9;
+
+/// check that `uri` looks like an absolute URI, a scheme followed by a colon
+/// and something after it, e.g. `https://example.com` or `mailto:a@b.c`
+pub(crate) fn check_uri(property: &'static str, uri: &str) -> Result<(), EventError> {
+    let invalid = || EventError::InvalidProperty(property, uri.to_string());
+    let (scheme, rest) = uri.split_once(':').ok_or_else(invalid)?;
+    let mut chars = scheme.chars();
+    let scheme_ok = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    if !scheme_ok || rest.is_empty() || uri.chars().any(|c| c.is_whitespace()) {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+/// the calendar address of a person, a bare email address is turned into a
+/// `mailto:` URI
+pub(crate) fn cal_address(property: &'static str, address: &str) -> Result<String, EventError> {
+    let address = match address.contains(':') || !address.contains('@') {
+        true => address.to_string(),
+        false => format!("mailto:{address}"),
+    };
+    check_uri(property, &address)?;
+    Ok(address)
+}
+
+/// priorities run from 0 (none given) to 9
+pub(crate) fn check_priority(priority: u8) -> Result<(), EventError> {
+    if priority > MAX_PRIORITY {
+        return Err(EventError::InvalidProperty(
+            "PRIORITY",
+            priority.to_string(),
+        ));
+    }
+    Ok(())
+}
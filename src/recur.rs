@@ -0,0 +1,763 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use super::EventError;
+
+/// How often a recurrence rule repeats, the FREQ part of an RRULE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Frequency {
+    Secondly,
+    Minutely,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Frequency::Secondly => "SECONDLY",
+            Frequency::Minutely => "MINUTELY",
+            Frequency::Hourly => "HOURLY",
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        }
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = EventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "SECONDLY" => Frequency::Secondly,
+            "MINUTELY" => Frequency::Minutely,
+            "HOURLY" => Frequency::Hourly,
+            "DAILY" => Frequency::Daily,
+            "WEEKLY" => Frequency::Weekly,
+            "MONTHLY" => Frequency::Monthly,
+            "YEARLY" => Frequency::Yearly,
+            _ => return Err(invalid(format!("unknown frequency {s}"))),
+        })
+    }
+}
+
+/// A BYDAY entry, a weekday optionally limited to the nth occurrence of that
+/// weekday within the month or year, e.g. `2MO` or `-1FR`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WeekdayNum {
+    ordinal: i8,
+    weekday: Weekday,
+}
+
+impl WeekdayNum {
+    /// every occurrence of the weekday
+    pub fn every(weekday: Weekday) -> Self {
+        WeekdayNum {
+            ordinal: 0,
+            weekday,
+        }
+    }
+
+    /// only the nth occurrence of the weekday, counting from the end of the
+    /// month or year when n is negative
+    pub fn nth(n: i8, weekday: Weekday) -> Result<Self, EventError> {
+        if n == 0 || !(-53..=53).contains(&n) {
+            return Err(invalid(format!("invalid weekday ordinal {n}")));
+        }
+        Ok(WeekdayNum {
+            ordinal: n,
+            weekday,
+        })
+    }
+
+    /// returns the weekday
+    pub fn weekday(&self) -> Weekday {
+        self.weekday
+    }
+
+    /// returns the ordinal, None meaning every occurrence of the weekday
+    pub fn ordinal(&self) -> Option<i8> {
+        (self.ordinal != 0).then_some(self.ordinal)
+    }
+}
+
+impl fmt::Display for WeekdayNum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ordinal != 0 {
+            write!(f, "{}", self.ordinal)?;
+        }
+        f.write_str(weekday_str(self.weekday))
+    }
+}
+
+impl FromStr for WeekdayNum {
+    type Err = EventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if !s.is_ascii() {
+            return Err(invalid(format!("invalid weekday {s}")));
+        }
+        let split = s.len().saturating_sub(2);
+        let weekday = parse_weekday(&s[split..])?;
+        match &s[..split] {
+            "" => Ok(WeekdayNum::every(weekday)),
+            n => WeekdayNum::nth(parse_num(n)?, weekday),
+        }
+    }
+}
+
+/// An RFC 5545 recurrence rule
+///
+/// # Examples
+/// ```
+/// use calib::RRule;
+///
+/// let rule: RRule = "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1".parse().unwrap();
+/// assert_eq!(rule.to_string(), "FREQ=MONTHLY;BYDAY=MO,TU,WE,TH,FR;BYSETPOS=-1");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RRule {
+    freq: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<NaiveDateTime>,
+    by_day: Vec<WeekdayNum>,
+    by_month_day: Vec<i8>,
+    by_month: Vec<u8>,
+    by_set_pos: Vec<i16>,
+    wkst: Weekday,
+}
+
+impl RRule {
+    /// Create a rule repeating every period of the given frequency forever
+    pub fn new(freq: Frequency) -> Self {
+        RRule {
+            freq,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+            by_set_pos: Vec::new(),
+            wkst: Weekday::Mon,
+        }
+    }
+
+    /// returns the frequency of the rule
+    pub fn frequency(&self) -> Frequency {
+        self.freq
+    }
+
+    /// returns the number of periods between each repetition
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// returns the total number of occurrences, if limited by COUNT
+    pub fn count(&self) -> Option<u32> {
+        self.count
+    }
+
+    /// returns the last possible occurrence start, if limited by UNTIL
+    pub fn until(&self) -> Option<NaiveDateTime> {
+        self.until
+    }
+
+    /// Set/Change how many periods pass between each repetition
+    pub fn set_interval(self, interval: u32) -> Result<Self, EventError> {
+        if interval == 0 {
+            return Err(invalid("INTERVAL must be at least 1".into()));
+        }
+        Ok(RRule { interval, ..self })
+    }
+
+    /// Limit the rule to a number of occurrences, cannot be combined with UNTIL
+    pub fn set_count(self, count: u32) -> Result<Self, EventError> {
+        if self.until.is_some() {
+            return Err(invalid("COUNT and UNTIL cannot both be set".into()));
+        }
+        if count == 0 {
+            return Err(invalid("COUNT must be at least 1".into()));
+        }
+        Ok(RRule {
+            count: Some(count),
+            ..self
+        })
+    }
+
+    /// Limit the rule to occurrences starting at or before `until`,
+    /// cannot be combined with COUNT
+    pub fn set_until(self, until: NaiveDateTime) -> Result<Self, EventError> {
+        if self.count.is_some() {
+            return Err(invalid("COUNT and UNTIL cannot both be set".into()));
+        }
+        Ok(RRule {
+            until: Some(until),
+            ..self
+        })
+    }
+
+    /// Remove any COUNT or UNTIL limit so the rule repeats forever
+    pub fn unbounded(self) -> Self {
+        RRule {
+            count: None,
+            until: None,
+            ..self
+        }
+    }
+
+    /// Set/Change the BYDAY part of the rule
+    pub fn set_by_day(self, by_day: Vec<WeekdayNum>) -> Self {
+        RRule { by_day, ..self }
+    }
+
+    /// Set/Change the BYMONTHDAY part of the rule, negative days count
+    /// back from the end of the month
+    pub fn set_by_month_day(self, by_month_day: Vec<i8>) -> Result<Self, EventError> {
+        if let Some(day) = by_month_day
+            .iter()
+            .find(|d| **d == 0 || !(-31..=31).contains(*d))
+        {
+            return Err(invalid(format!("invalid BYMONTHDAY {day}")));
+        }
+        Ok(RRule {
+            by_month_day,
+            ..self
+        })
+    }
+
+    /// Set/Change the BYMONTH part of the rule
+    pub fn set_by_month(self, by_month: Vec<u8>) -> Result<Self, EventError> {
+        if let Some(month) = by_month.iter().find(|m| !(1..=12).contains(*m)) {
+            return Err(invalid(format!("invalid BYMONTH {month}")));
+        }
+        Ok(RRule { by_month, ..self })
+    }
+
+    /// Set/Change the BYSETPOS part of the rule, picking the nth candidates
+    /// of each period
+    pub fn set_by_set_pos(self, by_set_pos: Vec<i16>) -> Result<Self, EventError> {
+        if let Some(pos) = by_set_pos
+            .iter()
+            .find(|p| **p == 0 || !(-366..=366).contains(*p))
+        {
+            return Err(invalid(format!("invalid BYSETPOS {pos}")));
+        }
+        Ok(RRule { by_set_pos, ..self })
+    }
+
+    /// Set/Change the day weeks start on, only affects WEEKLY rules
+    pub fn set_week_start(self, wkst: Weekday) -> Self {
+        RRule { wkst, ..self }
+    }
+
+    /// return an iterator over the start of every occurrence of a series
+    /// starting at `dtstart`. DTSTART is always the first occurrence and
+    /// counts towards COUNT, even if it does not match the rule
+    pub fn occurrences(&self, dtstart: NaiveDateTime) -> RuleIter<'_> {
+        RuleIter {
+            rule: self,
+            dtstart,
+            period: 0,
+            empty_periods: 0,
+            emitted: 0,
+            buffer: VecDeque::from([dtstart]),
+            done: false,
+        }
+    }
+}
+
+impl fmt::Display for RRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T: fmt::Display>(items: &[T]) -> String {
+            items.iter().map(T::to_string).collect::<Vec<_>>().join(",")
+        }
+
+        write!(f, "FREQ={}", self.freq.as_str())?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={count}")?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%dT%H%M%S"))?;
+        }
+        if !self.by_day.is_empty() {
+            write!(f, ";BYDAY={}", list(&self.by_day))?;
+        }
+        if !self.by_month_day.is_empty() {
+            write!(f, ";BYMONTHDAY={}", list(&self.by_month_day))?;
+        }
+        if !self.by_month.is_empty() {
+            write!(f, ";BYMONTH={}", list(&self.by_month))?;
+        }
+        if !self.by_set_pos.is_empty() {
+            write!(f, ";BYSETPOS={}", list(&self.by_set_pos))?;
+        }
+        if self.wkst != Weekday::Mon {
+            write!(f, ";WKST={}", weekday_str(self.wkst))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RRule {
+    type Err = EventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn list<T>(
+            value: &str,
+            parse: impl Fn(&str) -> Result<T, EventError>,
+        ) -> Result<Vec<T>, EventError> {
+            value.split(',').map(parse).collect()
+        }
+
+        let s = s.strip_prefix("RRULE:").unwrap_or(s);
+        let mut parts = Vec::new();
+        let mut freq = None;
+        for part in s.split(';').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("expected NAME=VALUE, found {part}")))?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => freq = Some(value.to_ascii_uppercase().parse()?),
+                key => parts.push((key.to_string(), value.to_ascii_uppercase())),
+            }
+        }
+
+        let freq = freq.ok_or_else(|| invalid("FREQ is required".into()))?;
+        parts
+            .into_iter()
+            .try_fold(RRule::new(freq), |rule, (key, value)| match key.as_str() {
+                "INTERVAL" => rule.set_interval(parse_num(&value)?),
+                "COUNT" => rule.set_count(parse_num(&value)?),
+                "UNTIL" => rule.set_until(parse_until(&value)?),
+                "BYDAY" => Ok(rule.set_by_day(list(&value, str::parse)?)),
+                "BYMONTHDAY" => rule.set_by_month_day(list(&value, parse_num)?),
+                "BYMONTH" => rule.set_by_month(list(&value, parse_num)?),
+                "BYSETPOS" => rule.set_by_set_pos(list(&value, parse_num)?),
+                "WKST" => Ok(rule.set_week_start(parse_weekday(&value)?)),
+                _ => Err(invalid(format!("unsupported rule part {key}"))),
+            })
+    }
+}
+
+// rules are ordered by their textual form so Event can keep deriving Ord
+impl PartialOrd for RRule {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RRule {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_string().cmp(&other.to_string())
+    }
+}
+
+impl Serialize for RRule {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for RRule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+// a rule that cannot produce anything, like the 30th of February, would
+// otherwise loop forever looking for its next occurrence
+const MAX_EMPTY_PERIODS: u32 = 10_000;
+
+// the Gregorian calendar repeats every 400 years, so a day matching the
+// BYxxx parts of a rule is found within this many days or never
+const GREGORIAN_CYCLE_DAYS: usize = 146_097;
+
+/// Iterator over the occurrence start times produced by [`RRule::occurrences`]
+pub struct RuleIter<'a> {
+    rule: &'a RRule,
+    dtstart: NaiveDateTime,
+    period: i64,
+    empty_periods: u32,
+    emitted: u32,
+    buffer: VecDeque<NaiveDateTime>,
+    done: bool,
+}
+
+impl RuleIter<'_> {
+    /// skip ahead to the occurrences starting around `from`, some of those
+    /// before it may still be returned. A rule with COUNT has to count every
+    /// occurrence from the start, so it can't skip any
+    pub(crate) fn seek(mut self, from: NaiveDateTime) -> Self {
+        if self.rule.count.is_some() || self.period > 0 || from <= self.dtstart {
+            return self;
+        }
+        let (start, interval) = (self.dtstart, self.rule.interval as i64);
+        let elapsed = from.signed_duration_since(start);
+        let periods = match self.rule.freq {
+            Frequency::Yearly => (from.year() - start.year()) as i64,
+            Frequency::Monthly => {
+                (from.year() - start.year()) as i64 * 12 + from.month0() as i64
+                    - start.month0() as i64
+            }
+            Frequency::Weekly => elapsed.num_weeks(),
+            Frequency::Daily => elapsed.num_days(),
+            Frequency::Hourly => elapsed.num_hours(),
+            Frequency::Minutely => elapsed.num_minutes(),
+            Frequency::Secondly => elapsed.num_seconds(),
+        };
+        // one period early, as a period may start before its occurrences
+        let period = periods / interval - 1;
+        if period > 0 {
+            self.period = period;
+            self.buffer.clear();
+        }
+        self
+    }
+
+    /// the period to expand after the empty period `n`. Rules repeating
+    /// daily or more often skip straight to the next day their BYxxx parts
+    /// match rather than stepping through every period of the days between.
+    /// None if there is no such day
+    fn next_period(&self, n: i64) -> Option<i64> {
+        let unit = match self.rule.freq {
+            Frequency::Daily => 86_400,
+            Frequency::Hourly => 3_600,
+            Frequency::Minutely => 60,
+            Frequency::Secondly => 1,
+            _ => return Some(n + 1),
+        };
+        let step = unit * self.rule.interval as i64;
+        let current = self
+            .dtstart
+            .checked_add_signed(Duration::try_seconds(n.checked_mul(step)?)?)?;
+        if self.matches_filters(&current.date()) {
+            return Some(n + 1);
+        }
+
+        let day = current
+            .date()
+            .iter_days()
+            .skip(1)
+            .take(GREGORIAN_CYCLE_DAYS)
+            .find(|day| self.matches_filters(day))?;
+        let wait = day
+            .and_time(NaiveTime::MIN)
+            .signed_duration_since(self.dtstart)
+            .num_seconds();
+        // the first period starting on or after that day
+        Some(((wait + step - 1).div_euclid(step)).max(n + 1))
+    }
+
+    /// all candidates for the nth period of the rule, in order, before
+    /// COUNT/UNTIL are applied
+    fn expand(&self, n: i64) -> Option<Vec<NaiveDateTime>> {
+        let rule = self.rule;
+        let start = self.dtstart.date();
+        let step = n.checked_mul(rule.interval as i64)?;
+        let time = self.dtstart.time();
+
+        let mut candidates = match rule.freq {
+            Frequency::Yearly => {
+                let year = i32::try_from(start.year() as i64 + step).ok()?;
+                NaiveDate::from_ymd_opt(year, 1, 1)?;
+                self.year_dates(year)
+            }
+            Frequency::Monthly => {
+                let months = start.year() as i64 * 12 + start.month0() as i64 + step;
+                let year = i32::try_from(months.div_euclid(12)).ok()?;
+                let month = months.rem_euclid(12) as u32 + 1;
+                NaiveDate::from_ymd_opt(year, month, 1)?;
+                if rule.by_month.is_empty() || rule.by_month.contains(&(month as u8)) {
+                    self.month_dates(year, month)
+                } else {
+                    Vec::new()
+                }
+            }
+            Frequency::Weekly => {
+                let offset = days_between(rule.wkst, start.weekday());
+                let week = start
+                    .checked_sub_signed(Duration::days(offset))?
+                    .checked_add_signed(Duration::try_weeks(step)?)?;
+                let mut weekdays: Vec<_> = rule.by_day.iter().map(|d| d.weekday).collect();
+                if weekdays.is_empty() {
+                    weekdays.push(start.weekday());
+                }
+                weekdays
+                    .into_iter()
+                    .filter_map(|wd| {
+                        week.checked_add_signed(Duration::days(days_between(rule.wkst, wd)))
+                    })
+                    .filter(|date| self.matches_month(date) && self.matches_month_day(date))
+                    .collect()
+            }
+            Frequency::Daily => {
+                let date = start.checked_add_signed(Duration::try_days(step)?)?;
+                vec![date]
+                    .into_iter()
+                    .filter(|date| self.matches_filters(date))
+                    .collect()
+            }
+            Frequency::Hourly | Frequency::Minutely | Frequency::Secondly => {
+                let delta = match rule.freq {
+                    Frequency::Hourly => Duration::try_hours(step)?,
+                    Frequency::Minutely => Duration::try_minutes(step)?,
+                    _ => Duration::try_seconds(step)?,
+                };
+                let dt = self.dtstart.checked_add_signed(delta)?;
+                return Some(
+                    self.set_pos(
+                        vec![dt]
+                            .into_iter()
+                            .filter(|dt| self.matches_filters(&dt.date()))
+                            .collect(),
+                    ),
+                );
+            }
+        }
+        .into_iter()
+        .map(|date| NaiveDateTime::new(date, time))
+        .collect::<Vec<_>>();
+
+        candidates.sort();
+        candidates.dedup();
+        Some(self.set_pos(candidates))
+    }
+
+    /// candidate dates within a single year
+    fn year_dates(&self, year: i32) -> Vec<NaiveDate> {
+        let rule = self.rule;
+        if !rule.by_month.is_empty() {
+            rule.by_month
+                .iter()
+                .flat_map(|m| self.month_dates(year, *m as u32))
+                .collect()
+        } else if !rule.by_month_day.is_empty() {
+            (1..=12).flat_map(|m| self.month_dates(year, m)).collect()
+        } else if !rule.by_day.is_empty() {
+            let first = NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+            let last = NaiveDate::from_ymd_opt(year, 12, 31).unwrap();
+            self.expand_by_day(first, last)
+        } else {
+            let start = self.dtstart.date();
+            NaiveDate::from_ymd_opt(year, start.month(), start.day())
+                .into_iter()
+                .collect()
+        }
+    }
+
+    /// candidate dates within a single month
+    fn month_dates(&self, year: i32, month: u32) -> Vec<NaiveDate> {
+        let rule = self.rule;
+        let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+        let last = last_day_of_month(first);
+
+        if !rule.by_month_day.is_empty() {
+            rule.by_month_day
+                .iter()
+                .filter_map(|day| match *day {
+                    d if d > 0 => first.with_day(d as u32),
+                    d => last.checked_sub_signed(Duration::days(-(d as i64) - 1)),
+                })
+                .filter(|date| date.month() == month && self.matches_weekday(date))
+                .collect()
+        } else if !rule.by_day.is_empty() {
+            self.expand_by_day(first, last)
+        } else {
+            // months without the start day, like the 31st, are skipped
+            first.with_day(self.dtstart.day()).into_iter().collect()
+        }
+    }
+
+    /// expand BYDAY within [first, last], where ordinals count from either end
+    fn expand_by_day(&self, first: NaiveDate, last: NaiveDate) -> Vec<NaiveDate> {
+        self.rule
+            .by_day
+            .iter()
+            .flat_map(|wd| {
+                let offset = days_between(first.weekday(), wd.weekday);
+                let days: Vec<_> = first
+                    .checked_add_signed(Duration::days(offset))
+                    .into_iter()
+                    .flat_map(|d| d.iter_weeks())
+                    .take_while(|d| *d <= last)
+                    .collect();
+                match wd.ordinal {
+                    0 => days,
+                    n if n > 0 => days.get(n as usize - 1).copied().into_iter().collect(),
+                    n => days
+                        .len()
+                        .checked_sub(n.unsigned_abs() as usize)
+                        .map(|i| days[i])
+                        .into_iter()
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// apply BYSETPOS to the ordered candidates of a period
+    fn set_pos(&self, candidates: Vec<NaiveDateTime>) -> Vec<NaiveDateTime> {
+        if self.rule.by_set_pos.is_empty() {
+            return candidates;
+        }
+
+        let len = candidates.len() as i64;
+        let mut picked: Vec<_> = self
+            .rule
+            .by_set_pos
+            .iter()
+            .filter_map(|pos| {
+                let i = if *pos > 0 {
+                    *pos as i64 - 1
+                } else {
+                    len + *pos as i64
+                };
+                (0..len).contains(&i).then(|| candidates[i as usize])
+            })
+            .collect();
+        picked.sort();
+        picked.dedup();
+        picked
+    }
+
+    fn matches_filters(&self, date: &NaiveDate) -> bool {
+        self.matches_month(date) && self.matches_month_day(date) && self.matches_weekday(date)
+    }
+
+    fn matches_month(&self, date: &NaiveDate) -> bool {
+        self.rule.by_month.is_empty() || self.rule.by_month.contains(&(date.month() as u8))
+    }
+
+    fn matches_month_day(&self, date: &NaiveDate) -> bool {
+        let days_in_month = last_day_of_month(*date).day() as i8;
+        self.rule.by_month_day.is_empty()
+            || self.rule.by_month_day.iter().any(|d| {
+                let day = if *d > 0 { *d } else { days_in_month + *d + 1 };
+                day == date.day() as i8
+            })
+    }
+
+    fn matches_weekday(&self, date: &NaiveDate) -> bool {
+        self.rule.by_day.is_empty() || self.rule.by_day.iter().any(|d| d.weekday == date.weekday())
+    }
+}
+
+impl Iterator for RuleIter<'_> {
+    type Item = NaiveDateTime;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(dt) = self.buffer.pop_front() {
+                let past_until = self.rule.until.is_some_and(|until| dt > until);
+                let past_count = self.rule.count.is_some_and(|count| self.emitted >= count);
+                if past_until || past_count {
+                    self.done = true;
+                    self.buffer.clear();
+                    return None;
+                }
+                self.emitted += 1;
+                return Some(dt);
+            }
+
+            if self.done {
+                return None;
+            }
+
+            match self.expand(self.period) {
+                Some(candidates) => {
+                    // DTSTART was returned first
+                    self.buffer
+                        .extend(candidates.into_iter().filter(|dt| *dt > self.dtstart));
+                    if self.buffer.is_empty() {
+                        self.empty_periods += 1;
+                        match self.next_period(self.period) {
+                            Some(next) if self.empty_periods <= MAX_EMPTY_PERIODS => {
+                                self.period = next;
+                            }
+                            _ => self.done = true,
+                        }
+                    } else {
+                        self.period += 1;
+                        self.empty_periods = 0;
+                    }
+                }
+                // ran past the dates chrono can represent
+                None => self.done = true,
+            }
+        }
+    }
+}
+
+fn invalid(msg: String) -> EventError {
+    EventError::InvalidRecurrence(msg)
+}
+
+fn parse_num<T: FromStr>(s: &str) -> Result<T, EventError> {
+    s.parse()
+        .map_err(|_| invalid(format!("expected a number, found {s}")))
+}
+
+fn parse_until(s: &str) -> Result<NaiveDateTime, EventError> {
+    let s = s.trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(s, "%Y%m%d").map(|d| NaiveDateTime::new(d, NaiveTime::MIN))
+        })
+        .map_err(|_| invalid(format!("invalid UNTIL {s}")))
+}
+
+fn parse_weekday(s: &str) -> Result<Weekday, EventError> {
+    Ok(match s {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return Err(invalid(format!("invalid weekday {s}"))),
+    })
+}
+
+fn weekday_str(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+/// number of days from `from` forward to the next `to`, 0 if they are equal
+fn days_between(from: Weekday, to: Weekday) -> i64 {
+    (to.num_days_from_monday() as i64 - from.num_days_from_monday() as i64).rem_euclid(7)
+}
+
+fn last_day_of_month(date: NaiveDate) -> NaiveDate {
+    let (year, month) = match date.month() {
+        12 => (date.year() + 1, 1),
+        m => (date.year(), m + 1),
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)
+        .and_then(|d| d.pred_opt())
+        .unwrap_or(NaiveDate::MAX)
+}
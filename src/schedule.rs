@@ -0,0 +1,277 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+
+use super::{Disambiguation, EventCalendar, EventError, EventTz, FreeBusy, Period};
+
+/// What kind of slot to look for with [`SlotQuery::find`], built up in the
+/// same way as an [`Event`](crate::Event)
+///
+/// # Examples
+/// ```
+/// use calib::{Disambiguation, Event, EventCalendar, SlotQuery};
+/// use chrono::{Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+///
+/// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+/// let hour = |h| NaiveTime::from_hms_opt(h, 0, 0).unwrap();
+/// let at = |h| date.and_time(hour(h));
+/// let meeting = |name: &str, start, end| {
+///     Event::new(name.into(), &date)
+///         .set_start(at(start))
+///         .and_then(|evt| evt.set_end(at(end)))
+///         .unwrap()
+/// };
+///
+/// let mut alice = EventCalendar::default();
+/// alice.add_event(meeting("Planning", 9, 11));
+/// let mut bob = EventCalendar::default();
+/// bob.add_event(meeting("Dentist", 11, 12));
+///
+/// let query = SlotQuery::new(Duration::hours(1))
+///     .unwrap()
+///     .set_count(2)
+///     .set_working_hours(hour(9), hour(17))
+///     .unwrap();
+/// let (start, end) = (Utc.from_utc_datetime(&at(0)), Utc.from_utc_datetime(&at(23)));
+/// let slots = query
+///     .find([&alice, &bob], &start, &end, Disambiguation::Compatible)
+///     .unwrap();
+/// let starts: Vec<_> = slots.iter().map(|slot| slot.start()).collect();
+/// assert_eq!(starts, vec![at(12), at(12) + Duration::minutes(15)]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotQuery {
+    duration: Duration,
+    count: usize,
+    step: Duration,
+    working_hours: Option<(NaiveTime, NaiveTime)>,
+    working_days: Vec<Weekday>,
+    buffer_before: Duration,
+    buffer_after: Duration,
+    preferred: Option<(NaiveTime, NaiveTime)>,
+}
+
+impl SlotQuery {
+    /// look for the earliest slot of the given length, starting on a quarter
+    /// hour, at any time of day
+    pub fn new(duration: Duration) -> Result<Self, EventError> {
+        if duration <= Duration::zero() {
+            return Err(EventError::InvalidProperty(
+                "slot duration",
+                duration.to_string(),
+            ));
+        }
+        Ok(SlotQuery {
+            duration,
+            count: 1,
+            step: Duration::minutes(15),
+            working_hours: None,
+            working_days: Vec::new(),
+            buffer_before: Duration::zero(),
+            buffer_after: Duration::zero(),
+            preferred: None,
+        })
+    }
+
+    /// returns how long each slot is
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Set/Change how many slots to find at most
+    pub fn set_count(self, count: usize) -> Self {
+        SlotQuery { count, ..self }
+    }
+
+    /// Set/Change the times slots may start at, a step of 30 minutes gives
+    /// slots starting on the hour and half hour. It must be whole seconds
+    pub fn set_step(self, step: Duration) -> Result<Self, EventError> {
+        if step < Duration::seconds(1) || step.subsec_nanos() != 0 {
+            return Err(EventError::InvalidProperty("slot step", step.to_string()));
+        }
+        Ok(SlotQuery { step, ..self })
+    }
+
+    /// Set/Change the hours of the day slots must fall within, a slot never
+    /// runs from one day's working hours into the next
+    pub fn set_working_hours(self, start: NaiveTime, end: NaiveTime) -> Result<Self, EventError> {
+        if end <= start {
+            return Err(EventError::InvalidProperty(
+                "working hours",
+                format!("{start}-{end}"),
+            ));
+        }
+        Ok(SlotQuery {
+            working_hours: Some((start, end)),
+            ..self
+        })
+    }
+
+    /// Set/Change the days of the week slots may fall on, every day when empty
+    pub fn set_working_days(self, days: &[Weekday]) -> Self {
+        SlotQuery {
+            working_days: days.to_vec(),
+            ..self
+        }
+    }
+
+    /// Set/Change how much free time a slot must leave before the next
+    /// event starts and after the last event ends
+    pub fn set_buffers(self, before: Duration, after: Duration) -> Self {
+        SlotQuery {
+            buffer_before: before.max(Duration::zero()),
+            buffer_after: after.max(Duration::zero()),
+            ..self
+        }
+    }
+
+    /// Set/Change the time of day slots should preferably fall within.
+    /// Slots inside it come first, then the others by how close they are
+    pub fn set_preferred(self, start: NaiveTime, end: NaiveTime) -> Result<Self, EventError> {
+        if end <= start {
+            return Err(EventError::InvalidProperty(
+                "preferred hours",
+                format!("{start}-{end}"),
+            ));
+        }
+        Ok(SlotQuery {
+            preferred: Some((start, end)),
+            ..self
+        })
+    }
+
+    /// return the slots within [start, end) of real time when every calendar
+    /// is free, as wall clock times in the zone of `start`. Working and
+    /// preferred hours are in that zone too, as are floating events, which
+    /// are read following `policy`. Without a preferred time of day these are
+    /// the earliest slots, with one they are ranked by it and then by how
+    /// early they are. Events that are transparent or cancelled do not block
+    /// a slot, and slots starting or ending at a wall clock time that a
+    /// daylight saving change skips or repeats are left out
+    pub fn find<'a, Z: TimeZone>(
+        &self,
+        calendars: impl IntoIterator<Item = &'a EventCalendar>,
+        start: &DateTime<Z>,
+        end: &DateTime<Z>,
+        policy: Disambiguation,
+    ) -> Result<Vec<Period>, EventError> {
+        let zone = start.timezone();
+        let local = |utc| {
+            Utc.from_utc_datetime(&utc)
+                .with_timezone(&zone)
+                .naive_local()
+        };
+        let (before, after) = (self.buffer_before, self.buffer_after);
+        // events just outside the window can still be too close to it
+        let (from, until) = start
+            .clone()
+            .checked_sub_signed(after)
+            .zip(end.clone().checked_add_signed(before))
+            .ok_or_else(|| {
+                EventError::InvalidProperty("slot buffers", format!("{before}, {after}"))
+            })?;
+        let mut busy = Vec::new();
+        for cal in calendars {
+            let fb = cal.free_busy_tz(&from, &until, policy)?;
+            busy.extend(fb.busy().iter().map(|p| {
+                (
+                    local(
+                        p.start()
+                            .checked_sub_signed(before)
+                            .unwrap_or(NaiveDateTime::MIN),
+                    ),
+                    local(
+                        p.end()
+                            .checked_add_signed(after)
+                            .unwrap_or(NaiveDateTime::MAX),
+                    ),
+                )
+            }));
+        }
+        let free = FreeBusy::new(
+            start.naive_local(),
+            end.naive_local(),
+            busy,
+            EventTz::Floating,
+        );
+
+        let exists = |slot: &Period| {
+            [slot.start(), slot.end()]
+                .iter()
+                .all(|dt| zone.from_local_datetime(dt).single().is_some())
+        };
+        let slots = free
+            .free()
+            .iter()
+            .flat_map(|period| self.working_periods(*period))
+            .flat_map(|period| self.slots_in(period))
+            .filter(exists);
+        Ok(match self.preferred {
+            None => slots.take(self.count).collect(),
+            Some(_) => {
+                let mut slots: Vec<_> = slots.collect();
+                slots.sort_by_key(|slot| (self.distance_from_preferred(slot), *slot));
+                slots.truncate(self.count);
+                slots
+            }
+        })
+    }
+
+    /// the parts of a free period within working hours on working days
+    fn working_periods(&self, period: Period) -> Vec<Period> {
+        if self.working_hours.is_none() && self.working_days.is_empty() {
+            return vec![period];
+        }
+        let (from, to) = self
+            .working_hours
+            .unwrap_or((NaiveTime::MIN, NaiveTime::MIN));
+        period
+            .start()
+            .date()
+            .iter_days()
+            .take_while(|day| day.and_time(from) < period.end())
+            .filter(|day| {
+                self.working_days.is_empty() || self.working_days.contains(&day.weekday())
+            })
+            .filter_map(|day| {
+                // without working hours the whole day, up to the next midnight
+                let end = match self.working_hours {
+                    Some(_) => day.and_time(to),
+                    None => day.succ_opt()?.and_time(NaiveTime::MIN),
+                };
+                Period::new(
+                    period.start().max(day.and_time(from)),
+                    period.end().min(end),
+                )
+            })
+            .collect()
+    }
+
+    /// every slot in a free period, starting on a step from midnight
+    fn slots_in(&self, period: Period) -> impl Iterator<Item = Period> + '_ {
+        let midnight = period.start().date().and_time(NaiveTime::MIN);
+        let steps = (period.start() - midnight).num_seconds();
+        let step = self.step.num_seconds();
+        let first =
+            midnight.checked_add_signed(Duration::seconds((steps + step - 1) / step * step));
+
+        std::iter::successors(first, move |start| start.checked_add_signed(self.step)).map_while(
+            move |start| {
+                let end = start.checked_add_signed(self.duration)?;
+                (end <= period.end())
+                    .then(|| Period::new(start, end))
+                    .flatten()
+            },
+        )
+    }
+
+    /// how far a slot is from the preferred time of day, 0 when inside it
+    fn distance_from_preferred(&self, slot: &Period) -> Duration {
+        let Some((from, to)) = self.preferred else {
+            return Duration::zero();
+        };
+        let day = slot.start().date();
+        let (from, to) = (day.and_time(from), day.and_time(to));
+        let early = from - slot.start();
+        let late = slot.end() - to;
+        early.max(late).max(Duration::zero())
+    }
+}
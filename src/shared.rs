@@ -0,0 +1,159 @@
+use chrono::NaiveDateTime;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use uuid::Uuid;
+
+use super::{Event, EventCalendar, EventError, IntoUuid, TryIntoUuid};
+
+/// A calendar that can be shared between threads. Clones are cheap and all
+/// refer to the same calendar, any number of threads may read it at once
+/// while writes take turns
+///
+/// # Examples
+/// ```
+/// use calib::{Event, SharedCalendar};
+/// use chrono::NaiveDate;
+/// use std::thread;
+///
+/// let cal = SharedCalendar::default();
+/// let handles: Vec<_> = (1..=3)
+///     .map(|day| {
+///         let cal = cal.clone();
+///         thread::spawn(move || {
+///             let date = NaiveDate::from_ymd_opt(2023, 1, day).unwrap();
+///             cal.add_event(Event::new(format!("Day {day}"), &date))
+///         })
+///     })
+///     .collect();
+/// for handle in handles {
+///     assert!(handle.join().unwrap());
+/// }
+///
+/// assert_eq!(cal.read().events().count(), 3);
+/// ```
+#[derive(Clone, Default)]
+pub struct SharedCalendar {
+    inner: Arc<RwLock<EventCalendar>>,
+}
+
+impl SharedCalendar {
+    /// lock the calendar for reading, giving access to the whole
+    /// [`EventCalendar`] API. Writers wait until the guard is dropped
+    pub fn read(&self) -> RwLockReadGuard<'_, EventCalendar> {
+        // writers only change the calendar with methods that check
+        // everything before they change it, or with edit, which puts back
+        // whatever a panicking closure changed. So the lock is only poisoned
+        // once the calendar is as it was before the panic
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// lock the calendar for writing, other readers and writers wait until
+    /// the guard is dropped
+    fn write(&self) -> RwLockWriteGuard<'_, EventCalendar> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// make several changes with `f` while the calendar is locked for
+    /// writing, as a single command, see [`EventCalendar::transaction`]. If
+    /// `f` fails or panics every change it made is undone first
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Event, SharedCalendar};
+    /// use chrono::NaiveDate;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let cal = SharedCalendar::default();
+    /// let failed = cal.edit(|cal| {
+    ///     cal.add_event(Event::new("Standup".into(), &date));
+    ///     cal.try_remove("not an id")
+    /// });
+    ///
+    /// assert!(failed.is_err());
+    /// assert_eq!(cal.read().events().count(), 0);
+    /// ```
+    pub fn edit<R, F>(&self, f: F) -> Result<R, EventError>
+    where
+        F: FnOnce(&mut EventCalendar) -> Result<R, EventError>,
+    {
+        let mut cal = self.write();
+        let mut panicked = None;
+        let result = cal.transaction(|cal| {
+            panic::catch_unwind(AssertUnwindSafe(|| f(cal))).unwrap_or_else(|payload| {
+                panicked = Some(payload);
+                // undone like any failure, then the panic carries on
+                Err(EventError::Storage("edit panicked".into()))
+            })
+        });
+        if let Some(payload) = panicked {
+            panic::resume_unwind(payload);
+        }
+        result
+    }
+
+    /// inserts event into the calendar, see [`EventCalendar::add_event`]
+    pub fn add_event(&self, event: Event) -> bool {
+        self.write().add_event(event)
+    }
+
+    /// inserts event into the calendar following its overlap policy, see
+    /// [`EventCalendar::try_add_event`]
+    pub fn try_add_event(&self, event: Event) -> Result<Vec<Uuid>, EventError> {
+        self.write().try_add_event(event)
+    }
+
+    /// removes an event from the calendar, see [`EventCalendar::remove`]
+    pub fn remove<T: IntoUuid>(&self, id: T) -> Option<Arc<Event>> {
+        self.write().remove(id)
+    }
+
+    /// removes an event given an id that may not be valid, see
+    /// [`EventCalendar::try_remove`]
+    pub fn try_remove<T: TryIntoUuid>(&self, id: T) -> Result<Arc<Event>, EventError> {
+        self.write().try_remove(id)
+    }
+
+    /// replaces the event with the given id, see [`EventCalendar::replace`]
+    pub fn replace<T: TryIntoUuid>(&self, id: T, event: Event) -> Result<Arc<Event>, EventError> {
+        self.write().replace(id, event)
+    }
+
+    /// edits the event with the given id, see [`EventCalendar::update`].
+    /// `f` runs while the calendar is locked for writing
+    pub fn update<T, F>(&self, id: T, f: F) -> Result<Arc<Event>, EventError>
+    where
+        T: TryIntoUuid,
+        F: FnOnce(Event) -> Result<Event, EventError>,
+    {
+        self.write().update(id, f)
+    }
+
+    /// return an event from it's ID
+    pub fn get<T: IntoUuid>(&self, id: T) -> Option<Arc<Event>> {
+        self.read().get(id).cloned()
+    }
+
+    /// return an event from an id that may not be valid, see
+    /// [`EventCalendar::try_get`]
+    pub fn try_get<T: TryIntoUuid>(&self, id: T) -> Result<Arc<Event>, EventError> {
+        self.read().try_get(id).cloned()
+    }
+
+    /// return every occurrence that overlaps the half-open range
+    /// [start, end) as a standalone event, see [`EventCalendar::events_in_range`]
+    /// and [`Occurrence::to_event`](crate::Occurrence::to_event)
+    pub fn events_in_range(&self, start: NaiveDateTime, end: NaiveDateTime) -> Vec<Event> {
+        self.read()
+            .events_in_range(start, end)
+            .map(|occ| occ.to_event())
+            .collect()
+    }
+}
+
+impl From<EventCalendar> for SharedCalendar {
+    fn from(cal: EventCalendar) -> Self {
+        SharedCalendar {
+            inner: Arc::new(RwLock::new(cal)),
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::Path;
+use uuid::Uuid;
+
+use super::{Change, Event, EventError, OverlapPolicy, Storage};
+
+/// The schema, one step per version. The database records how many of the
+/// steps it has had in its user_version, new steps go at the end
+const MIGRATIONS: &[&str] = &[
+    // items are stored as JSON, with the columns needed to find them
+    "CREATE TABLE events (
+        id TEXT NOT NULL,
+        recurrence_id TEXT NOT NULL,
+        start INTEGER NOT NULL,
+        span_end INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (id, recurrence_id)
+    );
+    CREATE INDEX events_start ON events (start);
+    CREATE INDEX events_span_end ON events (span_end);
+    CREATE TABLE tasks (id TEXT PRIMARY KEY, data TEXT NOT NULL);
+    CREATE TABLE journals (id TEXT PRIMARY KEY, data TEXT NOT NULL);",
+    // settings of the calendar, such as its overlap policy, by name
+    "CREATE TABLE settings (name TEXT PRIMARY KEY, data TEXT NOT NULL);",
+];
+
+/// the name the overlap policy is stored under in the settings table
+const POLICY_SETTING: &str = "overlap_policy";
+
+/// report an error from SQLite
+fn storage_error(err: rusqlite::Error) -> EventError {
+    EventError::Storage(err.to_string())
+}
+
+/// Storage in an embedded SQLite database, available with the `sqlite`
+/// feature. The schema is created or brought up to date when the database is
+/// opened and the start and end of events are indexed for range queries
+///
+/// # Examples
+/// ```
+/// use calib::{Event, PersistentCalendar, SqliteStorage, Storage};
+/// use chrono::{Duration, NaiveDate};
+///
+/// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+/// let storage = SqliteStorage::open_in_memory().unwrap();
+/// let mut cal = PersistentCalendar::open(storage).unwrap();
+/// cal.add_event(Event::new("Standup".into(), &date)).unwrap();
+///
+/// let start = date.and_hms_opt(0, 0, 0).unwrap();
+/// let found = cal.storage().events_in_range(start, start + Duration::days(1)).unwrap();
+/// assert_eq!(found[0].name(), "Standup");
+/// ```
+pub struct SqliteStorage {
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    /// open the database at `path`, creating it if it does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, EventError> {
+        Self::migrate(Connection::open(path).map_err(storage_error)?)
+    }
+
+    /// open a database that only lives as long as the storage
+    pub fn open_in_memory() -> Result<Self, EventError> {
+        Self::migrate(Connection::open_in_memory().map_err(storage_error)?)
+    }
+
+    /// returns the version of the schema the database has
+    pub fn schema_version(&self) -> Result<usize, EventError> {
+        let version: i64 = self
+            .conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(storage_error)?;
+        Ok(version as usize)
+    }
+
+    /// bring the schema up to date, failing for a database made by a newer
+    /// version of the library
+    fn migrate(mut conn: Connection) -> Result<Self, EventError> {
+        let tx = conn.transaction().map_err(storage_error)?;
+        let version: i64 = tx
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(storage_error)?;
+        let version = version as usize;
+        if version > MIGRATIONS.len() {
+            return Err(EventError::Storage(format!(
+                "database schema version {version} is newer than {}",
+                MIGRATIONS.len()
+            )));
+        }
+        for migration in &MIGRATIONS[version..] {
+            tx.execute_batch(migration).map_err(storage_error)?;
+        }
+        tx.pragma_update(None, "user_version", MIGRATIONS.len() as i64)
+            .map_err(storage_error)?;
+        tx.commit().map_err(storage_error)?;
+        Ok(SqliteStorage { conn })
+    }
+
+    /// make one change as part of a transaction
+    fn write(tx: &Transaction, change: &Change) -> Result<(), EventError> {
+        match change {
+            Change::PutEvent(evt) => {
+                // the start column holds the start of the first occurrence,
+                // which can be an extra one before the event's own start
+                tx.execute(
+                    "INSERT OR REPLACE INTO events (id, recurrence_id, start, span_end, data)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        evt.id().to_string(),
+                        recurrence_key(evt.recurrence_id()),
+                        micros(evt.span_start()),
+                        micros(evt.span_end()),
+                        to_json(evt)?,
+                    ],
+                )
+                .map_err(storage_error)?;
+            }
+            Change::RemoveEvent(id, recurrence_id) => {
+                tx.execute(
+                    "DELETE FROM events WHERE id = ?1 AND recurrence_id = ?2",
+                    params![id.to_string(), recurrence_key(*recurrence_id)],
+                )
+                .map_err(storage_error)?;
+            }
+            Change::PutTask(task) => {
+                tx.execute(
+                    "INSERT OR REPLACE INTO tasks (id, data) VALUES (?1, ?2)",
+                    params![task.id().to_string(), to_json(task)?],
+                )
+                .map_err(storage_error)?;
+            }
+            Change::RemoveTask(id) => {
+                tx.execute("DELETE FROM tasks WHERE id = ?1", [id.to_string()])
+                    .map_err(storage_error)?;
+            }
+            Change::PutJournal(journal) => {
+                tx.execute(
+                    "INSERT OR REPLACE INTO journals (id, data) VALUES (?1, ?2)",
+                    params![journal.id().to_string(), to_json(journal)?],
+                )
+                .map_err(storage_error)?;
+            }
+            Change::RemoveJournal(id) => {
+                tx.execute("DELETE FROM journals WHERE id = ?1", [id.to_string()])
+                    .map_err(storage_error)?;
+            }
+            Change::SetPolicy(policy) => {
+                tx.execute(
+                    "INSERT OR REPLACE INTO settings (name, data) VALUES (?1, ?2)",
+                    params![POLICY_SETTING, to_json(policy)?],
+                )
+                .map_err(storage_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// read every row of a query into items
+    fn read<T: DeserializeOwned>(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<T>, EventError> {
+        let mut stmt = self.conn.prepare(sql).map_err(storage_error)?;
+        let rows = stmt
+            .query_map(params, |row| row.get::<_, String>(0))
+            .map_err(storage_error)?;
+        rows.map(|data| from_json(&data.map_err(storage_error)?))
+            .collect()
+    }
+
+    /// return a stored event or override
+    pub fn get(
+        &self,
+        id: Uuid,
+        recurrence_id: Option<NaiveDateTime>,
+    ) -> Result<Option<Event>, EventError> {
+        let data: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT data FROM events WHERE id = ?1 AND recurrence_id = ?2",
+                params![id.to_string(), recurrence_key(recurrence_id)],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(storage_error)?;
+        data.map(|data| from_json(&data)).transpose()
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load(&self) -> Result<Vec<Change>, EventError> {
+        let policy: Vec<OverlapPolicy> = self.read(
+            "SELECT data FROM settings WHERE name = ?1",
+            [POLICY_SETTING],
+        )?;
+        let events = self.read("SELECT data FROM events", [])?;
+        let tasks = self.read("SELECT data FROM tasks", [])?;
+        let journals = self.read("SELECT data FROM journals", [])?;
+        Ok(policy
+            .into_iter()
+            .map(Change::SetPolicy)
+            .chain(events.into_iter().map(Change::PutEvent))
+            .chain(tasks.into_iter().map(Change::PutTask))
+            .chain(journals.into_iter().map(Change::PutJournal))
+            .collect())
+    }
+
+    fn commit(&mut self, changes: &[Change]) -> Result<(), EventError> {
+        let tx = self.conn.transaction().map_err(storage_error)?;
+        for change in changes {
+            Self::write(&tx, change)?;
+        }
+        tx.commit().map_err(storage_error)?;
+        Ok(())
+    }
+
+    fn events_in_range(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<Event>, EventError> {
+        let mut found: Vec<Event> = self.read(
+            "SELECT data FROM events WHERE start < ?2 AND span_end > ?1",
+            params![micros(start), micros(end)],
+        )?;
+        found.sort();
+        Ok(found)
+    }
+}
+
+/// the column value for a recurrence id, series have an empty one so they
+/// can be part of the primary key
+fn recurrence_key(recurrence_id: Option<NaiveDateTime>) -> String {
+    recurrence_id.map(|r| r.to_string()).unwrap_or_default()
+}
+
+/// a wall clock time as microseconds, which keeps times in order for the
+/// whole range of NaiveDateTime
+fn micros(dt: NaiveDateTime) -> i64 {
+    dt.and_utc().timestamp_micros()
+}
+
+fn to_json<T: Serialize>(item: &T) -> Result<String, EventError> {
+    serde_json::to_string(item).map_err(|e| EventError::Json(e.to_string()))
+}
+
+fn from_json<T: DeserializeOwned>(data: &str) -> Result<T, EventError> {
+    serde_json::from_str(data).map_err(|e| EventError::Json(e.to_string()))
+}
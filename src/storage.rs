@@ -0,0 +1,302 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use super::{Event, EventCalendar, EventError, Journal, OverlapPolicy, Task, TryIntoUuid};
+
+/// A single edit to the items of a calendar. Events are identified by their
+/// id and recurrence id, so the override of an occurrence is stored apart
+/// from its series
+// changes are short lived, so boxing events is not worth the extra step
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    /// add or replace an event, or the override of an occurrence if it has
+    /// a recurrence id
+    PutEvent(Event),
+    /// remove an event, or only the override of one occurrence of it
+    RemoveEvent(Uuid, Option<NaiveDateTime>),
+    /// add or replace a task
+    PutTask(Task),
+    /// remove a task
+    RemoveTask(Uuid),
+    /// add or replace a journal entry
+    PutJournal(Journal),
+    /// remove a journal entry
+    RemoveJournal(Uuid),
+    /// set the overlap policy of the calendar, which is not part of the
+    /// edits that are undone and redone
+    SetPolicy(OverlapPolicy),
+}
+
+/// What a [`Change`] is made to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Key {
+    Event(Uuid, Option<NaiveDateTime>),
+    Task(Uuid),
+    Journal(Uuid),
+}
+
+impl Key {
+    /// the change that removes what the key refers to
+    pub(crate) fn removal(self) -> Change {
+        match self {
+            Key::Event(id, recurrence_id) => Change::RemoveEvent(id, recurrence_id),
+            Key::Task(id) => Change::RemoveTask(id),
+            Key::Journal(id) => Change::RemoveJournal(id),
+        }
+    }
+}
+
+/// Somewhere the items of a calendar are kept, so they outlive the program.
+/// A [`PersistentCalendar`] reads everything back when it is opened and
+/// commits the changes of each edit
+pub trait Storage {
+    /// returns a change putting back every stored event, override, task and
+    /// journal entry, and the overlap policy if one was stored
+    fn load(&self) -> Result<Vec<Change>, EventError>;
+
+    /// make every change or, if any of them fails, none of them
+    fn commit(&mut self, changes: &[Change]) -> Result<(), EventError>;
+
+    /// returns the stored events and overrides with an occurrence that may
+    /// overlap [start, end), in the order they start. This reads the storage
+    /// directly, a [`PersistentCalendar`] answers its queries from memory
+    fn events_in_range(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<Event>, EventError>;
+}
+
+/// Storage that keeps everything in memory, so nothing is kept once it is
+/// dropped
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    events: BTreeMap<(Uuid, Option<NaiveDateTime>), Event>,
+    tasks: BTreeMap<Uuid, Task>,
+    journals: BTreeMap<Uuid, Journal>,
+    policy: Option<OverlapPolicy>,
+}
+
+impl Storage for MemoryStorage {
+    fn load(&self) -> Result<Vec<Change>, EventError> {
+        let policy = self.policy.map(Change::SetPolicy);
+        let events = self.events.values().cloned().map(Change::PutEvent);
+        let tasks = self.tasks.values().cloned().map(Change::PutTask);
+        let journals = self.journals.values().cloned().map(Change::PutJournal);
+        Ok(policy
+            .into_iter()
+            .chain(events)
+            .chain(tasks)
+            .chain(journals)
+            .collect())
+    }
+
+    fn commit(&mut self, changes: &[Change]) -> Result<(), EventError> {
+        for change in changes {
+            match change.clone() {
+                Change::PutEvent(evt) => {
+                    self.events.insert((*evt.id(), evt.recurrence_id()), evt);
+                }
+                Change::RemoveEvent(id, recurrence_id) => {
+                    self.events.remove(&(id, recurrence_id));
+                }
+                Change::PutTask(task) => {
+                    self.tasks.insert(*task.id(), task);
+                }
+                Change::RemoveTask(id) => {
+                    self.tasks.remove(&id);
+                }
+                Change::PutJournal(journal) => {
+                    self.journals.insert(*journal.id(), journal);
+                }
+                Change::RemoveJournal(id) => {
+                    self.journals.remove(&id);
+                }
+                Change::SetPolicy(policy) => {
+                    self.policy = Some(policy);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn events_in_range(
+        &self,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<Event>, EventError> {
+        let mut found: Vec<_> = self
+            .events
+            .values()
+            .filter(|evt| evt.span_start() < end && evt.span_end() > start)
+            .cloned()
+            .collect();
+        found.sort();
+        Ok(found)
+    }
+}
+
+/// A calendar backed by [`Storage`]. The storage is only written through:
+/// everything in it is read into memory when the calendar is opened, every
+/// query, ranges included, is answered from memory and every edit is
+/// committed to the storage as a single transaction
+///
+/// # Examples
+/// ```
+/// use calib::{Event, MemoryStorage, PersistentCalendar};
+/// use chrono::NaiveDate;
+///
+/// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+/// let mut cal = PersistentCalendar::open(MemoryStorage::default()).unwrap();
+/// cal.add_event(Event::new("Standup".into(), &date)).unwrap();
+///
+/// // opening the storage again finds the event
+/// let cal = PersistentCalendar::open(cal.into_storage()).unwrap();
+/// assert_eq!(cal.calendar().first_event().unwrap().name(), "Standup");
+/// ```
+pub struct PersistentCalendar<S: Storage> {
+    cal: EventCalendar,
+    storage: S,
+}
+
+impl<S: Storage> PersistentCalendar<S> {
+    /// read a calendar back from storage
+    pub fn open(storage: S) -> Result<Self, EventError> {
+        let mut cal = EventCalendar::default();
+        for change in storage.load()? {
+            cal.restore(change);
+        }
+        Ok(PersistentCalendar { cal, storage })
+    }
+
+    /// returns the calendar in memory, for queries
+    pub fn calendar(&self) -> &EventCalendar {
+        &self.cal
+    }
+
+    /// returns the storage behind the calendar
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// returns the storage behind the calendar for changes that do not go
+    /// through the calendar, such as settings of the storage itself
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    /// close the calendar, returning its storage
+    pub fn into_storage(self) -> S {
+        self.storage
+    }
+
+    /// edit the calendar with `f`, then commit everything it changed to the
+    /// storage at once. If `f` or the commit fails the calendar is put back
+    /// as it was and nothing is stored. The edit is undone as a whole, see
+    /// [`EventCalendar::transaction`]. The overlap policy is only stored
+    /// when it is set with [`PersistentCalendar::set_policy`]
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{Event, EventError, MemoryStorage, PersistentCalendar};
+    /// use chrono::NaiveDate;
+    ///
+    /// let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+    /// let mut cal = PersistentCalendar::open(MemoryStorage::default()).unwrap();
+    /// let failed = cal.edit(|cal| {
+    ///     cal.add_event(Event::new("Standup".into(), &date));
+    ///     cal.try_remove("not an id")
+    /// });
+    ///
+    /// assert!(matches!(failed, Err(EventError::InvalidUuid(..))));
+    /// assert_eq!(cal.calendar().events().count(), 0);
+    /// ```
+    pub fn edit<F, R>(&mut self, f: F) -> Result<R, EventError>
+    where
+        F: FnOnce(&mut EventCalendar) -> Result<R, EventError>,
+    {
+        let storage = &mut self.storage;
+        self.cal.transaction(|cal| {
+            let result = f(cal)?;
+            storage.commit(&cal.pending_changes())?;
+            Ok(result)
+        })
+    }
+
+    /// Set/Change the overlap policy of the calendar and store it, see
+    /// [`EventCalendar::set_policy`]. If the storage fails the policy stays
+    /// as it was
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::{MemoryStorage, OverlapPolicy, PersistentCalendar};
+    ///
+    /// let mut cal = PersistentCalendar::open(MemoryStorage::default()).unwrap();
+    /// cal.set_policy(OverlapPolicy::Reject).unwrap();
+    ///
+    /// let cal = PersistentCalendar::open(cal.into_storage()).unwrap();
+    /// assert_eq!(cal.calendar().policy(), OverlapPolicy::Reject);
+    /// ```
+    pub fn set_policy(&mut self, policy: OverlapPolicy) -> Result<(), EventError> {
+        self.storage.commit(&[Change::SetPolicy(policy)])?;
+        self.cal.set_policy(policy);
+        Ok(())
+    }
+
+    /// undo the latest edit in the calendar and the storage, see
+    /// [`EventCalendar::undo`]. If the storage fails the edit stays
+    pub fn undo(&mut self) -> Result<bool, EventError> {
+        let Some(changes) = self.cal.undo_changes() else {
+            return Ok(false);
+        };
+        if let Err(err) = self.storage.commit(&changes) {
+            self.cal.redo_changes();
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    /// make the latest undone edit again in the calendar and the storage,
+    /// see [`EventCalendar::redo`]. If the storage fails it stays undone
+    pub fn redo(&mut self) -> Result<bool, EventError> {
+        let Some(changes) = self.cal.redo_changes() else {
+            return Ok(false);
+        };
+        if let Err(err) = self.storage.commit(&changes) {
+            self.cal.undo_changes();
+            return Err(err);
+        }
+        Ok(true)
+    }
+
+    /// inserts event into the calendar, see [`EventCalendar::add_event`]
+    pub fn add_event(&mut self, event: Event) -> Result<bool, EventError> {
+        self.edit(|cal| Ok(cal.add_event(event)))
+    }
+
+    /// inserts event into the calendar following its overlap policy, see
+    /// [`EventCalendar::try_add_event`]
+    pub fn try_add_event(&mut self, event: Event) -> Result<Vec<Uuid>, EventError> {
+        self.edit(|cal| cal.try_add_event(event))
+    }
+
+    /// removes an event and its overrides from the calendar, see
+    /// [`EventCalendar::try_remove`]
+    pub fn remove<T: TryIntoUuid>(&mut self, id: T) -> Result<Arc<Event>, EventError> {
+        self.edit(|cal| cal.try_remove(id))
+    }
+
+    /// edits the event with the given id, see [`EventCalendar::update`]
+    pub fn update<T, F>(&mut self, id: T, f: F) -> Result<Arc<Event>, EventError>
+    where
+        T: TryIntoUuid,
+        F: FnOnce(Event) -> Result<Event, EventError>,
+    {
+        self.edit(|cal| cal.update(id, f))
+    }
+}
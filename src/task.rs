@@ -0,0 +1,388 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use super::{props, EventError, EventTz};
+
+/// How far along a task is, the STATUS of a VTODO
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum TaskStatus {
+    /// not started (the default)
+    #[default]
+    NeedsAction,
+    InProcess,
+    Completed,
+    Cancelled,
+}
+
+impl TaskStatus {
+    /// returns the name RFC 5545 uses for the status
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TaskStatus::NeedsAction => "NEEDS-ACTION",
+            TaskStatus::InProcess => "IN-PROCESS",
+            TaskStatus::Completed => "COMPLETED",
+            TaskStatus::Cancelled => "CANCELLED",
+        }
+    }
+
+    /// returns true if nothing more will be done, the task is completed or
+    /// cancelled
+    pub fn is_closed(&self) -> bool {
+        matches!(self, TaskStatus::Completed | TaskStatus::Cancelled)
+    }
+}
+
+impl fmt::Display for TaskStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TaskStatus {
+    type Err = EventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "NEEDS-ACTION" => Ok(TaskStatus::NeedsAction),
+            "IN-PROCESS" => Ok(TaskStatus::InProcess),
+            "COMPLETED" => Ok(TaskStatus::Completed),
+            "CANCELLED" => Ok(TaskStatus::Cancelled),
+            _ => Err(EventError::InvalidProperty("STATUS", s.to_string())),
+        }
+    }
+}
+
+/// the highest PERCENT-COMPLETE
+const MAX_PERCENT: u8 = 100;
+
+/// Something to get done, like a VTODO. Times are wall clock times in the
+/// task's zone just as they are for an [`Event`](crate::Event)
+///
+/// # Examples
+/// ```
+/// use calib::{Task, TaskStatus};
+/// use chrono::{NaiveDate, TimeZone, Utc};
+///
+/// let due = NaiveDate::from_ymd_opt(2023, 1, 6).unwrap().and_hms_opt(17, 0, 0).unwrap();
+/// let report = Task::new("Write report".into())
+///     .set_due(Some(due))
+///     .unwrap()
+///     .set_percent_complete(40)
+///     .unwrap();
+/// assert!(report.is_overdue(due));
+/// assert!(!report.is_overdue(due - chrono::Duration::hours(1)));
+///
+/// let report = report.complete(Utc.with_ymd_and_hms(2023, 1, 6, 16, 0, 0).unwrap());
+/// assert_eq!(report.status(), TaskStatus::Completed);
+/// assert_eq!(report.percent_complete(), 100);
+/// assert!(!report.is_overdue(due));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "TaskFields")]
+pub struct Task {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due: Option<NaiveDateTime>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start: Option<NaiveDateTime>,
+    name: String,
+    id: Uuid,
+    #[serde(skip_serializing_if = "EventTz::is_floating")]
+    tz: EventTz,
+    percent_complete: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completed: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<u8>,
+    status: TaskStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    // start and due date are midnights and only their days matter
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    all_day: bool,
+}
+
+/// The fields of a task as they are deserialized, before they have been
+/// checked
+#[derive(Deserialize)]
+struct TaskFields {
+    #[serde(default)]
+    due: Option<NaiveDateTime>,
+    #[serde(default)]
+    start: Option<NaiveDateTime>,
+    name: String,
+    id: Uuid,
+    #[serde(default)]
+    tz: EventTz,
+    #[serde(default)]
+    percent_complete: u8,
+    #[serde(default)]
+    completed: Option<DateTime<Utc>>,
+    #[serde(default)]
+    priority: Option<u8>,
+    #[serde(default)]
+    status: TaskStatus,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    all_day: bool,
+}
+
+impl TryFrom<TaskFields> for Task {
+    type Error = EventError;
+
+    fn try_from(fields: TaskFields) -> Result<Self, Self::Error> {
+        Task::new(fields.name)
+            .with_id(fields.id)
+            .set_tz(fields.tz)
+            .set_due(fields.due)?
+            .set_start(fields.start)?
+            .set_percent_complete(fields.percent_complete)?
+            .set_priority(fields.priority)
+            .map(|task| Task {
+                completed: fields.completed,
+                status: fields.status,
+                description: fields.description,
+                all_day: fields.all_day
+                    && [task.start, task.due]
+                        .iter()
+                        .flatten()
+                        .all(|dt| dt.time() == NaiveTime::MIN),
+                ..task
+            })
+    }
+}
+
+impl Task {
+    /// create a task that has not been started, with no due date
+    pub fn new(name: String) -> Self {
+        Task {
+            due: None,
+            start: None,
+            name,
+            id: Uuid::new_v4(),
+            tz: EventTz::Floating,
+            percent_complete: 0,
+            completed: None,
+            priority: None,
+            status: TaskStatus::NeedsAction,
+            description: None,
+            all_day: false,
+        }
+    }
+
+    /// returns the name of the task
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Change the name of the task
+    pub fn set_name(self, name: String) -> Self {
+        Task { name, ..self }
+    }
+
+    /// returns the unique id of the task
+    pub fn id(&self) -> &Uuid {
+        &self.id
+    }
+
+    /// returns when the task has to be done by
+    pub fn due(&self) -> Option<NaiveDateTime> {
+        self.due
+    }
+
+    /// Set/Change/Clear when the task has to be done by, it must be after
+    /// the start if there is one. A task with dates becomes one with times
+    pub fn set_due(self, due: Option<NaiveDateTime>) -> Result<Self, EventError> {
+        if matches!((self.start, due), (Some(start), Some(due)) if due <= start) {
+            return Err(EventError::InvalidEndTime);
+        }
+        Ok(Task {
+            due,
+            all_day: false,
+            ..self
+        })
+    }
+
+    /// returns when work on the task can start
+    pub fn start(&self) -> Option<NaiveDateTime> {
+        self.start
+    }
+
+    /// Set/Change/Clear when work on the task can start, it must be before
+    /// the due date if there is one. A task with dates becomes one with times
+    pub fn set_start(self, start: Option<NaiveDateTime>) -> Result<Self, EventError> {
+        if matches!((start, self.due), (Some(start), Some(due)) if due <= start) {
+            return Err(EventError::InvalidStartTime);
+        }
+        Ok(Task {
+            start,
+            all_day: false,
+            ..self
+        })
+    }
+
+    /// returns true if the task starts and is due on days rather than at
+    /// times of day
+    pub fn is_all_day(&self) -> bool {
+        self.all_day
+    }
+
+    /// Set/Change/Clear the days work on the task can start and it is due
+    /// on, without times of day. The due date must be after the start if
+    /// there are both
+    ///
+    /// # Examples
+    /// ```
+    /// use calib::Task;
+    /// use chrono::NaiveDate;
+    ///
+    /// let date = |d| NaiveDate::from_ymd_opt(2023, 1, d).unwrap();
+    /// let rent = Task::new("Pay rent".into())
+    ///     .set_dates(None, Some(date(31)))
+    ///     .unwrap();
+    /// assert!(rent.is_all_day());
+    /// assert_eq!(rent.due(), date(31).and_hms_opt(0, 0, 0));
+    /// ```
+    pub fn set_dates(
+        self,
+        start: Option<NaiveDate>,
+        due: Option<NaiveDate>,
+    ) -> Result<Self, EventError> {
+        if matches!((start, due), (Some(start), Some(due)) if due <= start) {
+            return Err(EventError::InvalidEndTime);
+        }
+        let midnight = |date: NaiveDate| NaiveDateTime::new(date, NaiveTime::MIN);
+        Ok(Task {
+            start: start.map(midnight),
+            due: due.map(midnight),
+            all_day: start.is_some() || due.is_some(),
+            ..self
+        })
+    }
+
+    /// returns the time zone the start and due date are written in
+    pub fn tz(&self) -> EventTz {
+        self.tz
+    }
+
+    /// Set/Change the time zone of the task, keeping its wall clock times
+    pub fn set_tz(self, tz: EventTz) -> Self {
+        Task { tz, ..self }
+    }
+
+    /// returns how much of the task is done, from 0 to 100 percent
+    pub fn percent_complete(&self) -> u8 {
+        self.percent_complete
+    }
+
+    /// Set/Change how much of the task is done, from 0 to 100 percent
+    pub fn set_percent_complete(self, percent_complete: u8) -> Result<Self, EventError> {
+        if percent_complete > MAX_PERCENT {
+            return Err(EventError::InvalidProperty(
+                "PERCENT-COMPLETE",
+                percent_complete.to_string(),
+            ));
+        }
+        Ok(Task {
+            percent_complete,
+            ..self
+        })
+    }
+
+    /// returns when the task was completed
+    pub fn completed(&self) -> Option<DateTime<Utc>> {
+        self.completed
+    }
+
+    /// returns true once the task has been completed
+    pub fn is_completed(&self) -> bool {
+        self.status == TaskStatus::Completed
+    }
+
+    /// mark the task as completed at `at`
+    pub fn complete(self, at: DateTime<Utc>) -> Self {
+        Task {
+            completed: Some(at),
+            percent_complete: MAX_PERCENT,
+            status: TaskStatus::Completed,
+            ..self
+        }
+    }
+
+    /// mark a completed task as needing action again, keeping how much of
+    /// it was done unless it was all of it
+    pub fn reopen(self) -> Self {
+        let percent_complete = match self.percent_complete {
+            MAX_PERCENT => 0,
+            percent => percent,
+        };
+        Task {
+            completed: None,
+            percent_complete,
+            status: TaskStatus::NeedsAction,
+            ..self
+        }
+    }
+
+    /// returns true if the task is still open at `now` after it was due
+    pub fn is_overdue(&self, now: NaiveDateTime) -> bool {
+        !self.status.is_closed() && self.due.is_some_and(|due| due <= now)
+    }
+
+    /// returns the priority of the task, from 1 (highest) to 9 (lowest)
+    pub fn priority(&self) -> Option<u8> {
+        self.priority
+    }
+
+    /// Set/Change/Clear the priority of the task, 0 means no priority
+    pub fn set_priority(self, priority: Option<u8>) -> Result<Self, EventError> {
+        if let Some(priority) = priority {
+            props::check_priority(priority)?;
+        }
+        Ok(Task {
+            priority: priority.filter(|p| *p != 0),
+            ..self
+        })
+    }
+
+    /// returns how far along the task is
+    pub fn status(&self) -> TaskStatus {
+        self.status
+    }
+
+    /// Set/Change how far along the task is, use [`Task::complete`] to
+    /// record when it was completed
+    pub fn set_status(self, status: TaskStatus) -> Self {
+        Task { status, ..self }
+    }
+
+    /// returns the longer description of the task
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Set/Change/Clear the longer description of the task
+    pub fn set_description(self, description: Option<String>) -> Self {
+        Task {
+            description,
+            ..self
+        }
+    }
+
+    /// give the task a specific id, for tasks read back from storage
+    pub(crate) fn with_id(self, id: Uuid) -> Self {
+        Task { id, ..self }
+    }
+
+    /// record when the task was completed without changing its status, as
+    /// when reading it back from iCalendar text
+    pub(crate) fn with_completed(self, completed: Option<DateTime<Utc>>) -> Self {
+        Task { completed, ..self }
+    }
+}
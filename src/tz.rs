@@ -0,0 +1,170 @@
+use chrono::{DateTime, Duration, LocalResult, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use super::EventError;
+
+/// The time zone an event's start and end are written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EventTz {
+    /// wall clock time that means the same local time wherever it is read,
+    /// like an alarm at 07:00 (the default)
+    #[default]
+    Floating,
+    /// times are in UTC
+    Utc,
+    /// times are wall clock time in an IANA time zone
+    Zone(Tz),
+}
+
+/// How to turn a wall clock time that does not map to exactly one instant,
+/// because of a daylight saving transition, into an instant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Disambiguation {
+    /// fail with [`EventError::AmbiguousTime`] or [`EventError::NonexistentTime`]
+    Reject,
+    /// pick the earlier instant, times in a gap move back by the gap's length
+    Earlier,
+    /// pick the later instant, times in a gap move forward by the gap's length
+    Later,
+    /// what RFC 5545 asks for, the earlier instant when the clocks go back
+    /// and forward by the gap's length when they go forward (the default)
+    #[default]
+    Compatible,
+}
+
+impl EventTz {
+    /// returns the name of the zone, "UTC" or "floating"
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventTz::Floating => "floating",
+            EventTz::Utc => "UTC",
+            EventTz::Zone(tz) => tz.name(),
+        }
+    }
+
+    /// returns true for floating time
+    pub fn is_floating(&self) -> bool {
+        *self == EventTz::Floating
+    }
+
+    /// returns the instant a wall clock time in this zone refers to, floating
+    /// times are read as wall clock time in `floating`
+    pub fn to_utc<Z: TimeZone>(
+        &self,
+        local: NaiveDateTime,
+        floating: &Z,
+        policy: Disambiguation,
+    ) -> Result<DateTime<Utc>, EventError> {
+        match self {
+            EventTz::Floating => {
+                resolve(floating, local, policy, self.name()).map(|dt| dt.to_utc())
+            }
+            EventTz::Utc => Ok(Utc.from_utc_datetime(&local)),
+            EventTz::Zone(tz) => resolve(tz, local, policy, self.name()).map(|dt| dt.to_utc()),
+        }
+    }
+
+    /// returns the wall clock time in this zone at an instant, floating
+    /// times are given as wall clock time in `floating`
+    pub fn from_utc<Z: TimeZone>(&self, instant: &DateTime<Utc>, floating: &Z) -> NaiveDateTime {
+        match self {
+            EventTz::Floating => instant.with_timezone(floating).naive_local(),
+            EventTz::Utc => instant.naive_utc(),
+            EventTz::Zone(tz) => instant.with_timezone(tz).naive_local(),
+        }
+    }
+}
+
+impl fmt::Display for EventTz {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for EventTz {
+    type Err = EventError;
+
+    /// parses an IANA name, "UTC"/"Z", or "floating"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "floating" => Ok(EventTz::Floating),
+            "UTC" | "Z" | "Etc/UTC" => Ok(EventTz::Utc),
+            name => name
+                .parse()
+                .map(EventTz::Zone)
+                .map_err(|_| EventError::UnknownTimeZone(name.to_string())),
+        }
+    }
+}
+
+impl From<Tz> for EventTz {
+    fn from(tz: Tz) -> Self {
+        EventTz::Zone(tz)
+    }
+}
+
+// zones are ordered by name so Event can keep deriving Ord
+impl PartialOrd for EventTz {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EventTz {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name().cmp(other.name())
+    }
+}
+
+impl Serialize for EventTz {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for EventTz {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// the instant wall clock time `local` refers to in `zone`
+fn resolve<Z: TimeZone>(
+    zone: &Z,
+    local: NaiveDateTime,
+    policy: Disambiguation,
+    name: &str,
+) -> Result<DateTime<Z>, EventError> {
+    match (zone.from_local_datetime(&local), policy) {
+        (LocalResult::Single(dt), _) => Ok(dt),
+        (LocalResult::Ambiguous(..), Disambiguation::Reject) => {
+            Err(EventError::AmbiguousTime(local, name.to_string()))
+        }
+        (
+            LocalResult::Ambiguous(earlier, _),
+            Disambiguation::Earlier | Disambiguation::Compatible,
+        ) => Ok(earlier),
+        (LocalResult::Ambiguous(_, later), Disambiguation::Later) => Ok(later),
+        (LocalResult::None, Disambiguation::Reject) => {
+            Err(EventError::NonexistentTime(local, name.to_string()))
+        }
+        (LocalResult::None, policy) => {
+            // read the time with the offset from either side of the gap, the
+            // offset from before it lands as far past the gap as the time was
+            // into it, the offset from after lands as far before
+            let offset_at = |dt: NaiveDateTime| zone.offset_from_utc_datetime(&dt).fix();
+            let offset = match policy {
+                Disambiguation::Earlier => offset_at(local + Duration::days(1)),
+                _ => offset_at(local - Duration::days(1)),
+            };
+            let instant = local - Duration::seconds(offset.local_minus_utc() as i64);
+            Ok(zone.from_utc_datetime(&instant))
+        }
+    }
+}